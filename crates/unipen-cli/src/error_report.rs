@@ -0,0 +1,63 @@
+use std::path::Path;
+
+use pest::error::LineColLocation;
+use serde::Serialize;
+use unipen::error::UniPenError;
+
+/// A `UniPenError` in a form that can be serialized for tooling.
+#[derive(Serialize)]
+pub struct ErrorReport {
+    /// The name of the error variant, e.g. `PestRule`.
+    pub kind: &'static str,
+    pub message: String,
+    /// The file the error occurred in. Falls back to the file passed to the CLI when the error does not name one.
+    pub path: String,
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+    /// The exit code of the CLI, which is distinct for each kind of error.
+    #[serde(skip)]
+    pub exit_code: i32,
+}
+
+impl ErrorReport {
+    #[must_use]
+    pub fn new(error: &UniPenError, file: &Path) -> Self {
+        let (kind, exit_code) = match error {
+            UniPenError::Io(_) => ("Io", 10),
+            UniPenError::Translation(_) => ("Translation", 11),
+            UniPenError::Validation(_) => ("Validation", 12),
+            UniPenError::ParseFloat(_) => ("ParseFloat", 13),
+            UniPenError::ParseInt(_) => ("ParseInt", 14),
+            UniPenError::PestRule(_) => ("PestRule", 15),
+            UniPenError::MissingInclude => ("MissingInclude", 16),
+            UniPenError::InvalidUtf8 { .. } => ("InvalidUtf8", 17),
+            UniPenError::IncludeDepth(_) => ("IncludeDepth", 18),
+            UniPenError::Serialize(_) => ("Serialize", 19),
+            UniPenError::FileTooLarge { .. } => ("FileTooLarge", 20),
+        };
+        let mut report = Self {
+            kind,
+            message: error.to_string(),
+            path: file.display().to_string(),
+            line: None,
+            column: None,
+            exit_code,
+        };
+        match error {
+            UniPenError::PestRule(error) => {
+                // The message of the error itself, without the excerpt of the source
+                report.message = error.variant.message().into_owned();
+                if let Some(path) = error.path() {
+                    report.path = path.to_owned();
+                }
+                let (LineColLocation::Pos((line, column)) | LineColLocation::Span((line, column), _)) = error.line_col;
+                report.line = Some(line);
+                report.column = Some(column);
+            }
+            UniPenError::InvalidUtf8 { name, .. } => report.path.clone_from(name),
+            UniPenError::FileTooLarge { path, .. } => report.path.clone_from(path),
+            _ => {}
+        }
+        report
+    }
+}
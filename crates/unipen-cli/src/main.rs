@@ -1,38 +1,216 @@
 extern crate unipen;
 
-use std::env;
-use std::path::Path;
+mod error_report;
 
-use unipen::statements;
+use std::collections::HashSet;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
 
-fn main() -> Result<(), &'static str> {
-    // Get the command line arguments.
-    let mut args = env::args();
+use clap::{error::ErrorKind, CommandFactory, Parser, Subcommand, ValueEnum};
+use error_report::ErrorReport;
+use unipen::builder::unipen::UniPenBuilder;
+use unipen::error::UniPenError;
+use unipen::model::{Component, ComponentSet, UniPen};
+use unipen::statements::{self, FileSystemResolver, IncludeResolver, Statement};
+use unipen::svg::SvgOptions;
+
+/// Parses a UniPen file and prints its statements.
+#[derive(Parser)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// The UniPen file to parse, or `-` to read from stdin.
+    #[arg(required = true)]
+    file: Option<PathBuf>,
+    /// The directory included files are resolved against when they are not next to the including file.
+    include_dir: Option<PathBuf>,
+    /// The format the statements are printed in.
+    #[arg(long, value_enum, default_value_t = Format::Json)]
+    format: Format,
+    /// The format errors are printed to stderr in.
+    #[arg(long, value_enum, global = true, default_value_t = ErrorFormat::Text)]
+    error_format: ErrorFormat,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Draws each component set as SVG.
+    Svg {
+        /// The UniPen file to draw, or `-` to read from stdin.
+        file: PathBuf,
+        /// The directory one SVG file per component set is written to. All sets are drawn into one SVG on stdout if
+        /// omitted.
+        out_dir: Option<PathBuf>,
+        /// The directory included files are resolved against when they are not next to the including file.
+        #[arg(long)]
+        include_dir: Option<PathBuf>,
+        /// Flips the Y axis, for data with its origin at the bottom left.
+        #[arg(long)]
+        flip_y: bool,
+        /// The stroke width, in coordinate units.
+        #[arg(long, default_value_t = 1.0)]
+        stroke_width: f64,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    Json,
+    JsonPretty,
+    Yaml,
+    /// UniPen source text, with included files inlined.
+    Unipen,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ErrorFormat {
+    Text,
+    /// A JSON object with the kind, message, path, line, and column of the error. The exit code is distinct for each
+    /// kind of error.
+    Json,
+}
+
+fn report_error(error: &UniPenError, file: &Path, error_format: ErrorFormat) -> &'static str {
+    match error_format {
+        ErrorFormat::Text => {
+            eprintln!("{error}");
+            "Error occured during parsing."
+        }
+        ErrorFormat::Json => {
+            let report = ErrorReport::new(error, file);
+            match serde_json::to_string(&report) {
+                Ok(json) => eprintln!("{json}"),
+                // The report is only strings and numbers, but fall back to the text of the error rather than panicking
+                Err(_) => eprintln!("{error}"),
+            }
+            std::process::exit(report.exit_code);
+        }
+    }
+}
+
+fn parse(file: &Path, include_dir: Option<&Path>) -> Result<Vec<Statement>, UniPenError> {
+    if file.as_os_str() != "-" {
+        return statements::parse(file, include_dir);
+    }
+    let mut content = String::new();
+    io::stdin().read_to_string(&mut content)?;
+    let resolver = include_dir.map(FileSystemResolver::new);
+    statements::parse_str(
+        "<stdin>",
+        &content,
+        resolver.as_ref().map(|resolver| resolver as &dyn IncludeResolver),
+    )
+}
 
-    // Skip the first argument, which is the program name.
-    args.next();
+// Draws all sets into one set, as they share the coordinate space of the tablet
+fn merge_sets(sets: &[ComponentSet]) -> ComponentSet {
+    let mut coordinates = Vec::new();
+    let mut components = Vec::new();
+    for set in sets {
+        let offset = coordinates.len();
+        let shift = |range: &RangeInclusive<usize>| range.start() + offset..=range.end() + offset;
+        components.extend(set.components.iter().map(|component| match component {
+            Component::PenDown(range) => Component::PenDown(shift(range)),
+            Component::PenUp(range) => Component::PenUp(shift(range)),
+            Component::Dt(dt) => Component::Dt(*dt),
+        }));
+        coordinates.extend(set.coordinates.iter().cloned());
+    }
+    ComponentSet {
+        name: "".into(),
+        coordinates: coordinates.into(),
+        components: components.into(),
+        segments: Rc::new([]),
+        bounding_boxes: Rc::new([]),
+        writer_id: None,
+        style: None,
+    }
+}
 
-    // Get the path to the file to parse.
-    let Some(path_str) = args.next() else {
-        return Err("Usage: unipen <file> [include-dir]");
+fn write_svg(unipen: &UniPen, out_dir: Option<&Path>, options: &SvgOptions) -> Result<(), Box<dyn std::error::Error>> {
+    let Some(out_dir) = out_dir else {
+        let mut stdout = io::stdout().lock();
+        stdout.write_all(merge_sets(unipen.sets()).to_svg(options).as_bytes())?;
+        stdout.flush()?;
+        return Ok(());
     };
-    let path = Path::new(path_str.as_str());
+    fs::create_dir_all(out_dir)?;
+    let mut file_names = HashSet::new();
+    for (index, set) in unipen.sets().iter().enumerate() {
+        // Sets are named after the paths of included files, or may not be named at all
+        let mut file_name = Path::new(&*set.name)
+            .file_name()
+            .map_or_else(|| format!("set{index}"), |name| name.to_string_lossy().into_owned());
+        if !file_names.insert(file_name.clone()) {
+            file_name = format!("{file_name}-{index}");
+            file_names.insert(file_name.clone());
+        }
+        fs::write(out_dir.join(format!("{file_name}.svg")), set.to_svg(options))?;
+    }
+    Ok(())
+}
+
+fn write_statements(statements: &[Statement], format: Format) -> Result<(), Box<dyn std::error::Error>> {
+    let mut stdout = io::stdout().lock();
+    match format {
+        Format::Json => statements::write_json(statements, &mut stdout, false)?,
+        Format::JsonPretty => statements::write_json(statements, &mut stdout, true)?,
+        // YAML tags cannot nest, so nested enums such as reserved arguments are written as maps like in JSON
+        Format::Yaml => {
+            serde_yaml::to_writer(&mut stdout, &serde_json::to_value(statements)?)?;
+            stdout.flush()?;
+        }
+        Format::Unipen => statements::write_unipen(statements, &mut stdout)?,
+    }
+    Ok(())
+}
 
-    // Get the path to the include directory.
-    let include_arg = args.next();
-    let include = include_arg.as_deref().map(Path::new);
+fn main() -> Result<(), &'static str> {
+    let cli = Cli::try_parse().unwrap_or_else(|error| {
+        // Unlike missing arguments, invalid values such as an unknown format are reported without the usage
+        if error.kind() == ErrorKind::InvalidValue {
+            eprintln!("{error}\n{}", Cli::command().render_usage());
+            std::process::exit(error.exit_code());
+        }
+        error.exit()
+    });
+
+    if let Some(Command::Svg {
+        file,
+        out_dir,
+        include_dir,
+        flip_y,
+        stroke_width,
+    }) = cli.command
+    {
+        let unipen = match parse(&file, include_dir.as_deref())
+            .and_then(|statements| UniPen::builder().statements(statements))
+            .and_then(UniPenBuilder::build)
+        {
+            Ok(unipen) => unipen,
+            Err(error) => return Err(report_error(&error, &file, cli.error_format)),
+        };
+        let options = SvgOptions::new().flip_y(flip_y).stroke_width(stroke_width);
+        return write_svg(&unipen, out_dir.as_deref(), &options).map_err(|error| {
+            eprintln!("{error}");
+            "Failed to write SVG."
+        });
+    }
 
     // Parse the file.
-    let statements = match statements::parse(path, include) {
+    let file = cli.file.expect("file is required without a subcommand");
+    let statements = match parse(&file, cli.include_dir.as_deref()) {
         Ok(statements) => statements,
-        Err(error) => {
-            eprintln!("{error}");
-            return Err("Error occured during parsing.");
-        }
+        Err(error) => return Err(report_error(&error, &file, cli.error_format)),
     };
 
     // Print the statements.
-    serde_json::to_writer(std::io::stdout(), &statements).expect("Failed to write to stdout.");
-
-    Ok(())
+    write_statements(&statements, cli.format).map_err(|error| {
+        eprintln!("{error}");
+        "Failed to write to stdout."
+    })
 }
@@ -0,0 +1,201 @@
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+const FIXTURE: &str = ".VERSION 1.0\n.COORD X Y T\n.PEN_DOWN\n1 2 3\n.PEN_UP\n";
+
+fn temp_path(prefix: &str) -> PathBuf {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    std::env::temp_dir().join(format!(
+        "unipen-cli-{prefix}-{}-{}",
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::SeqCst)
+    ))
+}
+
+fn temp_file(content: &str) -> PathBuf {
+    let path = temp_path("file");
+    std::fs::write(&path, content).unwrap();
+    path
+}
+
+fn cli() -> Command {
+    Command::new(env!("CARGO_BIN_EXE_unipen-cli"))
+}
+
+fn run_format(format: &str) -> Output {
+    cli().arg(temp_file(FIXTURE)).args(["--format", format]).output().unwrap()
+}
+
+fn stdout(output: Output) -> String {
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn format_json() {
+    let text = stdout(run_format("json"));
+    let value: serde_json::Value = serde_json::from_str(&text).unwrap();
+    // The synthetic include and end of input statements come with the four of the file
+    assert_eq!(value.as_array().unwrap().len(), 6);
+    assert!(!text.trim_end().contains('\n'), "{text}");
+}
+
+#[test]
+fn format_json_is_the_default() {
+    let file = temp_file(FIXTURE);
+    let default = stdout(cli().arg(&file).output().unwrap());
+    assert_eq!(default, stdout(cli().arg(&file).args(["--format", "json"]).output().unwrap()));
+}
+
+#[test]
+fn format_json_pretty() {
+    let text = stdout(run_format("json-pretty"));
+    let value: serde_json::Value = serde_json::from_str(&text).unwrap();
+    assert_eq!(value.as_array().unwrap().len(), 6);
+    assert!(text.trim_end().contains('\n'), "{text}");
+}
+
+#[test]
+fn format_yaml() {
+    let text = stdout(run_format("yaml"));
+    let value: serde_yaml::Value = serde_yaml::from_str(&text).unwrap();
+    assert_eq!(value.as_sequence().unwrap().len(), 6);
+    assert!(text.contains("keyword: Version"), "{text}");
+}
+
+#[test]
+fn format_unipen() {
+    let file = temp_file(FIXTURE);
+    let text = stdout(cli().arg(&file).args(["--format", "unipen"]).output().unwrap());
+    assert_eq!(
+        text,
+        format!(
+            ".COMMENT .INCLUDE {}\n.VERSION 1.0\n.COORD X Y T\n.PEN_DOWN 1 2 3\n.PEN_UP\n",
+            file.display()
+        )
+    );
+}
+
+#[test]
+fn format_unknown() {
+    let output = run_format("xml");
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Usage"));
+}
+
+fn run_stdin(command: &mut Command, input: &str) -> Output {
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn reads_stdin() {
+    let output = run_stdin(cli().args(["-", "--format", "unipen"]), ".VERSION 1.0\n.COORD X Y\n");
+    assert_eq!(stdout(output), ".COMMENT .INCLUDE <stdin>\n.VERSION 1.0\n.COORD X Y\n");
+
+    // Without an include directory, there is nowhere to look up included files
+    let output = run_stdin(cli().args(["-", "--format", "unipen"]), ".INCLUDE other\n");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(output.stdout.is_empty());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("Include path not provided, but file contains .INCLUDE"),
+        "{stderr}"
+    );
+    let output = run_stdin(cli().args(["-", "--error-format", "json"]), ".INCLUDE other\n");
+    assert_eq!(output.status.code(), Some(16));
+    let report: serde_json::Value = serde_json::from_slice(&output.stderr).unwrap();
+    assert_eq!(report["kind"], "MissingInclude");
+
+    let dir = temp_path("dir");
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("other"), ".DATA_ID other\n").unwrap();
+    let output = run_stdin(cli().arg("-").arg(&dir).args(["--format", "unipen"]), ".INCLUDE other\n");
+    assert!(stdout(output).contains("\n.DATA_ID other\n"));
+}
+
+#[test]
+fn svg_per_set() {
+    let dir = temp_path("dir");
+    std::fs::create_dir_all(dir.join("inc/sub")).unwrap();
+    std::fs::write(
+        dir.join("top"),
+        ".VERSION 1.0\n.DATA_SOURCE s\n.DATA_ID i\n.COORD X Y T\n.HIERARCHY W\n.INCLUDE a\n.INCLUDE sub/b\n.START_SET\n\
+         .PEN_DOWN\n9 9 1\n.PEN_UP\n",
+    )
+    .unwrap();
+    std::fs::write(dir.join("inc/a"), ".PEN_DOWN\n1 2 0\n3 4 10\n.PEN_UP\n").unwrap();
+    std::fs::write(dir.join("inc/sub/b"), ".PEN_DOWN\n5 6 20\n.PEN_UP\n").unwrap();
+    let out = dir.join("out");
+    let output = cli()
+        .arg("svg")
+        .arg(dir.join("top"))
+        .arg(&out)
+        .arg("--include-dir")
+        .arg(dir.join("inc"))
+        .args(["--flip-y", "--stroke-width", "2"])
+        .output()
+        .unwrap();
+    assert!(stdout(output).is_empty());
+    let mut names: Vec<_> = std::fs::read_dir(&out)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect();
+    names.sort();
+    assert_eq!(names, ["a.svg", "b.svg", "top.svg"]);
+    assert!(std::fs::read_to_string(out.join("a.svg"))
+        .unwrap()
+        .contains("stroke-width=\"2\""));
+
+    // Without an output directory, all sets are drawn into one SVG on stdout
+    let output = cli()
+        .arg("svg")
+        .arg(dir.join("top"))
+        .arg("--include-dir")
+        .arg(dir.join("inc"))
+        .output()
+        .unwrap();
+    let text = stdout(output);
+    assert_eq!(text.matches("<svg").count(), 1);
+    assert_eq!(text.matches("<path").count(), 3);
+}
+
+#[test]
+fn json_errors() {
+    let file = temp_file(".VERSION 1.0\n.BOGUS x\n");
+    let output = cli().arg(&file).args(["--error-format", "json"]).output().unwrap();
+    assert_eq!(output.status.code(), Some(15));
+    let report: serde_json::Value = serde_json::from_slice(&output.stderr).unwrap();
+    assert_eq!(report["kind"], "PestRule");
+    assert_eq!(report["path"], file.display().to_string());
+    assert_eq!(report["line"], 2);
+    assert_eq!(report["column"], 1);
+    assert!(report["message"].as_str().is_some_and(|message| !message.is_empty()));
+    // The exit code is not part of the report
+    assert_eq!(report.as_object().unwrap().len(), 5);
+
+    let output = cli().args(["svg", "--error-format", "json"]).arg(&file).output().unwrap();
+    assert_eq!(output.status.code(), Some(15));
+    let output = cli().arg(&file).output().unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert!(serde_json::from_slice::<serde_json::Value>(&output.stderr).is_err());
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn write_failures_are_reported() {
+    let full = std::fs::OpenOptions::new().write(true).open("/dev/full").unwrap();
+    let output = cli().arg(temp_file(FIXTURE)).stdout(full).output().unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("Failed to write to stdout."), "{stderr}");
+    assert!(!stderr.contains("panicked"), "{stderr}");
+}
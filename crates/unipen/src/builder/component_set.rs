@@ -1,8 +1,9 @@
-use std::{ops::RangeInclusive, rc::Rc};
+use std::{ops::RangeInclusive, rc::Rc, time::Duration};
 
 use crate::{
-    statements::ComponentList,
-    model::{BoundingBox, ComponentSet, CoordinateIndex, Quality, Segment},
+    error::UniPenError,
+    statements::{ComponentItem, ComponentList, ComponentPoint, Point},
+    model::{BoundingBox, Component, ComponentSet, Coordinate, CoordinateIndex, Quality, Segment, Style},
 };
 
 #[allow(clippy::module_name_repetitions)]
@@ -10,13 +11,16 @@ pub struct ComponentSetBuilder {
     component_counter: i32,
 
     name: Rc<str>,
-    // Coordinates are stored with their raw time values for analysis later
+    writer_id: Option<Rc<str>>,
+    style: Option<Style>,
+    // Coordinates are stored with their time values in milliseconds for analysis later
     coordinates: Vec<BuilderCoordinate>,
     components: Vec<BuilderComponent>,
     segments: Vec<Segment>,
     bounding_boxes: Vec<BoundingBox>,
 
     segment_statements: Vec<BuilderSegment>,
+    bounding_box_statements: Vec<BuilderBoundingBox>,
 }
 
 impl Default for ComponentSetBuilder {
@@ -24,11 +28,14 @@ impl Default for ComponentSetBuilder {
         Self {
             component_counter: 0,
             name: String::new().into(),
+            writer_id: None,
+            style: None,
             coordinates: Vec::default(),
             components: Vec::default(),
             segments: Vec::default(),
             bounding_boxes: Vec::default(),
             segment_statements: Vec::default(),
+            bounding_box_statements: Vec::default(),
         }
     }
 }
@@ -40,7 +47,15 @@ struct BuilderSegment {
     label: Option<Rc<str>>,
 }
 
-struct BuilderCoordinate {
+struct BuilderBoundingBox {
+    x_min: f64,
+    y_min: f64,
+    x_max: f64,
+    y_max: f64,
+    component_list: Option<ComponentList>,
+}
+
+pub struct BuilderCoordinate {
     pub x_position: f64,
     pub y_position: f64,
     pub time: f64,
@@ -58,36 +73,88 @@ enum BuilderComponent {
     Dt(f64),
 }
 
+// Raw time values are in milliseconds
+fn duration_from_millis(millis: f64) -> Result<Duration, UniPenError> {
+    Duration::try_from_secs_f64(millis / 1000.0)
+        .map_err(|err| UniPenError::Validation(format!("Invalid time value {millis}: {err}")))
+}
+
+impl BuilderCoordinate {
+    fn build(self) -> Result<Coordinate, UniPenError> {
+        Ok(Coordinate {
+            x_position: self.x_position,
+            y_position: self.y_position,
+            time: duration_from_millis(self.time)?,
+            pressure: self.pressure,
+            z_position: self.z_position,
+            button: self.button,
+            rho: self.rho,
+            theta: self.theta,
+            phi: self.phi,
+        })
+    }
+}
+
+impl BuilderComponent {
+    fn build(self) -> Result<Component, UniPenError> {
+        match self {
+            Self::PenDown(range) => Ok(Component::PenDown(range)),
+            Self::PenUp(range) => Ok(Component::PenUp(range)),
+            Self::Dt(dt) => Ok(Component::Dt(duration_from_millis(dt)?)),
+        }
+    }
+}
+
 impl ComponentSetBuilder {
     #[must_use]
     pub fn is_empty(&self) -> bool {
         self.coordinates.is_empty()
     }
 
+    /// Returns the number of numbered components, which are the non-empty pen components.
+    #[must_use]
+    pub fn component_count(&self) -> usize {
+        self.component_ranges().len()
+    }
+
     #[must_use]
     pub fn name(mut self, name: Rc<str>) -> Self {
         self.name = name;
         self
     }
 
+    #[must_use]
+    pub fn writer_id(mut self, writer_id: Option<Rc<str>>) -> Self {
+        self.writer_id = writer_id;
+        self
+    }
+
+    #[must_use]
+    pub const fn style(mut self, style: Option<Style>) -> Self {
+        self.style = style;
+        self
+    }
+
     fn add_coordinates(
         mut self,
         mut new_coordinates: Vec<BuilderCoordinate>,
         component: fn(RangeInclusive<CoordinateIndex>) -> BuilderComponent,
     ) -> Self {
         let start_idx = self.coordinates.len();
-        let component_size = new_coordinates.len();
-
-        self.coordinates.append(&mut new_coordinates);
 
-        let end_idx = start_idx + component_size - 1;
-        self.components.push(component(start_idx..=end_idx));
-
-        // If the component is empty, don't increment the component counter
-        if component_size == 0 {
+        // An empty component gets an empty range and is not counted. Inclusive ranges cannot end before 0, so an empty
+        // component before any coordinates gets 1..=0
+        if new_coordinates.is_empty() {
+            let range = start_idx
+                .checked_sub(1)
+                .map_or(RangeInclusive::new(1, 0), |end_idx| start_idx..=end_idx);
+            self.components.push(component(range));
             return self;
         }
 
+        let end_idx = start_idx + new_coordinates.len() - 1;
+        self.coordinates.append(&mut new_coordinates);
+        self.components.push(component(start_idx..=end_idx));
         self.component_counter += 1;
         self
     }
@@ -127,7 +194,124 @@ impl ComponentSetBuilder {
     }
 
     #[must_use]
-    pub fn build(self) -> ComponentSet {
-        todo!()
+    pub fn bounding_box(mut self, x_min: f64, y_min: f64, x_max: f64, y_max: f64, component_list: Option<ComponentList>) -> Self {
+        self.bounding_box_statements.push(BuilderBoundingBox {
+            x_min,
+            y_min,
+            x_max,
+            y_max,
+            component_list,
+        });
+        self
+    }
+
+    // Coordinate ranges of the numbered components. Only non-empty pen components are numbered.
+    fn component_ranges(&self) -> Vec<RangeInclusive<CoordinateIndex>> {
+        self.components
+            .iter()
+            .filter_map(|component| match component {
+                BuilderComponent::PenDown(range) | BuilderComponent::PenUp(range) if !range.is_empty() => Some(range.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn resolve_point(
+        component_ranges: &[RangeInclusive<CoordinateIndex>],
+        component_point: &ComponentPoint,
+    ) -> Result<RangeInclusive<CoordinateIndex>, UniPenError> {
+        let range = component_ranges.get(component_point.component).ok_or_else(|| {
+            UniPenError::Validation(format!(
+                "Segment references nonexistent component {}",
+                component_point.component
+            ))
+        })?;
+        match component_point.point {
+            Point::All => Ok(range.clone()),
+            Point::Index(point) => {
+                let index = range.start() + point;
+                if range.contains(&index) {
+                    Ok(index..=index)
+                } else {
+                    Err(UniPenError::Validation(format!(
+                        "Segment references nonexistent point {point} of component {}",
+                        component_point.component
+                    )))
+                }
+            }
+        }
+    }
+
+    fn resolve_list(
+        component_ranges: &[RangeInclusive<CoordinateIndex>],
+        component_list: &ComponentList,
+    ) -> Result<Rc<[RangeInclusive<CoordinateIndex>]>, UniPenError> {
+        component_list
+            .0
+            .iter()
+            .map(|item| match item {
+                ComponentItem::Single(point) => Self::resolve_point(component_ranges, point),
+                ComponentItem::Range(range) => {
+                    let start = *Self::resolve_point(component_ranges, &range.start)?.start();
+                    let end = *Self::resolve_point(component_ranges, &range.end)?.end();
+                    if start > end {
+                        return Err(UniPenError::Validation(format!(
+                            "Segment range ends before it starts ({start} > {end})"
+                        )));
+                    }
+                    Ok(start..=end)
+                }
+            })
+            .collect()
+    }
+
+    /// Builds the `ComponentSet`, resolving the component lists of segment and bounding box statements into coordinate ranges.
+    ///
+    /// # Errors
+    ///
+    /// * `UniPenError::Validation` - If a time value cannot be represented as a `Duration`, or a segment or bounding box
+    ///   references a component or point that does not exist.
+    ///
+    pub fn build(self) -> Result<ComponentSet, UniPenError> {
+        let component_ranges = self.component_ranges();
+        let mut segments = self.segments;
+        for segment in self.segment_statements {
+            segments.push(Segment {
+                hierarchy: segment.hierarchy,
+                coordinates: Self::resolve_list(&component_ranges, &segment.component_list)?,
+                quality: segment.quality,
+                label: segment.label,
+            });
+        }
+        let mut bounding_boxes = self.bounding_boxes;
+        for bounding_box in self.bounding_box_statements {
+            bounding_boxes.push(BoundingBox {
+                x_min: bounding_box.x_min,
+                y_min: bounding_box.y_min,
+                x_max: bounding_box.x_max,
+                y_max: bounding_box.y_max,
+                coordinates: match &bounding_box.component_list {
+                    Some(component_list) => Self::resolve_list(&component_ranges, component_list)?,
+                    None => Rc::new([]),
+                },
+            });
+        }
+        Ok(ComponentSet {
+            name: self.name,
+            writer_id: self.writer_id,
+            style: self.style,
+            coordinates: self
+                .coordinates
+                .into_iter()
+                .map(BuilderCoordinate::build)
+                .collect::<Result<_, _>>()?,
+            components: self
+                .components
+                .into_iter()
+                .map(BuilderComponent::build)
+                .collect::<Result<_, _>>()?,
+            segments: segments.into(),
+            bounding_boxes: bounding_boxes.into(),
+        })
     }
 }
@@ -1,15 +1,106 @@
-use std::{rc::Rc, time::Duration};
+use std::{ops::RangeInclusive, rc::Rc, time::Duration};
+
+use log::{debug, warn};
 
 use crate::{
     error::{translation_err, UniPenError},
-    statements::{Keyword, Statement, StatementArgument, Reserved},
     model::{
-        Coordinate, CoordinateType, Hand, Sex, Skill,
-        Style,
+        Acceptance, AlphabetItem, ComponentIndex, CoordinateType, DataDocumentation, Date, Hand, Layout, Lexicon, LexiconItem,
+        Quality, RecLabel, RecScore, Recognizer, SegmentReference, SetMembership, SetReference, Sex, Skill, Style, UniPen, Units,
+        Writer, MM_PER_INCH,
     },
+    statements::{ComponentItem, ComponentList, Keyword, Reserved, Statement, StatementArgument},
+    writer::coordinate_name,
 };
 
-use super::component_set::ComponentSetBuilder;
+use super::component_set::{BuilderCoordinate, ComponentSetBuilder};
+
+// Relative difference allowed between points per millimeter and converted points per inch, for rounded declarations
+const UNIT_TOLERANCE: f64 = 1e-3;
+// The plausible ages of writers unless set with `UniPenBuilder::age_range`
+const DEFAULT_AGE_RANGE: RangeInclusive<i32> = 0..=150;
+
+fn missing(keyword: &str) -> UniPenError {
+    UniPenError::Validation(format!("Missing mandatory {keyword} statement"))
+}
+
+// An unknown `?` segment quality is the same as no quality
+fn segment_quality(quality: &Reserved) -> Result<Option<Quality>, UniPenError> {
+    match quality {
+        Reserved::Unknown => Ok(None),
+        quality => Quality::try_from(quality).map(Some),
+    }
+}
+
+struct BuilderSetReference {
+    source: Rc<str>,
+    id: Rc<str>,
+    hierarchy: Option<Rc<str>>,
+    component_list: ComponentList,
+}
+
+// Components are numbered across the whole file. When `component_count` is `None`, the indices are not bounded.
+fn resolve_component_indices(
+    component_list: &ComponentList,
+    component_count: Option<usize>,
+) -> Result<Rc<[RangeInclusive<ComponentIndex>]>, UniPenError> {
+    component_list
+        .0
+        .iter()
+        .map(|item| {
+            let (start, end) = match item {
+                ComponentItem::Single(point) => (point.component, point.component),
+                ComponentItem::Range(range) => (range.start.component, range.end.component),
+            };
+            if start > end {
+                return Err(UniPenError::Validation(format!(
+                    "Component range ends before it starts ({start} > {end})"
+                )));
+            }
+            if component_count.is_some_and(|count| end >= count) {
+                return Err(UniPenError::Validation(format!("Reference to nonexistent component {end}")));
+            }
+            Ok(start..=end)
+        })
+        .collect()
+}
+
+impl BuilderSetReference {
+    // Lexicon sets reference lexicon entries, so they are not bounded by the component count
+    fn build(self, component_count: Option<usize>) -> Result<SetReference, UniPenError> {
+        Ok(SetReference {
+            components: resolve_component_indices(&self.component_list, component_count)?,
+            source: self.source,
+            id: self.id,
+            hierarchy: self.hierarchy,
+        })
+    }
+}
+
+struct BuilderSegmentReference {
+    hierarchy: Rc<str>,
+    component_list: ComponentList,
+}
+
+impl BuilderSegmentReference {
+    fn build(&self, component_count: usize) -> Result<SegmentReference, UniPenError> {
+        Ok(SegmentReference {
+            hierarchy: self.hierarchy.clone(),
+            components: resolve_component_indices(&self.component_list, Some(component_count))?,
+        })
+    }
+}
+
+struct BuilderRecLabels {
+    segment_ref: BuilderSegmentReference,
+    acceptance: Option<Acceptance>,
+    labels: Vec<Rc<str>>,
+}
+
+struct BuilderRecScores {
+    segment_ref: BuilderSegmentReference,
+    scores: Vec<(f64, Rc<str>)>,
+}
 
 #[allow(clippy::module_name_repetitions)]
 #[derive(Default)]
@@ -17,7 +108,12 @@ pub struct UniPenBuilder {
     // Used to determine the current set name when a set name is not defined
     file_stack: Vec<Rc<str>>,
     // Used to determine the coordinate time. For example, when .POINTS_PER_SECOND is used in place of T coordinate types
+    // Holds the time of the next sample
     current_time: Duration,
+    // Inconsistent data is an error instead of a warning
+    strict: bool,
+    // The plausible ages of writers, `DEFAULT_AGE_RANGE` when not set
+    age_range: Option<RangeInclusive<i32>>,
     // Used to collect data for the current component set
     current_component_set_builder: ComponentSetBuilder,
     // Old component set builders saved after a new component set is started
@@ -29,9 +125,11 @@ pub struct UniPenBuilder {
     data_id: Option<Rc<str>>,
     coordinate_order: Option<Vec<CoordinateType>>,
     hierarchy_order: Option<Vec<Rc<str>>>,
+    // Set by `.HIERARCHY ?`, which satisfies the requirement without naming any levels
+    hierarchy_declared: bool,
 
     alphabet: Option<Vec<Rc<str>>>,
-    alphabet_frequency: Option<Vec<i32>>,
+    alphabet_frequency: Option<Vec<f64>>,
 
     data_contact: Option<Rc<str>>,
     data_info: Option<Rc<str>>,
@@ -43,12 +141,12 @@ pub struct UniPenBuilder {
     lexicon_contact: Option<Rc<str>>,
     lexicon_info: Option<Rc<str>>,
     lexicon: Option<Vec<Rc<str>>>,
-    lexicon_frequency: Option<Vec<i32>>,
+    lexicon_frequency: Option<Vec<f64>>,
 
-    x_dimension: Option<i32>,
-    y_dimension: Option<i32>,
-    h_lines: Option<Vec<i32>>,
-    v_lines: Option<Vec<i32>>,
+    x_dimension: Option<f64>,
+    y_dimension: Option<f64>,
+    h_lines: Option<Vec<f64>>,
+    v_lines: Option<Vec<f64>>,
 
     x_points_per_inch: Option<f64>,
     y_points_per_inch: Option<f64>,
@@ -59,6 +157,7 @@ pub struct UniPenBuilder {
     points_per_gram: Option<f64>,
     points_per_second: Option<f64>,
 
+    date: Option<Date>,
     style: Option<Style>,
     writer_id: Option<Rc<str>>,
     country: Option<Rc<str>>,
@@ -67,14 +166,33 @@ pub struct UniPenBuilder {
     sex: Option<Sex>,
     skill: Option<Skill>,
     writer_info: Option<Rc<str>>,
+
+    recognizer_source: Option<Rc<str>>,
+    recognizer_id: Option<Rc<str>>,
+    recognizer_contact: Option<Rc<str>>,
+    recognizer_info: Option<Rc<str>>,
+    recognizer_implementation: Option<Rc<str>>,
+    recognizer_time: Option<Duration>,
+
+    training_sets: Vec<BuilderSetReference>,
+    test_sets: Vec<BuilderSetReference>,
+    adapt_sets: Vec<BuilderSetReference>,
+    lexicon_sets: Vec<BuilderSetReference>,
+
+    rec_labels: Vec<BuilderRecLabels>,
+    rec_scores: Vec<BuilderRecScores>,
+
+    comments: Vec<Rc<str>>,
+    declared_keywords: Vec<Rc<str>>,
+    declared_reserved: Vec<Rc<str>>,
 }
 
 impl UniPenBuilder {
-    fn pen_statement_to_coords(&self, arguments: &[StatementArgument]) -> Result<Vec<(Coordinate, f64)>, UniPenError> {
+    fn pen_statement_to_coords(&mut self, arguments: &[StatementArgument]) -> Result<Vec<BuilderCoordinate>, UniPenError> {
         let order = self
             .coordinate_order
             .as_ref()
-            .ok_or(UniPenError::Validation("Pen statement before coordinate order".into()))?;
+            .ok_or(UniPenError::Validation("Pen data before .COORD declaration".into()))?;
 
         let mut numbers = arguments
             .iter()
@@ -89,6 +207,23 @@ impl UniPenBuilder {
             .into_iter()
             .peekable();
 
+        // Without a Time coordinate, each point is one sample after the previous one
+        let sample_period = if order
+            .iter()
+            .any(|coordinate_type| matches!(coordinate_type, CoordinateType::Time))
+        {
+            None
+        } else {
+            let points_per_second = self.points_per_second.ok_or(UniPenError::Validation(
+                "Pen statement without Time coordinate or .POINTS_PER_SECOND".into(),
+            ))?;
+            Some(
+                Duration::try_from_secs_f64(1.0 / points_per_second)
+                    .map_err(|err| UniPenError::Validation(format!("Invalid .POINTS_PER_SECOND {points_per_second}: {err}")))?,
+            )
+        };
+        let mut current_time = self.current_time;
+
         let mut coordinates = Vec::new();
         while numbers.peek().is_some() {
             let mut x_position: Option<f64> = None;
@@ -116,26 +251,330 @@ impl UniPenBuilder {
                     CoordinateType::Theta => theta = Some(number),
                     CoordinateType::Phi => phi = Some(number),
                 }
-                coordinates.push((
-                    Coordinate {
-                        x_position: x_position.ok_or(UniPenError::Validation("Missing X coordinate".into()))?,
-                        y_position: y_position.ok_or(UniPenError::Validation("Missing Y coordinate".into()))?,
-                        time: Duration::default(),
-                        pressure,
-                        z_position,
-                        button,
-                        rho,
-                        theta,
-                        phi,
-                    },
-                    time.ok_or(UniPenError::Validation("Missing Time coordinate".into()))?,
-                ));
             }
+            let time = match time {
+                Some(time) => self.time_to_duration(time)?,
+                None => current_time,
+            };
+            current_time = time + sample_period.unwrap_or_default();
+            coordinates.push(BuilderCoordinate {
+                x_position: x_position.ok_or(UniPenError::Validation("Missing X coordinate".into()))?,
+                y_position: y_position.ok_or(UniPenError::Validation("Missing Y coordinate".into()))?,
+                time: time.as_secs_f64() * 1000.0,
+                pressure,
+                z_position,
+                button,
+                rho,
+                theta,
+                phi,
+            });
         }
+        self.current_time = current_time;
         Ok(coordinates)
     }
 
-    #[allow(clippy::too_many_lines, clippy::cognitive_complexity)]
+    // Stores the current component set, unless it has no pen data, and starts a new one
+    fn start_component_set(&mut self, name: Rc<str>) {
+        let previous_builder = std::mem::replace(
+            &mut self.current_component_set_builder,
+            ComponentSetBuilder::default().name(name),
+        );
+        if !previous_builder.is_empty() {
+            self.component_set_builders.push(previous_builder);
+        }
+    }
+
+    // A set belongs to the writer and style in effect when its pen data starts, as .WRITER_ID and .STYLE may come before
+    // or after .START_SET
+    fn stamp_component_set(&mut self) {
+        if self.current_component_set_builder.is_empty() {
+            self.current_component_set_builder = std::mem::take(&mut self.current_component_set_builder)
+                .writer_id(self.writer_id.clone())
+                .style(self.style);
+        }
+    }
+
+    // Every coordinate has one X and one Y position, and no coordinate type can be given twice
+    fn validate_coordinate_order(order: &[CoordinateType]) -> Result<(), UniPenError> {
+        for (i, coordinate_type) in order.iter().enumerate() {
+            if order[..i].contains(coordinate_type) {
+                return Err(UniPenError::Validation(format!(
+                    "Duplicate {} coordinate in .COORD",
+                    coordinate_name(coordinate_type)
+                )));
+            }
+        }
+        for required in [CoordinateType::XPosition, CoordinateType::YPosition] {
+            if !order.contains(&required) {
+                return Err(UniPenError::Validation(format!(
+                    "Missing {} coordinate in .COORD",
+                    coordinate_name(&required)
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    // Frequency statements give one frequency for each entry of the statement they belong to, in the same order
+    fn entry_frequencies(keyword: &str, entries: usize, frequencies: Option<Vec<f64>>) -> Result<Vec<Option<f64>>, UniPenError> {
+        match frequencies {
+            Some(frequencies) if frequencies.len() != entries => Err(UniPenError::Validation(format!(
+                "{keyword} has {entries} entries, but {keyword}_FREQ has {} frequencies",
+                frequencies.len()
+            ))),
+            Some(frequencies) => Ok(frequencies.into_iter().map(Some).collect()),
+            None => Ok(vec![None; entries]),
+        }
+    }
+
+    // Pairs each character of .ALPHABET with its frequency from .ALPHABET_FREQ
+    fn alphabet_items(
+        alphabet: Option<Vec<Rc<str>>>,
+        frequencies: Option<Vec<f64>>,
+    ) -> Result<Option<Vec<AlphabetItem>>, UniPenError> {
+        let Some(alphabet) = alphabet else {
+            return match frequencies {
+                Some(_) => Err(UniPenError::Validation(".ALPHABET_FREQ without .ALPHABET".into())),
+                None => Ok(None),
+            };
+        };
+        let frequencies = Self::entry_frequencies(".ALPHABET", alphabet.len(), frequencies)?;
+        alphabet
+            .iter()
+            .zip(frequencies)
+            .map(|(label, frequency)| {
+                let mut chars = label.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(character), None) => Ok(AlphabetItem { character, frequency }),
+                    _ => Err(UniPenError::Validation(format!(
+                        ".ALPHABET entry {label:?} is not a single character"
+                    ))),
+                }
+            })
+            .collect::<Result<_, _>>()
+            .map(Some)
+    }
+
+    // Pairs each word of .LEXICON with its frequency from .LEXICON_FREQ
+    fn lexicon_items(
+        lexicon: Option<Vec<Rc<str>>>,
+        frequencies: Option<Vec<f64>>,
+    ) -> Result<Option<Vec<LexiconItem>>, UniPenError> {
+        let Some(lexicon) = lexicon else {
+            return match frequencies {
+                Some(_) => Err(UniPenError::Validation(".LEXICON_FREQ without .LEXICON".into())),
+                None => Ok(None),
+            };
+        };
+        let frequencies = Self::entry_frequencies(".LEXICON", lexicon.len(), frequencies)?;
+        Ok(Some(
+            lexicon
+                .into_iter()
+                .zip(frequencies)
+                .map(|(label, frequency)| LexiconItem { label, frequency })
+                .collect(),
+        ))
+    }
+
+    fn date_from_arguments(arguments: &[StatementArgument]) -> Result<Option<Date>, UniPenError> {
+        let to_part = |argument: &StatementArgument| match argument {
+            StatementArgument::Number(value) => Ok(Some(i32::from(value))),
+            StatementArgument::Reserved(Reserved::Unknown) => Ok(None),
+            _ => Err(UniPenError::Validation(format!("Invalid date argument: {argument:?}"))),
+        };
+        let parse_part = |part: &str| match part {
+            "?" => Ok(None),
+            _ => part
+                .parse::<i32>()
+                .map(Some)
+                .map_err(|_| UniPenError::Validation(format!("Invalid date component: {part}"))),
+        };
+
+        match arguments {
+            [StatementArgument::Reserved(Reserved::Unknown)] => Ok(None),
+            // Month day year
+            [month, day, year] => Date::new(to_part(month)?, to_part(day)?, to_part(year)?).map(Some),
+            [StatementArgument::String(date)] => {
+                let (separator, day_first) = if date.contains('/') { ('/', false) } else { ('-', true) };
+                let parts = date.split(separator).map(parse_part).collect::<Result<Vec<_>, _>>()?;
+                match *parts.as_slice() {
+                    // DD-MM-YYYY
+                    [day, month, year] if day_first => Date::new(month, day, year).map(Some),
+                    // MM/DD/YYYY
+                    [month, day, year] => Date::new(month, day, year).map(Some),
+                    _ => Err(UniPenError::Validation(format!("Invalid date: {date}"))),
+                }
+            }
+            _ => Err(UniPenError::Validation(format!("Invalid date: {arguments:?}"))),
+        }
+    }
+
+    fn segment(&mut self, arguments: &[StatementArgument]) -> Result<(), UniPenError> {
+        let (hierarchy, component_list, rest) = match arguments {
+            [StatementArgument::String(hierarchy) | StatementArgument::Label(hierarchy), StatementArgument::List(component_list), rest @ ..] => {
+                (hierarchy.clone(), component_list.clone(), rest)
+            }
+            _ => return Err(translation_err!("Segment statement is missing a hierarchy or component list")),
+        };
+        let (quality, label) = match rest {
+            [] => (None, None),
+            [StatementArgument::Reserved(quality)] => (segment_quality(quality)?, None),
+            [StatementArgument::Label(label)] => (None, Some(label.clone())),
+            [StatementArgument::Reserved(quality), StatementArgument::Label(label)] => {
+                (segment_quality(quality)?, Some(label.clone()))
+            }
+            _ => return Err(translation_err!("Segment statement has invalid quality or label")),
+        };
+        self.current_component_set_builder =
+            std::mem::take(&mut self.current_component_set_builder).segment(hierarchy, component_list, quality, label);
+        Ok(())
+    }
+
+    fn bounding_box(&mut self, arguments: &[StatementArgument]) -> Result<(), UniPenError> {
+        let (numbers, component_list) = match arguments {
+            [numbers @ .., StatementArgument::List(component_list)] => (numbers, Some(component_list.clone())),
+            numbers => (numbers, None),
+        };
+        let numbers = numbers
+            .iter()
+            .map(|argument| match argument {
+                StatementArgument::Number(value) => Ok(f64::from(value)),
+                _ => Err(translation_err!("Bounding box statement has non-number argument")),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let [x_min, y_min, x_max, y_max] = numbers[..] else {
+            return Err(UniPenError::Validation(format!(
+                "Bounding box requires 4 numbers, found {}",
+                numbers.len()
+            )));
+        };
+        if x_min > x_max || y_min > y_max {
+            return Err(UniPenError::Validation(format!(
+                "Bounding box is inverted: ({x_min}, {y_min}) to ({x_max}, {y_max})"
+            )));
+        }
+        self.current_component_set_builder =
+            std::mem::take(&mut self.current_component_set_builder).bounding_box(x_min, y_min, x_max, y_max, component_list);
+        Ok(())
+    }
+
+    fn set_references(arguments: &[StatementArgument], lexicon: bool) -> Result<Vec<BuilderSetReference>, UniPenError> {
+        let chunk_size = if lexicon { 3 } else { 4 };
+        arguments
+            .chunks(chunk_size)
+            .map(|chunk| {
+                let [names @ .., StatementArgument::List(component_list)] = chunk else {
+                    return Err(translation_err!("Set statement is missing a component list"));
+                };
+                let names = names
+                    .iter()
+                    .map(|name| match name {
+                        StatementArgument::String(name) => Ok(name.clone()),
+                        _ => Err(translation_err!("Set statement has non-string name")),
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                let (source, id, hierarchy) = match names.as_slice() {
+                    [source, id] if lexicon => (source.clone(), id.clone(), None),
+                    [source, id, hierarchy] if !lexicon => (source.clone(), id.clone(), Some(hierarchy.clone())),
+                    _ => return Err(translation_err!("Set statement has wrong number of names")),
+                };
+                Ok(BuilderSetReference {
+                    source,
+                    id,
+                    hierarchy,
+                    component_list: component_list.clone(),
+                })
+            })
+            .collect()
+    }
+
+    // Raw times are in sample units when .POINTS_PER_SECOND is declared, otherwise they are in milliseconds
+    fn time_to_duration(&self, time: f64) -> Result<Duration, UniPenError> {
+        let seconds = if let Some(points_per_second) = self.points_per_second {
+            debug!("Converting time {time} using {points_per_second} points per second");
+            time / points_per_second
+        } else {
+            debug!("Converting time {time} as milliseconds, no points per second declared");
+            time / 1000.0
+        };
+        Duration::try_from_secs_f64(seconds).map_err(|err| UniPenError::Validation(format!("Invalid time value {time}: {err}")))
+    }
+
+    fn segment_reference(
+        arguments: &[StatementArgument],
+    ) -> Result<(BuilderSegmentReference, &[StatementArgument]), UniPenError> {
+        match arguments {
+            [StatementArgument::String(hierarchy), StatementArgument::List(component_list), rest @ ..] => Ok((
+                BuilderSegmentReference {
+                    hierarchy: hierarchy.clone(),
+                    component_list: component_list.clone(),
+                },
+                rest,
+            )),
+            _ => Err(translation_err!(
+                "Recognition statement is missing a hierarchy or component list"
+            )),
+        }
+    }
+
+    fn rec_labels(arguments: &[StatementArgument]) -> Result<BuilderRecLabels, UniPenError> {
+        let (segment_ref, rest) = Self::segment_reference(arguments)?;
+        let [StatementArgument::Reserved(acceptance), labels @ ..] = rest else {
+            return Err(translation_err!("Recognition labels statement is missing an acceptance"));
+        };
+        Ok(BuilderRecLabels {
+            segment_ref,
+            acceptance: match acceptance {
+                Reserved::Unknown => None,
+                acceptance => Some(Acceptance::try_from(acceptance)?),
+            },
+            labels: labels
+                .iter()
+                .map(|label| match label {
+                    StatementArgument::Label(label) => Ok(label.clone()),
+                    _ => Err(translation_err!("Recognition labels statement has non-label argument")),
+                })
+                .collect::<Result<_, _>>()?,
+        })
+    }
+
+    // Writer attributes are matched regardless of case. The grammar only produces reserved words for the exact casing, so
+    // other casings and unrecognized values are strings.
+    fn writer_attribute<T>(statement: &Statement, words: &[Reserved]) -> Result<Option<T>, UniPenError>
+    where
+        T: for<'a> TryFrom<&'a Reserved, Error = UniPenError>,
+    {
+        match statement.arguments.first() {
+            Some(StatementArgument::Reserved(Reserved::Unknown)) => Ok(None),
+            Some(StatementArgument::Reserved(reserved)) => T::try_from(reserved).map(Some),
+            Some(StatementArgument::String(token)) => {
+                let reserved = words
+                    .iter()
+                    .find(|word| word.to_string().eq_ignore_ascii_case(token))
+                    .ok_or_else(|| UniPenError::Validation(format!("Unknown {} value {token:?}", statement.keyword)))?;
+                T::try_from(reserved).map(Some)
+            }
+            _ => Err(translation_err!(format!(
+                "Statement of {} has invalid argument",
+                statement.keyword
+            ))),
+        }
+    }
+
+    fn rec_scores(arguments: &[StatementArgument]) -> Result<BuilderRecScores, UniPenError> {
+        let (segment_ref, rest) = Self::segment_reference(arguments)?;
+        Ok(BuilderRecScores {
+            segment_ref,
+            scores: rest
+                .chunks(2)
+                .map(|chunk| match chunk {
+                    [StatementArgument::Number(score), StatementArgument::Label(label)] => Ok((f64::from(score), label.clone())),
+                    _ => Err(translation_err!("Recognition scores statement has invalid score or label")),
+                })
+                .collect::<Result<_, _>>()?,
+        })
+    }
+
     /// Adds a statement to the `UniPenBuilder`'s representation of the UniPen data.
     ///
     /// # Arguments
@@ -147,17 +586,39 @@ impl UniPenBuilder {
     /// * `UniPenError::Translation` - If the builder was unable to translate the `Statement` into UniPen data.
     /// * `UniPenError::Validation` - If the builder was unable to create a valid structure from the UniPen data.
     ///
+    /// The messages of these errors include the line and column of the statement, when it has a span.
+    ///
     pub fn statement(mut self, statement: &Statement) -> Result<Self, UniPenError> {
+        self.add_statement(statement)?;
+        Ok(self)
+    }
+
+    // Like `statement`, but keeps the builder when the statement could not be added
+    pub(crate) fn add_statement(&mut self, statement: &Statement) -> Result<(), UniPenError> {
+        self.translate_statement(statement)
+            .map_err(|err| match (err, statement.span) {
+                (UniPenError::Translation(message), Some((line, column))) => {
+                    UniPenError::Translation(format!("{message} (line {line}, column {column})"))
+                }
+                (UniPenError::Validation(message), Some((line, column))) => {
+                    UniPenError::Validation(format!("{message} (line {line}, column {column})"))
+                }
+                (err, _) => err,
+            })
+    }
+
+    #[allow(clippy::too_many_lines, clippy::cognitive_complexity)]
+    fn translate_statement(&mut self, statement: &Statement) -> Result<(), UniPenError> {
         macro_rules! statement_translation_err {
             ($msg:expr) => {
                 Err(translation_err!(format!(
-                    "Statement of {:?} has invalid argument: {}",
+                    "Statement of {} has invalid argument: {}",
                     statement.keyword, $msg
                 )))
             };
             () => {
                 Err(translation_err!(format!(
-                    "Statement of {:?} has invalid argument",
+                    "Statement of {} has invalid argument",
                     statement.keyword
                 )))
             };
@@ -167,13 +628,13 @@ impl UniPenBuilder {
                 match &statement.arguments[0] {
                     StatementArgument::Reserved(Reserved::Unknown) => {
                         $into = None;
-                        Ok(self)
+                        Ok(())
                     }
                     // https://github.com/rust-lang/rust-clippy/issues/1553
                     #[allow(clippy::redundant_closure_call)]
                     $variant(value) => {
                         $into = Some($closure(value)?);
-                        Ok(self)
+                        Ok(())
                     }
                     _ => statement_translation_err!(stringify!($variant)),
                 }
@@ -184,8 +645,29 @@ impl UniPenBuilder {
                 translate_arg!($variant, _unused, $closure)
             }};
         }
+        // Repeated free text statements continue the text on a new line, as corpora split paragraphs over statements
+        macro_rules! append_free_text {
+            ($into:expr) => {
+                match &statement.arguments[0] {
+                    StatementArgument::FreeText(text) => {
+                        $into = Some(match $into.take() {
+                            Some(previous) => format!("{previous}\n{text}").into(),
+                            None => text.clone(),
+                        });
+                        Ok(())
+                    }
+                    _ => statement_translation_err!(stringify!(StatementArgument::FreeText)),
+                }
+            };
+        }
+        // A lone `?` marks the whole list as unknown and leaves it unset, unlike a statement without
+        // arguments which declares an empty list
         macro_rules! translate_homo {
             ($variant:path, $into:expr, $closure:expr) => {{
+                if let [StatementArgument::Reserved(Reserved::Unknown)] = statement.arguments.as_slice() {
+                    $into = None;
+                    return Ok(());
+                }
                 $into = Some(
                     statement
                         .arguments
@@ -199,7 +681,7 @@ impl UniPenBuilder {
                         })
                         .collect::<Result<Vec<_>, _>>()?,
                 );
-                Ok(self)
+                Ok(())
             }};
         }
 
@@ -209,36 +691,64 @@ impl UniPenBuilder {
 
         #[allow(clippy::match_same_arms)] // TODO remove this when all arms are implemented
         match statement.keyword {
-            Keyword::Keyword | Keyword::Reserve | Keyword::Comment => Ok(self),
+            Keyword::Keyword => match statement.arguments.first() {
+                Some(StatementArgument::String(name)) => {
+                    self.declared_keywords.push(name.clone());
+                    Ok(())
+                }
+                _ => statement_translation_err!(stringify!(StatementArgument::String)),
+            },
+            Keyword::Reserve => match statement.arguments.first() {
+                Some(StatementArgument::String(name)) => {
+                    self.declared_reserved.push(name.clone());
+                    Ok(())
+                }
+                _ => statement_translation_err!(stringify!(StatementArgument::String)),
+            },
+            Keyword::Comment => match statement.arguments.first() {
+                Some(StatementArgument::FreeText(text)) => {
+                    self.comments.push(text.clone());
+                    Ok(())
+                }
+                _ => statement_translation_err!(stringify!(StatementArgument::FreeText)),
+            },
             Keyword::Include => match &statement.arguments[0] {
                 StatementArgument::String(value) => {
                     self.file_stack.push(value.clone());
-                    self.current_component_set_builder = self.current_component_set_builder.name(value.clone());
-                    Ok(self)
+                    self.start_component_set(value.clone());
+                    Ok(())
                 }
                 _ => statement_translation_err!(stringify!(StatementArgument::String)),
             },
             Keyword::Version => translate_arg!(StatementArgument::Number, self.version, to_float),
             Keyword::DataSource => translate_arg!(StatementArgument::FreeText, self.data_source, to_str),
-            Keyword::DataId => translate_arg!(StatementArgument::String, to_str),
-            Keyword::Coordinate => translate_homo!(StatementArgument::Reserved, self.coordinate_order, CoordinateType::try_from),
-            Keyword::Hierarchy => translate_homo!(StatementArgument::String, self.hierarchy_order, to_str),
+            Keyword::DataId => translate_arg!(StatementArgument::String, self.data_id, to_str),
+            Keyword::Coordinate => {
+                let translated: Result<(), UniPenError> =
+                    translate_homo!(StatementArgument::Reserved, self.coordinate_order, CoordinateType::try_from);
+                translated?;
+                Self::validate_coordinate_order(self.coordinate_order.as_deref().unwrap_or_default())
+            }
+            Keyword::Hierarchy => {
+                self.hierarchy_declared = true;
+                translate_homo!(StatementArgument::String, self.hierarchy_order, to_str)
+            }
             Keyword::DataContact => translate_arg!(StatementArgument::FreeText, self.data_contact, to_str),
-            Keyword::DataInfo => translate_arg!(StatementArgument::FreeText, self.data_info, to_str),
-            Keyword::Setup => translate_arg!(StatementArgument::FreeText, self.setup, to_str),
-            Keyword::Pad => translate_arg!(StatementArgument::FreeText, self.pad, to_str),
-            Keyword::Alphabet => translate_homo!(StatementArgument::String, self.alphabet, to_str),
-            Keyword::AlphabetFreq => translate_homo!(StatementArgument::Number, self.alphabet_frequency, to_int),
+            Keyword::DataInfo => append_free_text!(self.data_info),
+            Keyword::Setup => append_free_text!(self.setup),
+            Keyword::Pad => append_free_text!(self.pad),
+            Keyword::Alphabet => translate_homo!(StatementArgument::Label, self.alphabet, to_str),
+            Keyword::AlphabetFreq => translate_homo!(StatementArgument::Number, self.alphabet_frequency, to_float),
             Keyword::LexiconSource => translate_arg!(StatementArgument::FreeText, self.lexicon_source, to_str),
             Keyword::LexiconId => translate_arg!(StatementArgument::String, self.lexicon_id, to_str),
             Keyword::LexiconContact => translate_arg!(StatementArgument::FreeText, self.lexicon_contact, to_str),
             Keyword::LexiconInfo => translate_arg!(StatementArgument::FreeText, self.lexicon_info, to_str),
-            Keyword::Lexicon => translate_homo!(StatementArgument::String, self.lexicon, to_str),
-            Keyword::LexiconFreq => translate_homo!(StatementArgument::Number, self.lexicon_frequency, to_int),
-            Keyword::XDimension => translate_arg!(StatementArgument::Number, self.x_dimension, to_int),
-            Keyword::YDimension => translate_arg!(StatementArgument::Number, self.y_dimension, to_int),
-            Keyword::HLine => translate_homo!(StatementArgument::Number, self.h_lines, to_int),
-            Keyword::VLine => translate_homo!(StatementArgument::Number, self.v_lines, to_int),
+            Keyword::Lexicon => translate_homo!(StatementArgument::Label, self.lexicon, to_str),
+            Keyword::LexiconFreq => translate_homo!(StatementArgument::Number, self.lexicon_frequency, to_float),
+            Keyword::XDimension => translate_arg!(StatementArgument::Number, self.x_dimension, to_float),
+            Keyword::YDimension => translate_arg!(StatementArgument::Number, self.y_dimension, to_float),
+            Keyword::HLine => translate_homo!(StatementArgument::Number, self.h_lines, to_float),
+            Keyword::VLine => translate_homo!(StatementArgument::Number, self.v_lines, to_float),
             Keyword::XPointsPerInch => translate_arg!(StatementArgument::Number, self.x_points_per_inch, to_float),
             Keyword::YPointsPerInch => translate_arg!(StatementArgument::Number, self.y_points_per_inch, to_float),
             Keyword::ZPointsPerInch => translate_arg!(StatementArgument::Number, self.z_points_per_inch, to_float),
@@ -247,47 +757,419 @@ impl UniPenBuilder {
             Keyword::ZPointsPerMm => translate_arg!(StatementArgument::Number, self.z_points_per_mm, to_float),
             Keyword::PointsPerGram => translate_arg!(StatementArgument::Number, self.points_per_gram, to_float),
             Keyword::PointsPerSecond => translate_arg!(StatementArgument::Number, self.points_per_second, to_float),
+            // Checked before the arguments are read, so that the error names the pen statement rather than its points
+            Keyword::PenDown | Keyword::PenUp if self.coordinate_order.is_none() => {
+                Err(UniPenError::Validation("Pen data before .COORD declaration".into()))
+            }
             Keyword::PenDown => {
                 let coordinates = self.pen_statement_to_coords(&statement.arguments)?;
-                self.current_component_set_builder = self.current_component_set_builder.pen_down(coordinates);
-                Ok(self)
+                self.stamp_component_set();
+                self.current_component_set_builder =
+                    std::mem::take(&mut self.current_component_set_builder).pen_down(coordinates);
+                Ok(())
             }
             Keyword::PenUp => {
                 let coordinates = self.pen_statement_to_coords(&statement.arguments)?;
-                self.current_component_set_builder = self.current_component_set_builder.pen_up(coordinates);
-                Ok(self)
+                self.stamp_component_set();
+                self.current_component_set_builder = std::mem::take(&mut self.current_component_set_builder).pen_up(coordinates);
+                Ok(())
+            }
+            Keyword::Dt => match statement.arguments.as_slice() {
+                [StatementArgument::Number(value)] => {
+                    let dt = self.time_to_duration(f64::from(value))?;
+                    self.current_time += dt;
+                    self.current_component_set_builder =
+                        std::mem::take(&mut self.current_component_set_builder).dt(dt.as_secs_f64() * 1000.0);
+                    Ok(())
+                }
+                _ => statement_translation_err!(stringify!(StatementArgument::Number)),
+            },
+            Keyword::Date => {
+                self.date = Self::date_from_arguments(&statement.arguments)?;
+                Ok(())
+            }
+            Keyword::Style => {
+                let words = [Reserved::Printed, Reserved::Cursive, Reserved::Mixed];
+                self.style = Self::writer_attribute(statement, &words)?;
+                Ok(())
             }
-            Keyword::Dt => todo!(),
-            Keyword::Date => Ok(self), // TODO Implement e_date
-            Keyword::Style => translate_arg!(StatementArgument::Reserved, self.style, Style::try_from),
             Keyword::WriterId => translate_arg!(StatementArgument::String, self.writer_id, to_str),
             Keyword::Country => translate_arg!(StatementArgument::FreeText, self.country, to_str),
-            Keyword::Hand => translate_arg!(StatementArgument::Reserved, self.hand, Hand::try_from),
+            Keyword::Hand => {
+                let words = [Reserved::LeftHand, Reserved::RightHand];
+                self.hand = Self::writer_attribute(statement, &words)?;
+                Ok(())
+            }
             Keyword::Age => translate_arg!(StatementArgument::Number, self.age, to_int),
-            Keyword::Sex => translate_arg!(StatementArgument::Reserved, self.sex, Sex::try_from),
-            Keyword::Skill => translate_arg!(StatementArgument::Reserved, self.skill, Skill::try_from),
+            Keyword::Sex => {
+                let words = [Reserved::Male, Reserved::Female];
+                self.sex = Self::writer_attribute(statement, &words)?;
+                Ok(())
+            }
+            Keyword::Skill => {
+                let words = [Reserved::Bad, Reserved::Ok, Reserved::Good];
+                self.skill = Self::writer_attribute(statement, &words)?;
+                Ok(())
+            }
             Keyword::WriterInfo => translate_arg!(StatementArgument::FreeText, self.writer_info, to_str),
-            Keyword::Segment => Ok(self),     // TODO Implement e_segment
-            Keyword::StartSet => Ok(self),    // TODO Implement e_start_set
-            Keyword::StartBox => Ok(self),    // TODO Implement e_start_box
-            Keyword::RecSource => Ok(self),   // TODO Implement e_rec_source
-            Keyword::RecId => Ok(self),       // TODO Implement e_rec_id
-            Keyword::RecContact => Ok(self),  // TODO Implement e_rec_contact
-            Keyword::RecInfo => Ok(self),     // TODO Implement e_rec_info
-            Keyword::Implement => Ok(self),   // TODO Implement e_implement
-            Keyword::TrainingSet => Ok(self), // TODO Implement e_training_set
-            Keyword::TestSet => Ok(self),     // TODO Implement e_test_set
-            Keyword::AdaptSet => Ok(self),    // TODO Implement e_adapt_set
-            Keyword::LexiconSet => Ok(self),  // TODO Implement e_lexicon_set
-            Keyword::RecTime => Ok(self),     // TODO Implement e_rec_time
-            Keyword::RecLabels => Ok(self),   // TODO Implement e_rec_labels
-            Keyword::RecScores => Ok(self),   // TODO Implement e_rec_scores
+            Keyword::Segment => self.segment(&statement.arguments),
+            Keyword::StartSet => {
+                let name = match statement.arguments.as_slice() {
+                    [StatementArgument::String(name)] => name.clone(),
+                    [] => self.file_stack.last().cloned().unwrap_or_default(),
+                    _ => return statement_translation_err!(stringify!(StatementArgument::String)),
+                };
+                self.start_component_set(name);
+                Ok(())
+            }
+            Keyword::StartBox => self.bounding_box(&statement.arguments),
+            Keyword::RecSource => translate_arg!(StatementArgument::String, self.recognizer_source, to_str),
+            Keyword::RecId => translate_arg!(StatementArgument::String, self.recognizer_id, to_str),
+            Keyword::RecContact => translate_arg!(StatementArgument::FreeText, self.recognizer_contact, to_str),
+            Keyword::RecInfo => translate_arg!(StatementArgument::FreeText, self.recognizer_info, to_str),
+            Keyword::Implement => translate_arg!(StatementArgument::FreeText, self.recognizer_implementation, to_str),
+            Keyword::TrainingSet => {
+                self.training_sets
+                    .append(&mut Self::set_references(&statement.arguments, false)?);
+                Ok(())
+            }
+            Keyword::TestSet => {
+                self.test_sets.append(&mut Self::set_references(&statement.arguments, false)?);
+                Ok(())
+            }
+            Keyword::AdaptSet => {
+                self.adapt_sets
+                    .append(&mut Self::set_references(&statement.arguments, false)?);
+                Ok(())
+            }
+            Keyword::LexiconSet => {
+                self.lexicon_sets
+                    .append(&mut Self::set_references(&statement.arguments, true)?);
+                Ok(())
+            }
+            Keyword::RecTime => match statement.arguments.last() {
+                Some(StatementArgument::Number(value)) => {
+                    self.recognizer_time = Some(self.time_to_duration(f64::from(value))?);
+                    Ok(())
+                }
+                _ => statement_translation_err!(stringify!(StatementArgument::Number)),
+            },
+            Keyword::RecLabels => {
+                self.rec_labels.push(Self::rec_labels(&statement.arguments)?);
+                Ok(())
+            }
+            Keyword::RecScores => {
+                self.rec_scores.push(Self::rec_scores(&statement.arguments)?);
+                Ok(())
+            }
             Keyword::EndOfInput => {
-                self.file_stack
+                let file = self
+                    .file_stack
                     .pop()
                     .ok_or(translation_err!("End of input without matching include"))?;
-                Ok(self)
+                if let Some(StatementArgument::String(name)) = statement.arguments.first() {
+                    if *name != file {
+                        return Err(translation_err!(format!("End of input of {name} while in {file}")));
+                    }
+                }
+                // Ink after an include belongs to the including file again
+                let name = self.file_stack.last().cloned().unwrap_or_default();
+                self.start_component_set(name);
+                Ok(())
+            }
+            // The meaning of custom keywords is not known, so they only appear in the statements
+            Keyword::Custom(ref name) => {
+                debug!("Skipping custom keyword .{name}");
+                Ok(())
             }
         }
     }
+
+    /// Adds statements to the `UniPenBuilder`'s representation of the UniPen data, one at a time. Combined with
+    /// `statements::parse_iter`, a file can be built without collecting its statements.
+    ///
+    /// # Arguments
+    ///
+    /// * `statements` - The statements to add.
+    ///
+    /// # Errors
+    ///
+    /// The error of the first statement that could not be added. See `statement`.
+    ///
+    pub fn statements<I: IntoIterator<Item = Statement>>(self, statements: I) -> Result<Self, UniPenError> {
+        statements
+            .into_iter()
+            .try_fold(self, |builder, statement| builder.statement(&statement))
+    }
+
+    /// Sets whether inconsistent data is an error when building. In lenient mode, the default, inconsistencies are
+    /// logged as warnings and resolved: points per millimeter are preferred over points per inch.
+    #[must_use]
+    pub const fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Clears the data added by statements, so that the builder can be used for another file. The options, such as
+    /// `strict` and `age_range`, are kept, as are the allocations of the component set list and the include stack.
+    pub fn reset(&mut self) {
+        let mut file_stack = std::mem::take(&mut self.file_stack);
+        file_stack.clear();
+        let mut component_set_builders = std::mem::take(&mut self.component_set_builders);
+        component_set_builders.clear();
+        *self = Self {
+            strict: self.strict,
+            age_range: self.age_range.take(),
+            file_stack,
+            component_set_builders,
+            ..Self::default()
+        };
+    }
+
+    /// Sets the plausible ages of writers. Building fails if `.AGE` is outside of the range. Defaults to `0..=150`.
+    #[must_use]
+    pub fn age_range(mut self, age_range: RangeInclusive<i32>) -> Self {
+        self.age_range = Some(age_range);
+        self
+    }
+
+    fn check_age(&self) -> Result<(), UniPenError> {
+        let age_range = self.age_range.clone().unwrap_or(DEFAULT_AGE_RANGE);
+        match self.age {
+            Some(age) if !age_range.contains(&age) => Err(UniPenError::Validation(format!(
+                ".AGE {age} is outside of the plausible range {}..={}",
+                age_range.start(),
+                age_range.end()
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    // Points per inch and per millimeter of an axis may both be declared, but they must describe the same resolution
+    fn check_units(&self) -> Result<(), UniPenError> {
+        for (axis, message) in self.unit_conflicts() {
+            if self.strict {
+                return Err(UniPenError::Validation(message));
+            }
+            warn!("{message}, using .{axis}_POINTS_PER_MM");
+        }
+        Ok(())
+    }
+
+    fn unit_conflicts(&self) -> Vec<(&'static str, String)> {
+        let mut conflicts = Vec::new();
+        let axes = [
+            ("X", self.x_points_per_mm, self.x_points_per_inch),
+            ("Y", self.y_points_per_mm, self.y_points_per_inch),
+            ("Z", self.z_points_per_mm, self.z_points_per_inch),
+        ];
+        for (axis, points_per_mm, points_per_inch) in axes {
+            let (Some(points_per_mm), Some(points_per_inch)) = (points_per_mm, points_per_inch) else {
+                continue;
+            };
+            let converted = points_per_inch / MM_PER_INCH;
+            if (points_per_mm - converted).abs() <= UNIT_TOLERANCE * points_per_mm.abs().max(converted.abs()) {
+                continue;
+            }
+            let message = format!(
+                ".{axis}_POINTS_PER_MM {points_per_mm} disagrees with .{axis}_POINTS_PER_INCH {points_per_inch} \
+                 ({converted} points per millimeter)"
+            );
+            conflicts.push((axis, message));
+        }
+        conflicts
+    }
+
+    // The errors `build` reports first, collected instead of stopping at the first one. Unit conflicts are included even
+    // when lenient.
+    pub(crate) fn problems(&self) -> Vec<UniPenError> {
+        let mut problems: Vec<UniPenError> = self
+            .unit_conflicts()
+            .into_iter()
+            .map(|(_, message)| UniPenError::Validation(message))
+            .collect();
+        problems.extend(self.check_age().err());
+        let mandatory = [
+            (".VERSION", self.version.is_some()),
+            (".DATA_SOURCE", self.data_source.is_some()),
+            (".DATA_ID", self.data_id.is_some()),
+            (".COORD", self.coordinate_order.is_some()),
+            (".HIERARCHY", self.hierarchy_order.is_some() || self.hierarchy_declared),
+        ];
+        for (keyword, present) in mandatory {
+            if !present {
+                problems.push(missing(keyword));
+            }
+        }
+        problems
+    }
+
+    /// Builds the UniPen data from the statements added to the `UniPenBuilder`.
+    ///
+    /// # Returns
+    ///
+    /// The UniPen data, with one `ComponentSet` for each set of components.
+    ///
+    /// # Errors
+    ///
+    /// * `UniPenError::Validation` - If a mandatory statement is missing, or a `ComponentSet` could not be built.
+    ///   `.REC_SOURCE` and `.REC_ID` are mandatory when any recognizer documentation is present, and `.WRITER_ID` is
+    ///   mandatory when any writer documentation is present.
+    /// * `UniPenError::Validation` - In strict mode, if the points per inch and per millimeter of an axis disagree.
+    ///
+    pub fn build(mut self) -> Result<UniPen, UniPenError> {
+        self.check_units()?;
+        self.check_age()?;
+
+        if !self.current_component_set_builder.is_empty() {
+            self.component_set_builders.push(self.current_component_set_builder);
+        }
+
+        let recognizer = if self.recognizer_source.is_some()
+            || self.recognizer_id.is_some()
+            || self.recognizer_contact.is_some()
+            || self.recognizer_info.is_some()
+            || self.recognizer_implementation.is_some()
+        {
+            Some(Recognizer {
+                recognizer_source: self.recognizer_source.ok_or_else(|| missing(".REC_SOURCE"))?,
+                recognizer_id: self.recognizer_id.ok_or_else(|| missing(".REC_ID"))?,
+                recognizer_contact: self.recognizer_contact,
+                recognizer_info: self.recognizer_info,
+                recognizer_implementation: self.recognizer_implementation,
+            })
+        } else {
+            None
+        };
+
+        let writer = if self.writer_id.is_some()
+            || self.country.is_some()
+            || self.hand.is_some()
+            || self.age.is_some()
+            || self.sex.is_some()
+            || self.skill.is_some()
+            || self.writer_info.is_some()
+        {
+            Some(Writer {
+                writer_id: self.writer_id.ok_or_else(|| missing(".WRITER_ID"))?,
+                country: self.country,
+                hand: self.hand,
+                age: self.age.map(f64::from),
+                sex: self.sex,
+                skill: self.skill,
+                writer_info: self.writer_info,
+            })
+        } else {
+            None
+        };
+        let lexicon = if self.lexicon_source.is_some()
+            || self.lexicon_id.is_some()
+            || self.lexicon_contact.is_some()
+            || self.lexicon_info.is_some()
+            || self.lexicon.is_some()
+            || self.lexicon_frequency.is_some()
+        {
+            Some(Rc::new(Lexicon {
+                lexicon_source: self.lexicon_source,
+                lexicon_id: self.lexicon_id,
+                lexicon_contact: self.lexicon_contact,
+                lexicon_info: self.lexicon_info,
+                lexicon: Self::lexicon_items(self.lexicon, self.lexicon_frequency)?,
+            }))
+        } else {
+            None
+        };
+
+        let units = Units {
+            x_points_per_inch: self.x_points_per_inch,
+            y_points_per_inch: self.y_points_per_inch,
+            z_points_per_inch: self.z_points_per_inch,
+            x_points_per_mm: self.x_points_per_mm,
+            y_points_per_mm: self.y_points_per_mm,
+            z_points_per_mm: self.z_points_per_mm,
+            points_per_gram: self.points_per_gram,
+            points_per_second: self.points_per_second,
+        };
+
+        // Set statements can reference components anywhere in the file, so they are resolved once all components exist
+        let component_count = self
+            .component_set_builders
+            .iter()
+            .map(ComponentSetBuilder::component_count)
+            .sum();
+        let build_sets = |sets: Vec<BuilderSetReference>, component_count: Option<ComponentIndex>| {
+            sets.into_iter()
+                .map(|set| set.build(component_count))
+                .collect::<Result<Vec<_>, _>>()
+        };
+        let sets_membership = SetMembership {
+            training_sets: build_sets(self.training_sets, Some(component_count))?,
+            test_sets: build_sets(self.test_sets, Some(component_count))?,
+            adapt_sets: build_sets(self.adapt_sets, Some(component_count))?,
+            lexicon_sets: build_sets(self.lexicon_sets, None)?,
+        };
+
+        let mut rec_labels = Vec::new();
+        for statement in self.rec_labels {
+            for label in statement.labels {
+                rec_labels.push(RecLabel {
+                    segment_ref: statement.segment_ref.build(component_count)?,
+                    acceptance: statement.acceptance,
+                    label,
+                });
+            }
+        }
+        let mut rec_scores = Vec::new();
+        for statement in self.rec_scores {
+            for (score, label) in statement.scores {
+                rec_scores.push(RecScore {
+                    segment_ref: statement.segment_ref.build(component_count)?,
+                    score,
+                    label,
+                });
+            }
+        }
+
+        Ok(UniPen {
+            version: self.version.ok_or_else(|| missing(".VERSION"))?,
+            data_source: self.data_source.ok_or_else(|| missing(".DATA_SOURCE"))?,
+            data_id: self.data_id.ok_or_else(|| missing(".DATA_ID"))?,
+            coordinate_order: self.coordinate_order.ok_or_else(|| missing(".COORD"))?,
+            hierarchy_order: match self.hierarchy_order {
+                Some(hierarchy_order) => hierarchy_order,
+                None if self.hierarchy_declared => Vec::new(),
+                None => return Err(missing(".HIERARCHY")),
+            },
+            data_documentation: DataDocumentation {
+                data_contact: self.data_contact,
+                data_info: self.data_info,
+                setup: self.setup,
+                pad: self.pad,
+            },
+            alphabet: Self::alphabet_items(self.alphabet, self.alphabet_frequency)?,
+            lexicon,
+            layout: Layout {
+                x_dimension: self.x_dimension,
+                y_dimension: self.y_dimension,
+                h_lines: self.h_lines,
+                v_lines: self.v_lines,
+            },
+            units,
+            date: self.date,
+            writer,
+            sets: self
+                .component_set_builders
+                .into_iter()
+                .map(ComponentSetBuilder::build)
+                .collect::<Result<_, _>>()?,
+            recognizer,
+            recognizer_time: self.recognizer_time,
+            sets_membership,
+            rec_labels,
+            rec_scores,
+            comments: self.comments,
+            declared_keywords: self.declared_keywords,
+            declared_reserved: self.declared_reserved,
+        })
+    }
 }
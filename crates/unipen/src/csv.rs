@@ -0,0 +1,49 @@
+use std::io::{self, Write};
+
+use crate::model::ComponentSet;
+
+const HEADER: &str = "component_index,point_index,x,y,time_ns,pressure,z,button,rho,theta,phi";
+
+fn optional(value: Option<f64>) -> String {
+    value.map(|value| value.to_string()).unwrap_or_default()
+}
+
+impl ComponentSet {
+    /// Writes the coordinates of the pen components as CSV, with a header row.
+    ///
+    /// The columns are `component_index,point_index,x,y,time_ns,pressure,z,button,rho,theta,phi`. The component index is the
+    /// index of the component in `components`, as in `points_for_component`. Absent coordinate types are left blank.
+    ///
+    /// # Arguments
+    ///
+    /// * `writer` - The writer to write the CSV to.
+    ///
+    /// # Errors
+    ///
+    /// Any I/O error of the writer.
+    ///
+    pub fn to_csv(&self, mut writer: impl Write) -> io::Result<()> {
+        writeln!(writer, "{HEADER}")?;
+        for component_index in 0..self.components.len() {
+            let Some(points) = self.points_for_component(component_index) else {
+                continue;
+            };
+            for (point_index, coordinate) in points.iter().enumerate() {
+                writeln!(
+                    writer,
+                    "{component_index},{point_index},{},{},{},{},{},{},{},{},{}",
+                    coordinate.x_position,
+                    coordinate.y_position,
+                    coordinate.time.as_nanos(),
+                    optional(coordinate.pressure),
+                    optional(coordinate.z_position),
+                    optional(coordinate.button),
+                    optional(coordinate.rho),
+                    optional(coordinate.theta),
+                    optional(coordinate.phi),
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
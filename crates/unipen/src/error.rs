@@ -24,6 +24,22 @@ pub enum UniPenError {
     PestRule(#[from] Box<pest::error::Error<Rule>>),
     #[error("Include path not provided, but file contains .INCLUDE")]
     MissingInclude,
+    #[error("Invalid UTF-8 in {name} at byte offset {offset}")]
+    InvalidUtf8 { name: String, offset: usize },
+    #[error("Includes are nested deeper than the maximum depth of {0}")]
+    IncludeDepth(usize),
+    #[error("{path} is {size} bytes, more than the limit of {limit} bytes")]
+    FileTooLarge { path: String, size: u64, limit: u64 },
+    // Holds the message rather than the `serde_json` error, so that the variant does not depend on the serde feature
+    #[error("Serialization error: {0}")]
+    Serialize(String),
+}
+
+#[cfg(feature = "serde")]
+impl From<serde_json::Error> for UniPenError {
+    fn from(error: serde_json::Error) -> Self {
+        Self::Serialize(error.to_string())
+    }
 }
 
 macro_rules! translation_err {
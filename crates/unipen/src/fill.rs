@@ -0,0 +1,75 @@
+use crate::model::{Component, ComponentSet, Coordinate};
+
+/// How `ComponentSet::fill_pressure` fills in missing pressures.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FillPolicy {
+    /// Interpolates linearly between the nearest points before and after with a pressure, by the position of the point
+    /// between them. Points before the first or after the last pressure of the stroke take the nearest pressure.
+    LinearInterpolate,
+    /// Uses the same pressure for every point without one.
+    Constant(f64),
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn interpolate_stroke(stroke: &mut [Coordinate]) {
+    let known: Vec<(usize, f64)> = stroke
+        .iter()
+        .enumerate()
+        .filter_map(|(index, coordinate)| Some((index, coordinate.pressure?)))
+        .collect();
+    let (Some(&(first, first_pressure)), Some(&(last, last_pressure))) = (known.first(), known.last()) else {
+        return;
+    };
+    for coordinate in &mut stroke[..first] {
+        coordinate.pressure = Some(first_pressure);
+    }
+    for coordinate in &mut stroke[last + 1..] {
+        coordinate.pressure = Some(last_pressure);
+    }
+    for pair in known.windows(2) {
+        let [(start, start_pressure), (end, end_pressure)] = *pair else {
+            unreachable!()
+        };
+        for (offset, coordinate) in stroke[start + 1..end].iter_mut().enumerate() {
+            let t = (offset + 1) as f64 / (end - start) as f64;
+            coordinate.pressure = Some((end_pressure - start_pressure).mul_add(t, start_pressure));
+        }
+    }
+}
+
+impl ComponentSet {
+    /// Fills in the missing pressures of the points of each pen down component. Strokes without any pressure are left
+    /// without one, so that a set without pressure data is unchanged. Pen up components are not changed.
+    ///
+    /// # Arguments
+    ///
+    /// * `policy` - How to fill in the pressures.
+    #[must_use]
+    pub fn fill_pressure(&self, policy: FillPolicy) -> Self {
+        let mut coordinates = self.coordinates.to_vec();
+        for component in self.components.iter() {
+            let Component::PenDown(range) = component else {
+                continue;
+            };
+            // The range of an empty component may lie past the end of the coordinates
+            let Some(stroke) = coordinates.get_mut(range.clone()) else {
+                continue;
+            };
+            if stroke.iter().all(|coordinate| coordinate.pressure.is_none()) {
+                continue;
+            }
+            match policy {
+                FillPolicy::LinearInterpolate => interpolate_stroke(stroke),
+                FillPolicy::Constant(pressure) => {
+                    for coordinate in stroke.iter_mut() {
+                        coordinate.pressure.get_or_insert(pressure);
+                    }
+                }
+            }
+        }
+        Self {
+            coordinates: coordinates.into(),
+            ..self.clone()
+        }
+    }
+}
@@ -0,0 +1,59 @@
+use crate::model::{ComponentSet, Coordinate, CoordinateType};
+
+// Times are in milliseconds, like in the UniPen writer
+#[allow(clippy::cast_possible_truncation)]
+fn channel_value(coordinate: &Coordinate, channel: CoordinateType) -> Option<f32> {
+    let value = match channel {
+        CoordinateType::XPosition => coordinate.x_position,
+        CoordinateType::YPosition => coordinate.y_position,
+        CoordinateType::Time => coordinate.time.as_secs_f64() * 1000.0,
+        CoordinateType::Pressure => coordinate.pressure?,
+        CoordinateType::ZPosition => coordinate.z_position?,
+        CoordinateType::Button => coordinate.button?,
+        CoordinateType::Rho => coordinate.rho?,
+        CoordinateType::Theta => coordinate.theta?,
+        CoordinateType::Phi => coordinate.phi?,
+    };
+    Some(value as f32)
+}
+
+impl ComponentSet {
+    /// Returns the coordinates of the pen components as a row-major `[point][channel]` array, for use with numeric libraries.
+    ///
+    /// The points are those of `coordinates_iter`. Times are in milliseconds.
+    ///
+    /// # Arguments
+    ///
+    /// * `channels` - The coordinate types to include, in order.
+    /// * `missing` - The value of the channels that a coordinate does not have, such as `0.0` or `f32::NAN`.
+    ///
+    /// # Returns
+    ///
+    /// `shape(channels).0 * channels.len()` values.
+    #[must_use]
+    pub fn to_flat_f32(&self, channels: &[CoordinateType], missing: f32) -> Vec<f32> {
+        let mut values = Vec::with_capacity(self.shape(channels).0 * channels.len());
+        for coordinate in self.coordinates_iter() {
+            values.extend(
+                channels
+                    .iter()
+                    .map(|channel| channel_value(coordinate, *channel).unwrap_or(missing)),
+            );
+        }
+        values
+    }
+
+    /// Returns the shape of the array of `to_flat_f32`.
+    ///
+    /// # Arguments
+    ///
+    /// * `channels` - The coordinate types to include.
+    ///
+    /// # Returns
+    ///
+    /// The number of points and the number of channels.
+    #[must_use]
+    pub fn shape(&self, channels: &[CoordinateType]) -> (usize, usize) {
+        (self.coordinates_iter().count(), channels.len())
+    }
+}
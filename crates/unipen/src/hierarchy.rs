@@ -0,0 +1,68 @@
+use std::{ops::RangeInclusive, rc::Rc};
+
+use crate::model::{ComponentSet, CoordinateIndex, Segment};
+
+/// A segment with the segments of finer hierarchy levels that it contains.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, PartialEq)]
+pub struct SegmentNode {
+    pub segment: Segment,
+    pub children: Vec<SegmentNode>,
+}
+
+// Whether every range of `inner` lies within one of the ranges of `outer`
+fn contains(outer: &[RangeInclusive<CoordinateIndex>], inner: &[RangeInclusive<CoordinateIndex>]) -> bool {
+    !inner.is_empty()
+        && inner.iter().all(|range| {
+            outer
+                .iter()
+                .any(|outer| outer.start() <= range.start() && range.end() <= outer.end())
+        })
+}
+
+fn node(segments: &[&Segment], parents: &[Option<usize>], index: usize) -> SegmentNode {
+    SegmentNode {
+        segment: segments[index].clone(),
+        children: (0..segments.len())
+            .filter(|child| parents[*child] == Some(index))
+            .map(|child| node(segments, parents, child))
+            .collect(),
+    }
+}
+
+impl ComponentSet {
+    /// Nests the segments of the set by hierarchy level. A segment is a child of the segment of the nearest coarser level
+    /// whose coordinates contain all of its coordinates, or a root if there is none. Siblings are in the order of the
+    /// segments.
+    ///
+    /// # Arguments
+    ///
+    /// * `order` - The hierarchy levels from coarsest to finest, usually `UniPen::hierarchy_order`. Segments of other levels
+    ///   are left out.
+    ///
+    /// # Returns
+    ///
+    /// The root segments.
+    #[must_use]
+    pub fn hierarchy_tree(&self, order: &[Rc<str>]) -> Vec<SegmentNode> {
+        let (segments, levels): (Vec<&Segment>, Vec<usize>) = self
+            .segments
+            .iter()
+            .filter_map(|segment| Some((segment, order.iter().position(|level| *level == segment.hierarchy)?)))
+            .unzip();
+        let parents: Vec<Option<usize>> = segments
+            .iter()
+            .zip(&levels)
+            .map(|(segment, level)| {
+                (0..segments.len())
+                    .filter(|parent| levels[*parent] < *level && contains(&segments[*parent].coordinates, &segment.coordinates))
+                    // The first of the segments of the nearest level
+                    .min_by_key(|parent| level - levels[*parent])
+            })
+            .collect();
+        (0..segments.len())
+            .filter(|index| parents[*index].is_none())
+            .map(|index| node(&segments, &parents, index))
+            .collect()
+    }
+}
@@ -8,3 +8,14 @@ pub mod error;
 pub mod statements;
 pub mod model;
 pub mod builder;
+pub mod writer;
+pub mod svg;
+pub mod csv;
+pub mod resample;
+pub mod flat;
+pub mod smooth;
+pub mod timestamps;
+pub mod hierarchy;
+pub mod merge;
+pub mod fill;
+pub mod summary;
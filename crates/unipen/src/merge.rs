@@ -0,0 +1,254 @@
+use std::{ops::RangeInclusive, rc::Rc};
+
+use log::warn;
+
+use crate::{
+    error::UniPenError,
+    model::{Component, ComponentIndex, ComponentSet, SegmentReference, SetMembership, SetReference, UniPen, Writer},
+    writer::coordinate_name,
+};
+
+// The number of components of a set that component lists count
+fn numbered_components(set: &ComponentSet) -> usize {
+    set.components
+        .iter()
+        .filter(|component| match component {
+            Component::PenDown(range) | Component::PenUp(range) => !range.is_empty(),
+            Component::Dt(_) => false,
+        })
+        .count()
+}
+
+fn shift_ranges(ranges: &[RangeInclusive<ComponentIndex>], offset: ComponentIndex) -> Rc<[RangeInclusive<ComponentIndex>]> {
+    ranges
+        .iter()
+        .map(|range| range.start() + offset..=range.end() + offset)
+        .collect()
+}
+
+fn shift(reference: &SegmentReference, offset: ComponentIndex) -> SegmentReference {
+    SegmentReference {
+        hierarchy: reference.hierarchy.clone(),
+        components: shift_ranges(&reference.components, offset),
+    }
+}
+
+// Moves the components of set references, which lexicon sets do not have as they count lexicon entries
+fn shift_sets(references: Vec<SetReference>, offset: ComponentIndex) -> impl Iterator<Item = SetReference> {
+    references.into_iter().map(move |mut reference| {
+        reference.components = shift_ranges(&reference.components, offset);
+        reference
+    })
+}
+
+// Renumbers component ranges, unless one of the components is not kept or a range would no longer be contiguous
+fn renumber_ranges(
+    ranges: &[RangeInclusive<ComponentIndex>],
+    new_index: &[Option<ComponentIndex>],
+) -> Option<Rc<[RangeInclusive<ComponentIndex>]>> {
+    ranges
+        .iter()
+        .map(|range| {
+            let start = (*new_index.get(*range.start())?)?;
+            let end = (*new_index.get(*range.end())?)?;
+            (end.checked_sub(start)? == range.end() - range.start()).then_some(start..=end)
+        })
+        .collect()
+}
+
+fn renumber(reference: &SegmentReference, new_index: &[Option<ComponentIndex>]) -> Option<SegmentReference> {
+    Some(SegmentReference {
+        hierarchy: reference.hierarchy.clone(),
+        components: renumber_ranges(&reference.components, new_index)?,
+    })
+}
+
+fn renumber_sets(references: &[SetReference], new_index: &[Option<ComponentIndex>]) -> Vec<SetReference> {
+    references
+        .iter()
+        .filter_map(|reference| {
+            let mut reference = reference.clone();
+            reference.components = renumber_ranges(&reference.components, new_index)?;
+            Some(reference)
+        })
+        .collect()
+}
+
+// Keeps the value of the first document, warning when the second one has another
+fn prefer<T: PartialEq>(name: &str, kept: &T, other: &T) {
+    if kept != other {
+        warn!("Merged documents have different {name}, keeping the first");
+    }
+}
+
+// Like `prefer`, but takes the value of the second document when the first has none
+fn prefer_option<T: PartialEq>(name: &str, kept: &mut Option<T>, other: Option<T>) {
+    match (kept.as_ref(), other) {
+        (None, other) => *kept = other,
+        (Some(kept), Some(other)) => prefer(name, kept, &other),
+        (Some(_), None) => {}
+    }
+}
+
+impl UniPen {
+    /// Appends the component sets of another document, as if they were in an included file.
+    ///
+    /// Metadata of this document is kept, and a warning is logged when the other document has different metadata.
+    /// Metadata that only the other document has is taken from it. Coordinates are not converted, so documents with
+    /// different units should not be merged. The set memberships, recognition results and comments of both documents are
+    /// kept. The component indices of the other document's training, test and adapt sets and recognition results are moved
+    /// after those of this document, while its lexicon sets are kept as they are, as they count lexicon entries.
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - The document to append.
+    ///
+    /// # Errors
+    ///
+    /// * `UniPenError::Validation` - If the coordinate orders of the documents differ.
+    ///
+    pub fn merge(mut self, other: Self) -> Result<Self, UniPenError> {
+        if self.coordinate_order != other.coordinate_order {
+            let names = |document: &Self| {
+                document
+                    .coordinate_order
+                    .iter()
+                    .map(coordinate_name)
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            };
+            return Err(UniPenError::Validation(format!(
+                "Cannot merge documents with coordinate orders {} and {}",
+                names(&self),
+                names(&other)
+            )));
+        }
+        prefer("versions", &self.version, &other.version);
+        prefer("data sources", &self.data_source, &other.data_source);
+        prefer("data IDs", &self.data_id, &other.data_id);
+        prefer("hierarchies", &self.hierarchy_order, &other.hierarchy_order);
+        prefer("data documentation", &self.data_documentation, &other.data_documentation);
+        prefer("layouts", &self.layout, &other.layout);
+        prefer("units", &self.units, &other.units);
+        prefer_option("alphabets", &mut self.alphabet, other.alphabet);
+        prefer_option("lexicons", &mut self.lexicon, other.lexicon);
+        prefer_option("dates", &mut self.date, other.date);
+        prefer_option("writers", &mut self.writer, other.writer);
+        prefer_option("recognizers", &mut self.recognizer, other.recognizer);
+        prefer_option("recognition times", &mut self.recognizer_time, other.recognizer_time);
+
+        let offset = self.sets.iter().map(numbered_components).sum();
+        self.sets.extend(other.sets);
+        let membership = &mut self.sets_membership;
+        membership
+            .training_sets
+            .extend(shift_sets(other.sets_membership.training_sets, offset));
+        membership
+            .test_sets
+            .extend(shift_sets(other.sets_membership.test_sets, offset));
+        membership
+            .adapt_sets
+            .extend(shift_sets(other.sets_membership.adapt_sets, offset));
+        membership.lexicon_sets.extend(other.sets_membership.lexicon_sets);
+        self.rec_labels.extend(other.rec_labels.into_iter().map(|mut rec_label| {
+            rec_label.segment_ref = shift(&rec_label.segment_ref, offset);
+            rec_label
+        }));
+        self.rec_scores.extend(other.rec_scores.into_iter().map(|mut rec_score| {
+            rec_score.segment_ref = shift(&rec_score.segment_ref, offset);
+            rec_score
+        }));
+        self.comments.extend(other.comments);
+        for keyword in other.declared_keywords {
+            if !self.declared_keywords.contains(&keyword) {
+                self.declared_keywords.push(keyword);
+            }
+        }
+        for reserved in other.declared_reserved {
+            if !self.declared_reserved.contains(&reserved) {
+                self.declared_reserved.push(reserved);
+            }
+        }
+        Ok(self)
+    }
+
+    /// Splits the document into one document per writer, by the `.WRITER_ID` that each component set was recorded with.
+    ///
+    /// The documents are in the order of the first set of each writer, and each keeps the sets of its writer in order. Sets
+    /// recorded before any `.WRITER_ID` form a document without a writer. The other metadata is copied to each document,
+    /// except that the writer attributes other than the ID are only kept in the document of the last declared writer, as
+    /// only the last values are known. Recognition results and training, test and adapt sets are renumbered to the
+    /// components of their document, and dropped when they refer to components of another writer. Lexicon sets count
+    /// lexicon entries rather than components, so each document keeps all of them.
+    ///
+    /// # Returns
+    ///
+    /// The documents of the writers. A document without sets results in no documents.
+    #[must_use]
+    pub fn split_by_writer(&self) -> Vec<Self> {
+        let mut writer_ids: Vec<Option<Rc<str>>> = Vec::new();
+        for set in &self.sets {
+            if !writer_ids.contains(&set.writer_id) {
+                writer_ids.push(set.writer_id.clone());
+            }
+        }
+        writer_ids
+            .into_iter()
+            .map(|writer_id| {
+                // The index of each component of the file in the document of the writer
+                let mut new_index = Vec::new();
+                let mut count = 0;
+                for set in &self.sets {
+                    let components = numbered_components(set);
+                    if set.writer_id == writer_id {
+                        new_index.extend((count..count + components).map(Some));
+                        count += components;
+                    } else {
+                        new_index.extend(std::iter::repeat_n(None, components));
+                    }
+                }
+                let writer = writer_id.as_ref().map(|writer_id| match &self.writer {
+                    Some(writer) if writer.writer_id == *writer_id => writer.clone(),
+                    _ => Writer {
+                        writer_id: writer_id.clone(),
+                        country: None,
+                        hand: None,
+                        age: None,
+                        sex: None,
+                        skill: None,
+                        writer_info: None,
+                    },
+                });
+                Self {
+                    writer,
+                    sets_membership: SetMembership {
+                        training_sets: renumber_sets(&self.sets_membership.training_sets, &new_index),
+                        test_sets: renumber_sets(&self.sets_membership.test_sets, &new_index),
+                        adapt_sets: renumber_sets(&self.sets_membership.adapt_sets, &new_index),
+                        lexicon_sets: self.sets_membership.lexicon_sets.clone(),
+                    },
+                    sets: self.sets.iter().filter(|set| set.writer_id == writer_id).cloned().collect(),
+                    rec_labels: self
+                        .rec_labels
+                        .iter()
+                        .filter_map(|rec_label| {
+                            let mut rec_label = rec_label.clone();
+                            rec_label.segment_ref = renumber(&rec_label.segment_ref, &new_index)?;
+                            Some(rec_label)
+                        })
+                        .collect(),
+                    rec_scores: self
+                        .rec_scores
+                        .iter()
+                        .filter_map(|rec_score| {
+                            let mut rec_score = rec_score.clone();
+                            rec_score.segment_ref = renumber(&rec_score.segment_ref, &new_index)?;
+                            Some(rec_score)
+                        })
+                        .collect(),
+                    ..self.clone()
+                }
+            })
+            .collect()
+    }
+}
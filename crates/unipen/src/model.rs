@@ -1,4 +1,4 @@
-use std::{ops::RangeInclusive, rc::Rc, time::Duration};
+use std::{fmt, ops::RangeInclusive, rc::Rc, time::Duration};
 
 use crate::{
     error::{translation_err, UniPenError},
@@ -6,25 +6,39 @@ use crate::{
     builder::unipen::UniPenBuilder
 };
 
+/// Parsed UniPen data. Equality compares all values exactly, including floating point values. Cloning is cheap, as text
+/// and the data of component sets are shared through `Rc`, and changes made through a clone do not affect the original.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, PartialEq)]
 pub struct UniPen {
-    pub version: f64,
-    pub data_source: Rc<str>,
-    pub data_id: Rc<str>,
-    pub coordinate_order: Vec<CoordinateType>,
-    pub hierarchy_order: Vec<Rc<str>>,
-    //data_documentation: DataDocumentation,
-    //alphabet: Option<Vec<AlphabetItem>>,
-    //lexicon: Option<Rc<Lexicon>>,
-    //layout: Layout,
-    //units: Units,
-    //date: Option<Date>,
+    pub(crate) version: f64,
+    pub(crate) data_source: Rc<str>,
+    pub(crate) data_id: Rc<str>,
+    pub(crate) coordinate_order: Vec<CoordinateType>,
+    // Empty when .HIERARCHY names no levels or is given as ?
+    pub(crate) hierarchy_order: Vec<Rc<str>>,
+    pub(crate) data_documentation: DataDocumentation,
+    pub(crate) alphabet: Option<Vec<AlphabetItem>>,
+    pub(crate) lexicon: Option<Rc<Lexicon>>,
+    pub(crate) layout: Layout,
+    pub(crate) units: Units,
+    pub(crate) date: Option<Date>,
     //style: Option<Style>,
-    //writer: Writer,
-    //sets: Vec<Set>,
+    pub(crate) writer: Option<Writer>,
+    pub(crate) sets: Vec<ComponentSet>,
     //bounding_boxes: Vec<BoundingBox>,
-    //recognizer: Recognizer,
-
-    // TODO Recognition tagging unimplemented
+    pub(crate) recognizer: Option<Recognizer>,
+    // Converted with .POINTS_PER_SECOND when declared, otherwise treated as milliseconds
+    #[cfg_attr(feature = "serde", serde(with = "duration_nanos::option"))]
+    pub(crate) recognizer_time: Option<Duration>,
+    pub(crate) sets_membership: SetMembership,
+    pub(crate) rec_labels: Vec<RecLabel>,
+    pub(crate) rec_scores: Vec<RecScore>,
+    // The text of the .COMMENT statements, in source order
+    pub(crate) comments: Vec<Rc<str>>,
+    // The names declared by .KEYWORD and .RESERVE, which are not otherwise interpreted
+    pub(crate) declared_keywords: Vec<Rc<str>>,
+    pub(crate) declared_reserved: Vec<Rc<str>>,
 }
 
 impl UniPen {
@@ -32,55 +46,381 @@ impl UniPen {
     pub fn builder() -> UniPenBuilder {
         UniPenBuilder::default()
     }
+
+    #[must_use]
+    pub const fn version(&self) -> f64 {
+        self.version
+    }
+
+    #[must_use]
+    pub fn data_source(&self) -> &str {
+        &self.data_source
+    }
+
+    #[must_use]
+    pub fn data_id(&self) -> &str {
+        &self.data_id
+    }
+
+    #[must_use]
+    pub fn coordinate_order(&self) -> &[CoordinateType] {
+        &self.coordinate_order
+    }
+
+    #[must_use]
+    pub fn hierarchy_order(&self) -> &[Rc<str>] {
+        &self.hierarchy_order
+    }
+
+    /// The documentation of the data declared by `.DATA_CONTACT`, `.DATA_INFO`, `.SETUP`, and `.PAD`.
+    #[must_use]
+    pub const fn data_documentation(&self) -> &DataDocumentation {
+        &self.data_documentation
+    }
+
+    /// The characters declared by `.ALPHABET`, with their frequencies when `.ALPHABET_FREQ` is declared.
+    #[must_use]
+    pub fn alphabet(&self) -> Option<&[AlphabetItem]> {
+        self.alphabet.as_deref()
+    }
+
+    /// The lexicon declared by the `.LEXICON*` statements.
+    #[must_use]
+    pub fn lexicon(&self) -> Option<&Lexicon> {
+        self.lexicon.as_deref()
+    }
+
+    /// The layout of the writing area declared by `.X_DIM`, `.Y_DIM`, `.H_LINE`, and `.V_LINE`.
+    #[must_use]
+    pub const fn layout(&self) -> &Layout {
+        &self.layout
+    }
+
+    /// The unit system declared by the `.*_POINTS_PER_*` statements.
+    #[must_use]
+    pub const fn units(&self) -> &Units {
+        &self.units
+    }
+
+    /// The date of `.DATE`, or `None` if it is absent or entirely unknown.
+    #[must_use]
+    pub const fn date(&self) -> Option<&Date> {
+        self.date.as_ref()
+    }
+
+    /// The writer declared by the writer statements, or `None` if there are no writer statements.
+    #[must_use]
+    pub const fn writer(&self) -> Option<&Writer> {
+        self.writer.as_ref()
+    }
+
+    #[must_use]
+    pub fn sets(&self) -> &[ComponentSet] {
+        &self.sets
+    }
+
+    /// Returns an iterator over the coordinates of all sets, in document order. Each coordinate is paired with the index of
+    /// its set in `sets`. The coordinates of a set are in the order of `ComponentSet::coordinates_iter`.
+    pub fn all_coordinates(&self) -> impl Iterator<Item = (usize, &Coordinate)> {
+        self.sets
+            .iter()
+            .enumerate()
+            .flat_map(|(index, set)| set.coordinates_iter().map(move |coordinate| (index, coordinate)))
+    }
+
+    #[must_use]
+    pub const fn recognizer(&self) -> Option<&Recognizer> {
+        self.recognizer.as_ref()
+    }
+
+    #[must_use]
+    pub const fn recognizer_time(&self) -> Option<Duration> {
+        self.recognizer_time
+    }
+
+    #[must_use]
+    pub const fn sets_membership(&self) -> &SetMembership {
+        &self.sets_membership
+    }
+
+    #[must_use]
+    pub fn rec_labels(&self) -> &[RecLabel] {
+        &self.rec_labels
+    }
+
+    #[must_use]
+    pub fn rec_scores(&self) -> &[RecScore] {
+        &self.rec_scores
+    }
+
+    /// Returns the text of the `.COMMENT` statements of the file and its includes, in source order.
+    #[must_use]
+    pub fn comments(&self) -> &[Rc<str>] {
+        &self.comments
+    }
+
+    /// Returns the names of the keywords declared with `.KEYWORD`, in source order. The statements of declared keywords are
+    /// syntax errors unless [`ParseOptions::lenient_keywords`](crate::statements::ParseOptions::lenient_keywords) is on,
+    /// which parses them as `Keyword::Custom`.
+    #[must_use]
+    pub fn declared_keywords(&self) -> &[Rc<str>] {
+        &self.declared_keywords
+    }
+
+    /// Returns the reserved words declared with `.RESERVE`, in source order.
+    #[must_use]
+    pub fn declared_reserved(&self) -> &[Rc<str>] {
+        &self.declared_reserved
+    }
+
+    /// Converts a raw X position to millimeters, using `.X_POINTS_PER_MM`, or `.X_POINTS_PER_INCH` when it is not declared.
+    ///
+    /// # Returns
+    ///
+    /// The position in millimeters, or `None` if neither unit is declared.
+    #[must_use]
+    pub fn x_to_mm(&self, raw: f64) -> Option<f64> {
+        points_per_mm(self.units.x_points_per_mm, self.units.x_points_per_inch).map(|points_per_mm| raw / points_per_mm)
+    }
+
+    /// Converts a raw Y position to millimeters, using `.Y_POINTS_PER_MM`, or `.Y_POINTS_PER_INCH` when it is not declared.
+    ///
+    /// # Returns
+    ///
+    /// The position in millimeters, or `None` if neither unit is declared.
+    #[must_use]
+    pub fn y_to_mm(&self, raw: f64) -> Option<f64> {
+        points_per_mm(self.units.y_points_per_mm, self.units.y_points_per_inch).map(|points_per_mm| raw / points_per_mm)
+    }
+
+    /// Converts a raw Z position to millimeters, using `.Z_POINTS_PER_MM`, or `.Z_POINTS_PER_INCH` when it is not declared.
+    ///
+    /// # Returns
+    ///
+    /// The position in millimeters, or `None` if neither unit is declared.
+    #[must_use]
+    pub fn z_to_mm(&self, raw: f64) -> Option<f64> {
+        points_per_mm(self.units.z_points_per_mm, self.units.z_points_per_inch).map(|points_per_mm| raw / points_per_mm)
+    }
+}
+
+pub(crate) const MM_PER_INCH: f64 = 25.4;
+
+// Points per millimeter are used directly, points per inch are converted
+fn points_per_mm(points_per_mm: Option<f64>, points_per_inch: Option<f64>) -> Option<f64> {
+    points_per_mm.or_else(|| points_per_inch.map(|points_per_inch| points_per_inch / MM_PER_INCH))
 }
 
-struct DataDocumentation {
+/// The documentation of the data. Repeated `.DATA_INFO`, `.SETUP` and `.PAD` statements are joined with newlines.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, PartialEq)]
+pub struct DataDocumentation {
     // Data Documentation
-    data_contact: Option<Rc<str>>,
-    data_info: Option<Rc<str>>,
-    setup: Option<Rc<str>>,
-    pad: Option<Rc<str>>,
+    pub(crate) data_contact: Option<Rc<str>>,
+    pub(crate) data_info: Option<Rc<str>>,
+    pub(crate) setup: Option<Rc<str>>,
+    pub(crate) pad: Option<Rc<str>>,
 }
 
-struct Layout {
+impl DataDocumentation {
+    #[must_use]
+    pub fn data_contact(&self) -> Option<&str> {
+        self.data_contact.as_deref()
+    }
+
+    #[must_use]
+    pub fn data_info(&self) -> Option<&str> {
+        self.data_info.as_deref()
+    }
+
+    #[must_use]
+    pub fn setup(&self) -> Option<&str> {
+        self.setup.as_deref()
+    }
+
+    #[must_use]
+    pub fn pad(&self) -> Option<&str> {
+        self.pad.as_deref()
+    }
+}
+
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, PartialEq)]
+pub struct Layout {
     // Data Layout
-    x_dimension: Option<f64>,
-    y_dimension: Option<f64>,
-    h_lines: Option<Vec<f64>>,
-    v_lines: Option<Vec<f64>>,
+    pub(crate) x_dimension: Option<f64>,
+    pub(crate) y_dimension: Option<f64>,
+    pub(crate) h_lines: Option<Vec<f64>>,
+    pub(crate) v_lines: Option<Vec<f64>>,
+}
+
+impl Layout {
+    #[must_use]
+    pub const fn x_dimension(&self) -> Option<f64> {
+        self.x_dimension
+    }
+
+    #[must_use]
+    pub const fn y_dimension(&self) -> Option<f64> {
+        self.y_dimension
+    }
+
+    #[must_use]
+    pub fn h_lines(&self) -> Option<&[f64]> {
+        self.h_lines.as_deref()
+    }
+
+    #[must_use]
+    pub fn v_lines(&self) -> Option<&[f64]> {
+        self.v_lines.as_deref()
+    }
 }
 
-struct Units {
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, PartialEq)]
+pub struct Units {
     // Unit System
-    x_points_per_inch: Option<f64>,
-    y_points_per_inch: Option<f64>,
-    z_points_per_inch: Option<f64>,
-    x_points_per_mm: Option<f64>,
-    y_points_per_mm: Option<f64>,
-    z_points_per_mm: Option<f64>,
-    points_per_gram: Option<f64>,
-    points_per_second: Option<f64>,
-}
-
-struct Writer {
-    writer_id: Rc<str>,
-    country: Option<Rc<str>>,
-    hand: Option<Hand>,
-    age: Option<f64>,
-    sex: Option<Sex>,
-    skill: Option<Skill>,
-    writer_info: Option<Rc<str>>,
-}
-
-struct Recognizer {
+    pub(crate) x_points_per_inch: Option<f64>,
+    pub(crate) y_points_per_inch: Option<f64>,
+    pub(crate) z_points_per_inch: Option<f64>,
+    pub(crate) x_points_per_mm: Option<f64>,
+    pub(crate) y_points_per_mm: Option<f64>,
+    pub(crate) z_points_per_mm: Option<f64>,
+    pub(crate) points_per_gram: Option<f64>,
+    pub(crate) points_per_second: Option<f64>,
+}
+
+impl Units {
+    #[must_use]
+    pub const fn x_points_per_inch(&self) -> Option<f64> {
+        self.x_points_per_inch
+    }
+
+    #[must_use]
+    pub const fn y_points_per_inch(&self) -> Option<f64> {
+        self.y_points_per_inch
+    }
+
+    #[must_use]
+    pub const fn z_points_per_inch(&self) -> Option<f64> {
+        self.z_points_per_inch
+    }
+
+    #[must_use]
+    pub const fn x_points_per_mm(&self) -> Option<f64> {
+        self.x_points_per_mm
+    }
+
+    #[must_use]
+    pub const fn y_points_per_mm(&self) -> Option<f64> {
+        self.y_points_per_mm
+    }
+
+    #[must_use]
+    pub const fn z_points_per_mm(&self) -> Option<f64> {
+        self.z_points_per_mm
+    }
+
+    #[must_use]
+    pub const fn points_per_gram(&self) -> Option<f64> {
+        self.points_per_gram
+    }
+
+    #[must_use]
+    pub const fn points_per_second(&self) -> Option<f64> {
+        self.points_per_second
+    }
+}
+
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, PartialEq)]
+pub struct Writer {
+    pub(crate) writer_id: Rc<str>,
+    pub(crate) country: Option<Rc<str>>,
+    pub(crate) hand: Option<Hand>,
+    pub(crate) age: Option<f64>,
+    pub(crate) sex: Option<Sex>,
+    pub(crate) skill: Option<Skill>,
+    pub(crate) writer_info: Option<Rc<str>>,
+}
+
+impl Writer {
+    #[must_use]
+    pub fn writer_id(&self) -> &str {
+        &self.writer_id
+    }
+
+    #[must_use]
+    pub fn country(&self) -> Option<&str> {
+        self.country.as_deref()
+    }
+
+    #[must_use]
+    pub const fn hand(&self) -> Option<&Hand> {
+        self.hand.as_ref()
+    }
+
+    #[must_use]
+    pub const fn age(&self) -> Option<f64> {
+        self.age
+    }
+
+    #[must_use]
+    pub const fn sex(&self) -> Option<&Sex> {
+        self.sex.as_ref()
+    }
+
+    #[must_use]
+    pub const fn skill(&self) -> Option<&Skill> {
+        self.skill.as_ref()
+    }
+
+    #[must_use]
+    pub fn writer_info(&self) -> Option<&str> {
+        self.writer_info.as_deref()
+    }
+}
+
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, PartialEq)]
+pub struct Recognizer {
     // Recognizer Documentation
-    recognizer_source: Rc<str>,
-    recognizer_id: Rc<str>,
-    recognizer_contact: Option<Rc<str>>,
-    recognizer_info: Option<Rc<str>>,
-    recognizer_implementation: Option<Rc<str>>,
+    pub(crate) recognizer_source: Rc<str>,
+    pub(crate) recognizer_id: Rc<str>,
+    pub(crate) recognizer_contact: Option<Rc<str>>,
+    pub(crate) recognizer_info: Option<Rc<str>>,
+    pub(crate) recognizer_implementation: Option<Rc<str>>,
 }
 
+impl Recognizer {
+    #[must_use]
+    pub fn recognizer_source(&self) -> &str {
+        &self.recognizer_source
+    }
+
+    #[must_use]
+    pub fn recognizer_id(&self) -> &str {
+        &self.recognizer_id
+    }
+
+    #[must_use]
+    pub fn recognizer_contact(&self) -> Option<&str> {
+        self.recognizer_contact.as_deref()
+    }
+
+    #[must_use]
+    pub fn recognizer_info(&self) -> Option<&str> {
+        self.recognizer_info.as_deref()
+    }
+
+    #[must_use]
+    pub fn recognizer_implementation(&self) -> Option<&str> {
+        self.recognizer_implementation.as_deref()
+    }
+}
+
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum CoordinateType {
     XPosition,
     YPosition,
@@ -112,22 +452,90 @@ impl TryFrom<&Reserved> for CoordinateType {
     }
 }
 
-struct AlphabetItem {
-    character: char,
-    frequency: Option<f64>,
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, PartialEq)]
+pub struct AlphabetItem {
+    pub(crate) character: char,
+    pub(crate) frequency: Option<f64>,
+}
+
+impl AlphabetItem {
+    #[must_use]
+    pub const fn character(&self) -> char {
+        self.character
+    }
+
+    #[must_use]
+    pub const fn frequency(&self) -> Option<f64> {
+        self.frequency
+    }
 }
 
-struct LexiconItem {
-    label: Rc<str>,
-    frequency: Option<f64>,
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, PartialEq)]
+pub struct LexiconItem {
+    pub(crate) label: Rc<str>,
+    pub(crate) frequency: Option<f64>,
 }
 
+impl LexiconItem {
+    #[must_use]
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    #[must_use]
+    pub const fn frequency(&self) -> Option<f64> {
+        self.frequency
+    }
+}
+
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, PartialEq)]
 pub struct Date {
-    month: Option<i32>,
-    day: Option<i32>,
-    year: Option<i32>,
+    pub(crate) month: Option<i32>,
+    pub(crate) day: Option<i32>,
+    pub(crate) year: Option<i32>,
+}
+
+impl Date {
+    /// Creates a `Date`, checking that the month and day are in range when they are known.
+    ///
+    /// # Errors
+    ///
+    /// * `UniPenError::Validation` - If the month is not in `1..=12`, the day is not in `1..=31`, or the year is negative.
+    ///
+    pub fn new(month: Option<i32>, day: Option<i32>, year: Option<i32>) -> Result<Self, UniPenError> {
+        if month.is_some_and(|month| !(1..=12).contains(&month)) {
+            return Err(UniPenError::Validation(format!("Invalid month in date: {month:?}")));
+        }
+        if day.is_some_and(|day| !(1..=31).contains(&day)) {
+            return Err(UniPenError::Validation(format!("Invalid day in date: {day:?}")));
+        }
+        if year.is_some_and(|year| year < 0) {
+            return Err(UniPenError::Validation(format!("Invalid year in date: {year:?}")));
+        }
+        Ok(Self { month, day, year })
+    }
+
+    #[must_use]
+    pub const fn month(&self) -> Option<i32> {
+        self.month
+    }
+
+    #[must_use]
+    pub const fn day(&self) -> Option<i32> {
+        self.day
+    }
+
+    #[must_use]
+    pub const fn year(&self) -> Option<i32> {
+        self.year
+    }
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Style {
     Printed,
     Cursive,
@@ -147,6 +555,8 @@ impl TryFrom<&Reserved> for Style {
     }
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Copy, PartialEq)]
 pub enum Hand {
     Left,
     Right,
@@ -164,6 +574,8 @@ impl TryFrom<&Reserved> for Hand {
     }
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Copy, PartialEq)]
 pub enum Sex {
     Male,
     Female,
@@ -181,6 +593,8 @@ impl TryFrom<&Reserved> for Sex {
     }
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Copy, PartialEq)]
 pub enum Skill {
     Bad,
     Ok,
@@ -200,6 +614,9 @@ impl TryFrom<&Reserved> for Skill {
     }
 }
 
+/// The quality of a `.SEGMENT`. An unknown `?` quality is read as no quality, so a segment's quality is `None`.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Quality {
     Ok,
     Good,
@@ -217,28 +634,400 @@ impl TryFrom<&Reserved> for Quality {
     }
 }
 
-struct Lexicon {
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, PartialEq)]
+pub struct Lexicon {
     // Lexicon
-    lexicon_source: Option<Rc<str>>,
-    lexicon_id: Option<Rc<str>>,
-    lexicon_contact: Option<Rc<str>>,
-    lexicon_info: Option<Rc<str>>,
-    lexicon: Option<Vec<LexiconItem>>,
+    pub(crate) lexicon_source: Option<Rc<str>>,
+    pub(crate) lexicon_id: Option<Rc<str>>,
+    pub(crate) lexicon_contact: Option<Rc<str>>,
+    pub(crate) lexicon_info: Option<Rc<str>>,
+    pub(crate) lexicon: Option<Vec<LexiconItem>>,
+}
+
+impl Lexicon {
+    #[must_use]
+    pub fn lexicon_source(&self) -> Option<&str> {
+        self.lexicon_source.as_deref()
+    }
+
+    #[must_use]
+    pub fn lexicon_id(&self) -> Option<&str> {
+        self.lexicon_id.as_deref()
+    }
+
+    #[must_use]
+    pub fn lexicon_contact(&self) -> Option<&str> {
+        self.lexicon_contact.as_deref()
+    }
+
+    #[must_use]
+    pub fn lexicon_info(&self) -> Option<&str> {
+        self.lexicon_info.as_deref()
+    }
+
+    #[must_use]
+    pub fn lexicon(&self) -> Option<&[LexiconItem]> {
+        self.lexicon.as_deref()
+    }
 }
 
 pub type CoordinateIndex = usize;
+pub type ComponentIndex = usize;
+
+/// Sets declared by `.TRAINING_SET`, `.TEST_SET`, `.ADAPT_SET`, and `.LEXICON_SET`.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, PartialEq, Default)]
+pub struct SetMembership {
+    pub(crate) training_sets: Vec<SetReference>,
+    pub(crate) test_sets: Vec<SetReference>,
+    pub(crate) adapt_sets: Vec<SetReference>,
+    pub(crate) lexicon_sets: Vec<SetReference>,
+}
+
+impl SetMembership {
+    #[must_use]
+    pub fn training_sets(&self) -> &[SetReference] {
+        &self.training_sets
+    }
+
+    #[must_use]
+    pub fn test_sets(&self) -> &[SetReference] {
+        &self.test_sets
+    }
+
+    #[must_use]
+    pub fn adapt_sets(&self) -> &[SetReference] {
+        &self.adapt_sets
+    }
+
+    #[must_use]
+    pub fn lexicon_sets(&self) -> &[SetReference] {
+        &self.lexicon_sets
+    }
+}
+
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, PartialEq)]
+pub struct SetReference {
+    pub(crate) source: Rc<str>,
+    pub(crate) id: Rc<str>,
+    pub(crate) hierarchy: Option<Rc<str>>,
+    pub(crate) components: Rc<[RangeInclusive<ComponentIndex>]>,
+}
+
+impl SetReference {
+    #[must_use]
+    pub fn source(&self) -> &str {
+        &self.source
+    }
 
+    #[must_use]
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// The hierarchy level of the components, which lexicon sets do not have.
+    #[must_use]
+    pub fn hierarchy(&self) -> Option<&str> {
+        self.hierarchy.as_deref()
+    }
+
+    /// The ranges of the components of the set, or of the lexicon entries for lexicon sets.
+    #[must_use]
+    pub fn components(&self) -> &[RangeInclusive<ComponentIndex>] {
+        &self.components
+    }
+}
+
+/// The components of a file or of a `.START_SET`. Cloning only copies the `Rc` pointers, so a clone shares its coordinates,
+/// components, segments, and bounding boxes with the original until they are replaced.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, PartialEq)]
 pub struct ComponentSet {
     pub name: Rc<str>,
     pub coordinates: Rc<[Coordinate]>,
     pub components: Rc<[Component]>,
     pub segments: Rc<[Segment]>,
     pub bounding_boxes: Rc<[BoundingBox]>,
+    /// The `.WRITER_ID` in effect when the pen data of the set started.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub writer_id: Option<Rc<str>>,
+    /// The `.STYLE` in effect when the pen data of the set started.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub style: Option<Style>,
+}
+
+/// Lists the fields of the set. Lists of more than eight entries, such as the coordinates of a large set, only show their
+/// first two entries and the number left out, so that sets can be logged. The alternate form `{:#?}` shows every entry.
+impl fmt::Debug for ComponentSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ComponentSet")
+            .field("name", &self.name)
+            .field("coordinates", &ElidedList(&self.coordinates))
+            .field("components", &ElidedList(&self.components))
+            .field("segments", &ElidedList(&self.segments))
+            .field("bounding_boxes", &ElidedList(&self.bounding_boxes))
+            .field("writer_id", &self.writer_id)
+            .field("style", &self.style)
+            .finish()
+    }
+}
+
+impl ComponentSet {
+    /// Returns an iterator over the coordinates of the pen components, in the order of the components. `.DT` components have no
+    /// coordinates.
+    pub fn coordinates_iter(&self) -> impl Iterator<Item = &Coordinate> {
+        (0..self.components.len())
+            .filter_map(|index| self.points_for_component(index))
+            .flatten()
+    }
+
+    /// Returns the coordinates of a component.
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - The index of the component in `components`. Unlike in component lists, `.DT` and empty components are counted.
+    ///
+    /// # Returns
+    ///
+    /// The coordinates of the component, or `None` if the component does not exist or is a `.DT` component.
+    #[must_use]
+    pub fn points_for_component(&self, index: usize) -> Option<&[Coordinate]> {
+        match self.components.get(index)? {
+            // The range of an empty component may lie past the end of the coordinates
+            Component::PenDown(range) | Component::PenUp(range) if range.is_empty() => Some(&[]),
+            Component::PenDown(range) | Component::PenUp(range) => self.coordinates.get(range.clone()),
+            Component::Dt(_) => None,
+        }
+    }
+
+    /// Returns the segments at a hierarchy level, such as `CHARACTER`. Hierarchy levels are matched case-sensitively.
+    pub fn segments_at<'a>(&'a self, hierarchy: &'a str) -> impl Iterator<Item = &'a Segment> {
+        self.segments.iter().filter(move |segment| &*segment.hierarchy == hierarchy)
+    }
+
+    /// Returns the segments at a hierarchy level, matching the level while ignoring ASCII case. See `segments_at`.
+    pub fn segments_at_ignore_ascii_case<'a>(&'a self, hierarchy: &'a str) -> impl Iterator<Item = &'a Segment> {
+        self.segments
+            .iter()
+            .filter(move |segment| segment.hierarchy.eq_ignore_ascii_case(hierarchy))
+    }
+
+    /// Returns the coordinates of a segment, concatenated in the order of its coordinate ranges.
+    ///
+    /// # Arguments
+    ///
+    /// * `segment` - The segment, which must belong to this set.
+    ///
+    /// # Errors
+    ///
+    /// * `UniPenError::Validation` - If a coordinate range of the segment is outside of the coordinates of this set.
+    ///
+    pub fn segment_points<'a>(&'a self, segment: &Segment) -> Result<impl Iterator<Item = &'a Coordinate> + 'a, UniPenError> {
+        let slices = segment
+            .coordinates
+            .iter()
+            .map(|range| {
+                self.coordinates.get(range.clone()).ok_or_else(|| {
+                    UniPenError::Validation(format!(
+                        "Segment coordinates {}..={} are out of bounds of {} coordinates",
+                        range.start(),
+                        range.end(),
+                        self.coordinates.len()
+                    ))
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(slices.into_iter().flatten())
+    }
+
+    /// Returns the time from the first to the last coordinate. `.DT` components before the first or after the last
+    /// coordinate extend the duration, while gaps between coordinates are already part of their times.
+    ///
+    /// # Returns
+    ///
+    /// The duration, or `None` if the set has no components.
+    #[must_use]
+    pub fn duration(&self) -> Option<Duration> {
+        if self.components.is_empty() {
+            return None;
+        }
+        let dt_sum = |components: &[Component]| -> Duration {
+            components
+                .iter()
+                .filter_map(|component| match component {
+                    Component::Dt(dt) => Some(*dt),
+                    _ => None,
+                })
+                .sum()
+        };
+        let has_coordinates = |component: &Component| match component {
+            Component::PenDown(range) | Component::PenUp(range) => !range.is_empty(),
+            Component::Dt(_) => false,
+        };
+        let (Some(first), Some(last)) = (
+            self.components.iter().position(has_coordinates),
+            self.components.iter().rposition(has_coordinates),
+        ) else {
+            return Some(dt_sum(&self.components));
+        };
+        let coordinates = self.coordinates.first()?.time..=self.coordinates.last()?.time;
+        Some(
+            coordinates.end().saturating_sub(*coordinates.start())
+                + dt_sum(&self.components[..first])
+                + dt_sum(&self.components[last + 1..]),
+        )
+    }
+
+    /// Returns the mean time between consecutive coordinates. Coordinates separated by a `.DT` component are not
+    /// consecutive, and times that go backwards count as no time.
+    ///
+    /// # Returns
+    ///
+    /// The mean interval, or `None` if no two coordinates are consecutive.
+    #[must_use]
+    pub fn mean_sampling_interval(&self) -> Option<Duration> {
+        let mut total = Duration::ZERO;
+        let mut count: u32 = 0;
+        let mut previous: Option<&Coordinate> = None;
+        for component in self.components.iter() {
+            let Some(points) = (match component {
+                Component::PenDown(range) | Component::PenUp(range) => self.coordinates.get(range.clone()),
+                Component::Dt(_) => None,
+            }) else {
+                previous = None;
+                continue;
+            };
+            for coordinate in points {
+                if let Some(previous) = previous {
+                    total += coordinate.time.saturating_sub(previous.time);
+                    count += 1;
+                }
+                previous = Some(coordinate);
+            }
+        }
+        (count > 0).then(|| total / count)
+    }
+
+    /// Returns the number of `.PEN_DOWN` components, counting strokes without coordinates.
+    #[must_use]
+    pub fn stroke_count(&self) -> usize {
+        self.components
+            .iter()
+            .filter(|component| matches!(component, Component::PenDown(_)))
+            .count()
+    }
+
+    /// Returns the length of the path through the coordinates of each `.PEN_DOWN` component, in component order. A
+    /// stroke with fewer than two coordinates has length 0.
+    #[must_use]
+    pub fn stroke_lengths(&self) -> Vec<f64> {
+        self.components
+            .iter()
+            .filter_map(|component| match component {
+                Component::PenDown(range) => Some(self.coordinates.get(range.clone()).unwrap_or_default()),
+                _ => None,
+            })
+            .map(|points| {
+                points
+                    .windows(2)
+                    .map(|pair| (pair[1].x_position - pair[0].x_position).hypot(pair[1].y_position - pair[0].y_position))
+                    .sum()
+            })
+            .collect()
+    }
+
+    /// Returns the summed length of all `.PEN_DOWN` strokes.
+    #[must_use]
+    pub fn total_ink_length(&self) -> f64 {
+        self.stroke_lengths().into_iter().sum()
+    }
+
+    /// Returns the speed of the pen between each pair of consecutive coordinates of a component, in position units per
+    /// second. Pairs whose time does not increase are skipped, so there can be fewer than one speed per pair.
+    ///
+    /// # Arguments
+    ///
+    /// * `component_index` - The index of the component in `components`, as in `points_for_component`.
+    ///
+    /// # Returns
+    ///
+    /// The speeds, or `None` if the component does not exist or is a `.DT` component.
+    #[must_use]
+    pub fn velocity_profile(&self, component_index: usize) -> Option<Vec<f64>> {
+        Some(
+            timed_velocities(self.points_for_component(component_index)?)
+                .map(|(_, speed)| speed)
+                .collect(),
+        )
+    }
+
+    /// Returns the change in speed between each pair of consecutive speeds of `velocity_profile`, in position units per
+    /// second squared. Each speed is timed at the middle of its pair of coordinates, and pairs of speeds whose time does
+    /// not increase are skipped.
+    ///
+    /// # Arguments
+    ///
+    /// * `component_index` - The index of the component in `components`, as in `points_for_component`.
+    ///
+    /// # Returns
+    ///
+    /// The accelerations, or `None` if the component does not exist or is a `.DT` component.
+    #[must_use]
+    pub fn acceleration_profile(&self, component_index: usize) -> Option<Vec<f64>> {
+        let velocities: Vec<(f64, f64)> = timed_velocities(self.points_for_component(component_index)?).collect();
+        Some(
+            velocities
+                .windows(2)
+                .filter_map(|pair| {
+                    let ((t0, v0), (t1, v1)) = (pair[0], pair[1]);
+                    (t1 > t0).then(|| (v1 - v0) / (t1 - t0))
+                })
+                .collect(),
+        )
+    }
+
+    /// Returns the bounding box of all coordinates, or `None` if there are no coordinates. The box covers every coordinate.
+    #[must_use]
+    pub fn extent(&self) -> Option<BoundingBox> {
+        let first = self.coordinates.first()?;
+        let (mut x_min, mut y_min, mut x_max, mut y_max) =
+            (first.x_position, first.y_position, first.x_position, first.y_position);
+        for coordinate in self.coordinates.iter() {
+            x_min = x_min.min(coordinate.x_position);
+            y_min = y_min.min(coordinate.y_position);
+            x_max = x_max.max(coordinate.x_position);
+            y_max = y_max.max(coordinate.y_position);
+        }
+        Some(BoundingBox {
+            x_min,
+            y_min,
+            x_max,
+            y_max,
+            coordinates: Rc::new([0..=self.coordinates.len() - 1]),
+        })
+    }
+}
+
+/// Yields the time in seconds halfway between each pair of consecutive coordinates with increasing times, together with
+/// the speed of the pen between them.
+fn timed_velocities(points: &[Coordinate]) -> impl Iterator<Item = (f64, f64)> + '_ {
+    points.windows(2).filter_map(|pair| {
+        let elapsed = pair[1].time.checked_sub(pair[0].time).filter(|elapsed| !elapsed.is_zero())?;
+        let distance = (pair[1].x_position - pair[0].x_position).hypot(pair[1].y_position - pair[0].y_position);
+        Some(((pair[0].time + elapsed / 2).as_secs_f64(), distance / elapsed.as_secs_f64()))
+    })
 }
 
+/// A sampled point of the pen. Equality compares the floating point values exactly, so a coordinate with a NaN value is
+/// not equal to itself. Use `approx_eq` to compare coordinates that went through arithmetic.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Coordinate {
     pub x_position: f64,
     pub y_position: f64,
+    #[cfg_attr(feature = "serde", serde(with = "duration_nanos"))]
     pub time: Duration,
     pub pressure: Option<f64>,
     pub z_position: Option<f64>,
@@ -248,12 +1037,122 @@ pub struct Coordinate {
     pub phi: Option<f64>,
 }
 
+impl Coordinate {
+    /// Creates a coordinate without any of the optional values. These can be added with the `with_` methods.
+    ///
+    /// # Arguments
+    ///
+    /// * `x_position` - The X position.
+    /// * `y_position` - The Y position.
+    /// * `time` - The time of the sample.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use unipen::model::Coordinate;
+    ///
+    /// let coordinate = Coordinate::new(10.0, 20.0, Duration::from_millis(5)).with_pressure(0.5);
+    /// assert_eq!(coordinate.pressure, Some(0.5));
+    /// assert_eq!(coordinate.z_position, None);
+    /// ```
+    #[must_use]
+    pub const fn new(x_position: f64, y_position: f64, time: Duration) -> Self {
+        Self {
+            x_position,
+            y_position,
+            time,
+            pressure: None,
+            z_position: None,
+            button: None,
+            rho: None,
+            theta: None,
+            phi: None,
+        }
+    }
+
+    /// Sets the pressure of the coordinate.
+    #[must_use]
+    pub const fn with_pressure(mut self, pressure: f64) -> Self {
+        self.pressure = Some(pressure);
+        self
+    }
+
+    /// Sets the Z position of the coordinate.
+    #[must_use]
+    pub const fn with_z_position(mut self, z_position: f64) -> Self {
+        self.z_position = Some(z_position);
+        self
+    }
+
+    /// Sets the button of the coordinate.
+    #[must_use]
+    pub const fn with_button(mut self, button: f64) -> Self {
+        self.button = Some(button);
+        self
+    }
+
+    /// Sets the rho of the coordinate.
+    #[must_use]
+    pub const fn with_rho(mut self, rho: f64) -> Self {
+        self.rho = Some(rho);
+        self
+    }
+
+    /// Sets the theta of the coordinate.
+    #[must_use]
+    pub const fn with_theta(mut self, theta: f64) -> Self {
+        self.theta = Some(theta);
+        self
+    }
+
+    /// Sets the phi of the coordinate.
+    #[must_use]
+    pub const fn with_phi(mut self, phi: f64) -> Self {
+        self.phi = Some(phi);
+        self
+    }
+
+    /// Compares two coordinates with a tolerance.
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - The coordinate to compare to.
+    /// * `epsilon` - The largest allowed difference between the positions and optional values of the coordinates.
+    ///
+    /// # Returns
+    ///
+    /// Whether all values differ by at most `epsilon`, and the same optional values are present. Times are compared exactly.
+    #[must_use]
+    pub fn approx_eq(&self, other: &Self, epsilon: f64) -> bool {
+        let close = |a: f64, b: f64| (a - b).abs() <= epsilon;
+        let close_optional = |a: Option<f64>, b: Option<f64>| match (a, b) {
+            (Some(a), Some(b)) => close(a, b),
+            (None, None) => true,
+            _ => false,
+        };
+        close(self.x_position, other.x_position)
+            && close(self.y_position, other.y_position)
+            && self.time == other.time
+            && close_optional(self.pressure, other.pressure)
+            && close_optional(self.z_position, other.z_position)
+            && close_optional(self.button, other.button)
+            && close_optional(self.rho, other.rho)
+            && close_optional(self.theta, other.theta)
+            && close_optional(self.phi, other.phi)
+    }
+}
+
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Component {
     PenDown(RangeInclusive<CoordinateIndex>),
     PenUp(RangeInclusive<CoordinateIndex>),
-    Dt(Duration),
+    Dt(#[cfg_attr(feature = "serde", serde(with = "duration_nanos"))] Duration),
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Segment {
     pub hierarchy: Rc<str>,
     pub coordinates: Rc<[RangeInclusive<CoordinateIndex>]>,
@@ -261,6 +1160,99 @@ pub struct Segment {
     pub label: Option<Rc<str>>,
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Copy, PartialEq)]
+pub enum Acceptance {
+    Accept,
+    Reject,
+}
+
+impl TryFrom<&Reserved> for Acceptance {
+    type Error = UniPenError;
+
+    fn try_from(value: &Reserved) -> Result<Self, UniPenError> {
+        match value {
+            Reserved::Accept => Ok(Self::Accept),
+            Reserved::Reject => Ok(Self::Reject),
+            _ => Err(translation_err!("No acceptance rule")),
+        }
+    }
+}
+
+/// The components of a segment that recognition results refer to.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, PartialEq)]
+pub struct SegmentReference {
+    pub(crate) hierarchy: Rc<str>,
+    pub(crate) components: Rc<[RangeInclusive<ComponentIndex>]>,
+}
+
+impl SegmentReference {
+    #[must_use]
+    pub fn hierarchy(&self) -> &str {
+        &self.hierarchy
+    }
+
+    #[must_use]
+    pub fn components(&self) -> &[RangeInclusive<ComponentIndex>] {
+        &self.components
+    }
+}
+
+/// A recognized label of a segment, declared by `.REC_LABELS`. Each label of the statement is a separate `RecLabel`.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, PartialEq)]
+pub struct RecLabel {
+    pub(crate) segment_ref: SegmentReference,
+    pub(crate) acceptance: Option<Acceptance>,
+    pub(crate) label: Rc<str>,
+}
+
+impl RecLabel {
+    #[must_use]
+    pub const fn segment_ref(&self) -> &SegmentReference {
+        &self.segment_ref
+    }
+
+    #[must_use]
+    pub const fn acceptance(&self) -> Option<&Acceptance> {
+        self.acceptance.as_ref()
+    }
+
+    #[must_use]
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+}
+
+/// A recognition score of a segment's label, declared by `.REC_SCORES`. Scores are not normalized.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, PartialEq)]
+pub struct RecScore {
+    pub(crate) segment_ref: SegmentReference,
+    pub(crate) score: f64,
+    pub(crate) label: Rc<str>,
+}
+
+impl RecScore {
+    #[must_use]
+    pub const fn segment_ref(&self) -> &SegmentReference {
+        &self.segment_ref
+    }
+
+    #[must_use]
+    pub const fn score(&self) -> f64 {
+        self.score
+    }
+
+    #[must_use]
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+}
+
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, PartialEq)]
 pub struct BoundingBox {
     pub x_min: f64,
     pub y_min: f64,
@@ -268,3 +1260,56 @@ pub struct BoundingBox {
     pub y_max: f64,
     pub coordinates: Rc<[RangeInclusive<CoordinateIndex>]>,
 }
+
+// Lists longer than this are elided by `ElidedList`
+const ELIDED_LIST_LENGTH: usize = 8;
+
+// Debug formats a list, showing only the first two entries of a long list unless the alternate form is used
+pub(crate) struct ElidedList<'a, T>(pub(crate) &'a [T]);
+
+impl<T: fmt::Debug> fmt::Debug for ElidedList<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if f.alternate() || self.0.len() <= ELIDED_LIST_LENGTH {
+            return f.debug_list().entries(self.0).finish();
+        }
+        f.debug_list()
+            .entries(&self.0[..2])
+            .entry(&format_args!("… +{} more", self.0.len() - 2))
+            .finish()
+    }
+}
+
+// Durations are serialized as whole nanoseconds so they are lossless across formats
+#[cfg(feature = "serde")]
+mod duration_nanos {
+    use std::time::Duration;
+
+    use serde::{ser::Error as _, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        let nanos = u64::try_from(duration.as_nanos()).map_err(S::Error::custom)?;
+        serializer.serialize_u64(nanos)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        u64::deserialize(deserializer).map(Duration::from_nanos)
+    }
+
+    pub mod option {
+        use std::time::Duration;
+
+        use serde::{ser::Error as _, Deserialize, Deserializer, Serializer};
+
+        #[allow(clippy::ref_option)] // Signature required by serde(with)
+        pub fn serialize<S: Serializer>(duration: &Option<Duration>, serializer: S) -> Result<S::Ok, S::Error> {
+            match duration {
+                Some(duration) => serializer.serialize_some(&u64::try_from(duration.as_nanos()).map_err(S::Error::custom)?),
+                None => serializer.serialize_none(),
+            }
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Duration>, D::Error> {
+            Ok(Option::<u64>::deserialize(deserializer)?.map(Duration::from_nanos))
+        }
+    }
+}
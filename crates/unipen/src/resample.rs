@@ -0,0 +1,203 @@
+use std::{convert::Infallible, ops::RangeInclusive, rc::Rc, time::Duration};
+
+use crate::{
+    error::UniPenError,
+    model::{BoundingBox, Component, ComponentSet, Coordinate, CoordinateIndex, Segment},
+};
+
+fn lerp(a: f64, b: f64, t: f64) -> f64 {
+    (b - a).mul_add(t, a)
+}
+
+// Optional coordinate types are only interpolated when both ends have them
+fn lerp_optional(a: Option<f64>, b: Option<f64>, t: f64) -> Option<f64> {
+    Some(lerp(a?, b?, t))
+}
+
+// Interpolates between two coordinates, `t` being the fraction of the way from `a` to `b`
+fn interpolate(a: &Coordinate, b: &Coordinate, t: f64, time: Duration) -> Coordinate {
+    Coordinate {
+        x_position: lerp(a.x_position, b.x_position, t),
+        y_position: lerp(a.y_position, b.y_position, t),
+        time,
+        pressure: lerp_optional(a.pressure, b.pressure, t),
+        z_position: lerp_optional(a.z_position, b.z_position, t),
+        button: lerp_optional(a.button, b.button, t),
+        rho: lerp_optional(a.rho, b.rho, t),
+        theta: lerp_optional(a.theta, b.theta, t),
+        phi: lerp_optional(a.phi, b.phi, t),
+    }
+}
+
+// An empty range starting at `start` when there are no coordinates
+#[allow(clippy::range_minus_one)]
+const fn coordinate_range(start: CoordinateIndex, len: usize) -> RangeInclusive<CoordinateIndex> {
+    if len == 0 {
+        start + 1..=start
+    } else {
+        start..=start + len - 1
+    }
+}
+
+fn resample_spatial_stroke(stroke: &[Coordinate], step: f64) -> Vec<Coordinate> {
+    let Some(first) = stroke.first() else {
+        return Vec::new();
+    };
+    let mut resampled = vec![first.clone()];
+    // Distance travelled since the last emitted point
+    let mut travelled = 0.0;
+    for pair in stroke.windows(2) {
+        let [a, b] = pair else { unreachable!() };
+        let length = (b.x_position - a.x_position).hypot(b.y_position - a.y_position);
+        let mut position = step - travelled;
+        while position <= length {
+            let t = position / length;
+            let time = Duration::from_secs_f64(lerp(a.time.as_secs_f64(), b.time.as_secs_f64(), t));
+            resampled.push(interpolate(a, b, t, time));
+            position += step;
+        }
+        travelled = length - (position - step);
+    }
+    resampled
+}
+
+// `start` is the index of the first coordinate of the stroke in the set, used in errors
+fn resample_temporal_stroke(stroke: &[Coordinate], dt: Duration, start: CoordinateIndex) -> Result<Vec<Coordinate>, UniPenError> {
+    if let Some(index) = stroke.windows(2).position(|pair| pair[1].time < pair[0].time) {
+        return Err(UniPenError::Validation(format!(
+            "Time is not monotonic at coordinate {}",
+            start + index + 1
+        )));
+    }
+    let (Some(first), Some(last)) = (stroke.first(), stroke.last()) else {
+        return Ok(Vec::new());
+    };
+    let mut resampled = Vec::new();
+    let mut time = first.time;
+    // Index of the last original point at or before `time`
+    let mut index = 0;
+    while time <= last.time {
+        while index + 1 < stroke.len() - 1 && stroke[index + 1].time <= time {
+            index += 1;
+        }
+        let a = &stroke[index];
+        let b = &stroke[(index + 1).min(stroke.len() - 1)];
+        let t = if b.time > a.time {
+            (time - a.time).as_secs_f64() / (b.time - a.time).as_secs_f64()
+        } else {
+            0.0
+        };
+        resampled.push(interpolate(a, b, t, time));
+        time += dt;
+    }
+    Ok(resampled)
+}
+
+impl ComponentSet {
+    // Rebuilds the set with resampled pen down components. Pen up components are kept as they are.
+    fn resample<E>(
+        &self,
+        mut resample_stroke: impl FnMut(&[Coordinate], CoordinateIndex) -> Result<Vec<Coordinate>, E>,
+    ) -> Result<Self, E> {
+        let mut coordinates = Vec::new();
+        let mut components = Vec::new();
+        // The old and new coordinate ranges of each pen component
+        let mut mapping = Vec::new();
+        for component in self.components.iter() {
+            // The range of an empty component may lie past the end of the coordinates
+            let points = |range: &RangeInclusive<CoordinateIndex>| self.coordinates.get(range.clone()).unwrap_or_default();
+            let (range, new_coordinates) = match component {
+                Component::PenDown(range) => (range, resample_stroke(points(range), *range.start())?),
+                Component::PenUp(range) => (range, points(range).to_vec()),
+                Component::Dt(dt) => {
+                    components.push(Component::Dt(*dt));
+                    continue;
+                }
+            };
+            let new_range = coordinate_range(coordinates.len(), new_coordinates.len());
+            coordinates.extend(new_coordinates);
+            components.push(match component {
+                Component::PenDown(_) => Component::PenDown(new_range.clone()),
+                _ => Component::PenUp(new_range.clone()),
+            });
+            mapping.push((range.clone(), new_range));
+        }
+
+        // Points no longer correspond after resampling, so references are widened to whole components
+        let remap = |ranges: &[RangeInclusive<CoordinateIndex>]| -> Rc<[RangeInclusive<CoordinateIndex>]> {
+            let find = |index: &CoordinateIndex| mapping.iter().find(|(old, _)| old.contains(index)).map(|(_, new)| new);
+            ranges
+                .iter()
+                .filter_map(|range| Some(*find(range.start())?.start()..=*find(range.end())?.end()))
+                .collect()
+        };
+        Ok(Self {
+            name: self.name.clone(),
+            writer_id: self.writer_id.clone(),
+            style: self.style,
+            coordinates: coordinates.into(),
+            components: components.into(),
+            segments: self
+                .segments
+                .iter()
+                .map(|segment| Segment {
+                    hierarchy: segment.hierarchy.clone(),
+                    coordinates: remap(&segment.coordinates),
+                    quality: segment.quality,
+                    label: segment.label.clone(),
+                })
+                .collect(),
+            bounding_boxes: self
+                .bounding_boxes
+                .iter()
+                .map(|bounding_box| BoundingBox {
+                    x_min: bounding_box.x_min,
+                    y_min: bounding_box.y_min,
+                    x_max: bounding_box.x_max,
+                    y_max: bounding_box.y_max,
+                    coordinates: remap(&bounding_box.coordinates),
+                })
+                .collect(),
+        })
+    }
+
+    /// Resamples each pen down component to points that are `step` apart along the stroke, interpolating linearly between
+    /// the original points. The first point of each stroke is kept, so single point strokes are unchanged. Pen up and `.DT`
+    /// components are kept as they are.
+    ///
+    /// Segments and bounding boxes that refer to points of a resampled component are widened to the whole component.
+    ///
+    /// # Arguments
+    ///
+    /// * `step` - The distance between resampled points, in coordinate units.
+    ///
+    /// # Panics
+    ///
+    /// If `step` is not positive.
+    #[must_use]
+    pub fn resample_spatial(&self, step: f64) -> Self {
+        assert!(step > 0.0, "Resampling step must be positive, got {step}");
+        let Ok(resampled) = self.resample(|stroke, _| Ok::<_, Infallible>(resample_spatial_stroke(stroke, step)));
+        resampled
+    }
+
+    /// Resamples each pen down component to points that are `dt` apart in time, interpolating linearly between the original
+    /// points. Each stroke starts at its first point. Pen up and `.DT` components are kept as they are.
+    ///
+    /// Segments and bounding boxes that refer to points of a resampled component are widened to the whole component.
+    ///
+    /// # Arguments
+    ///
+    /// * `dt` - The time between resampled points.
+    ///
+    /// # Errors
+    ///
+    /// * `UniPenError::Validation` - If `dt` is zero, or the time of a pen down component decreases.
+    ///
+    pub fn resample_temporal(&self, dt: Duration) -> Result<Self, UniPenError> {
+        if dt.is_zero() {
+            return Err(UniPenError::Validation("Resampling interval must be positive".into()));
+        }
+        self.resample(|stroke, start| resample_temporal_stroke(stroke, dt, start))
+    }
+}
@@ -0,0 +1,54 @@
+use crate::model::{Component, ComponentSet, Coordinate};
+
+// Averages the values of the points that have one
+fn mean(values: impl Iterator<Item = Option<f64>>) -> Option<f64> {
+    let (sum, count) = values
+        .flatten()
+        .fold((0.0, 0_u32), |(sum, count), value| (sum + value, count + 1));
+    (count > 0).then(|| sum / f64::from(count))
+}
+
+fn smooth_stroke(stroke: &mut [Coordinate], half_window: usize) {
+    let original = stroke.to_vec();
+    for (index, coordinate) in stroke.iter_mut().enumerate() {
+        // The window is truncated at the ends of the stroke
+        let window = &original[index.saturating_sub(half_window)..original.len().min(index + half_window + 1)];
+        coordinate.x_position = mean(window.iter().map(|point| Some(point.x_position))).unwrap_or(coordinate.x_position);
+        coordinate.y_position = mean(window.iter().map(|point| Some(point.y_position))).unwrap_or(coordinate.y_position);
+        if coordinate.pressure.is_some() {
+            coordinate.pressure = mean(window.iter().map(|point| point.pressure));
+        }
+    }
+}
+
+impl ComponentSet {
+    /// Smooths the X and Y positions and the pressure of each pen down component with a centered moving average. Points near
+    /// the ends of a stroke are averaged over the part of the window inside the stroke, so strokes are not averaged with each
+    /// other or with pen up components. The number of points is unchanged, so segments and bounding boxes keep referring to
+    /// the same points.
+    ///
+    /// # Arguments
+    ///
+    /// * `window` - The number of points to average. Even windows are rounded up to the next odd number, so that the window
+    ///   is centered. A window of 1 leaves the set unchanged.
+    ///
+    /// # Panics
+    ///
+    /// If `window` is zero.
+    #[must_use]
+    pub fn smooth(&self, window: usize) -> Self {
+        assert!(window > 0, "Smoothing window must be positive");
+        let mut coordinates = self.coordinates.to_vec();
+        for component in self.components.iter() {
+            if let Component::PenDown(range) = component {
+                if !range.is_empty() {
+                    smooth_stroke(&mut coordinates[range.clone()], window / 2);
+                }
+            }
+        }
+        Self {
+            coordinates: coordinates.into(),
+            ..self.clone()
+        }
+    }
+}
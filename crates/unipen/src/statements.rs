@@ -1,18 +1,495 @@
 use lazy_regex::regex;
-use log::debug;
-use pest::iterators::Pair;
+use log::{debug, warn};
+use pest::iterators::{Pair, Pairs};
 use pest::Parser;
-use std::path::Path;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt::{self, Display, Formatter};
+use std::io::Write;
+use std::mem::{discriminant, Discriminant};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 use std::{fs, rc::Rc};
 
 use crate::error::{translation_err, UniPenError};
+use crate::model::{ElidedList, UniPen};
+use crate::writer::{escape_free_text, escape_label};
 
+/// The pest parser of the UniPen grammar, for parsing with the `Rule`s of the grammar directly. `Rule::file` parses a
+/// whole file, and each statement has a rule, such as `Rule::s_pen_down`. `parse_pairs` parses a file without a dependency
+/// on pest.
 #[derive(Parser)]
 #[grammar = "statements.pest"]
-struct StatementParser;
+pub struct StatementParser;
 
-/// Parses the UniPen keyword statements from a file. If the file contains an include directive, the include directory must be provided. Recursively parses included files.
-/// `.INCLUDE` statements are generated for each file path parsed. The data in the original `.INCLUDE` statement are not outputted.
+/// Resolves the content of files included with `.INCLUDE` statements.
+pub trait IncludeResolver {
+    /// Returns the content of an included file.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the included file, as written in the `.INCLUDE` statement.
+    ///
+    /// # Errors
+    ///
+    /// Any `UniPenError` if the content could not be retrieved.
+    fn resolve(&self, path: &str) -> Result<String, UniPenError>;
+
+    /// Returns the name of an included file used in the generated `.INCLUDE` and `EndOfInput` statements and in errors. By
+    /// default, the path as written in the `.INCLUDE` statement.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the included file, as written in the `.INCLUDE` statement.
+    fn resolved_name(&self, path: &str) -> String {
+        path.to_owned()
+    }
+
+    /// Returns the content of a file included by another file. By default, `resolve`, ignoring the including file.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the included file, as written in the `.INCLUDE` statement.
+    /// * `including` - The name of the including file, as named by `resolved_name` or given to the parser.
+    ///
+    /// # Errors
+    ///
+    /// Any `UniPenError` if the content could not be retrieved.
+    fn resolve_from(&self, path: &str, including: &str) -> Result<String, UniPenError> {
+        let _ = including;
+        self.resolve(path)
+    }
+
+    /// Returns the name of a file included by another file. By default, `resolved_name`, ignoring the including file.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the included file, as written in the `.INCLUDE` statement.
+    /// * `including` - The name of the including file, as named by `resolved_name` or given to the parser.
+    fn resolved_name_from(&self, path: &str, including: &str) -> String {
+        let _ = including;
+        self.resolved_name(path)
+    }
+
+    /// Returns a name that is the same for every path of an included file, with which `ParseOptions::reuse_includes`
+    /// recognizes a file that was already included. By default, `resolved_name_from`.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the included file, as written in the `.INCLUDE` statement.
+    /// * `including` - The name of the including file, as named by `resolved_name` or given to the parser.
+    fn canonical_name(&self, path: &str, including: &str) -> String {
+        self.resolved_name_from(path, including)
+    }
+
+    /// Expands the path of an `.INCLUDE` statement to the paths of the files to include, in order. By default, the path
+    /// itself.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the included file, as written in the `.INCLUDE` statement.
+    /// * `including` - The name of the including file, as named by `resolved_name` or given to the parser.
+    ///
+    /// # Errors
+    ///
+    /// Any `UniPenError` if the path could not be expanded.
+    fn expand(&self, path: &str, including: &str) -> Result<Vec<String>, UniPenError> {
+        let _ = including;
+        Ok(vec![path.to_owned()])
+    }
+}
+
+/// The character encoding of UniPen files.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Encoding {
+    #[default]
+    Utf8,
+    /// ISO-8859-1, used by many older corpora.
+    Latin1,
+}
+
+impl Encoding {
+    /// Decodes the content of a file.
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - The content of the file.
+    /// * `name` - The name of the file, used in errors.
+    ///
+    /// # Returns
+    ///
+    /// The decoded content.
+    ///
+    /// # Errors
+    ///
+    /// * `UniPenError::InvalidUtf8` - If the encoding is UTF-8 and the content is not valid UTF-8.
+    ///
+    pub fn decode(self, bytes: Vec<u8>, name: &str) -> Result<String, UniPenError> {
+        match self {
+            Self::Utf8 => String::from_utf8(bytes).map_err(|err| UniPenError::InvalidUtf8 {
+                name: name.into(),
+                offset: err.utf8_error().valid_up_to(),
+            }),
+            // The code points of ISO-8859-1 are the first 256 code points of Unicode
+            Self::Latin1 => Ok(bytes.into_iter().map(char::from).collect()),
+        }
+    }
+}
+
+/// How `.INCLUDE` paths with the wildcards `*` and `?` are treated. A `*` matches any part of a file or directory name, and
+/// a `?` matches a single character.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum IncludeGlobs {
+    /// Paths are file names, even with wildcard characters.
+    #[default]
+    Literal,
+    /// Paths with wildcards are expanded to the matching files, in sorted order. A path that matches no files includes
+    /// nothing, with a warning.
+    Expand,
+    /// Like `Expand`, but a path that matches no files is a `UniPenError::Io` error of kind `NotFound`.
+    ExpandStrict,
+}
+
+// Matches a path with wildcards against the files of a directory, returning the paths of the matches relative to it
+fn glob(directory: &Path, pattern: &str) -> Vec<PathBuf> {
+    let mut matches = vec![PathBuf::new()];
+    for component in Path::new(pattern).components() {
+        let component = component.as_os_str().to_string_lossy();
+        if !component.contains(['*', '?']) {
+            for path in &mut matches {
+                path.push(component.as_ref());
+            }
+            continue;
+        }
+        let pattern: String = component
+            .chars()
+            .map(|c| match c {
+                '*' => ".*".to_owned(),
+                '?' => ".".to_owned(),
+                c => regex::escape(&c.to_string()),
+            })
+            .collect();
+        let Ok(regex) = regex::Regex::new(&format!("^{pattern}$")) else {
+            return Vec::new();
+        };
+        matches = matches
+            .iter()
+            .flat_map(|path| {
+                let parent = directory.join(path);
+                // The directory of a file named without one is the working directory
+                let parent = if parent.as_os_str().is_empty() {
+                    PathBuf::from(".")
+                } else {
+                    parent
+                };
+                fs::read_dir(parent)
+                    .into_iter()
+                    .flatten()
+                    .filter_map(Result::ok)
+                    .filter_map(|entry| {
+                        let name = entry.file_name().into_string().ok()?;
+                        regex.is_match(&name).then(|| path.join(name))
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+    }
+    matches.retain(|path| directory.join(path).is_file());
+    matches.sort();
+    matches
+}
+
+// Reads a file, checking its size before reading it
+fn read_file(path: &Path, max_file_bytes: Option<u64>) -> Result<Vec<u8>, UniPenError> {
+    if let Some(limit) = max_file_bytes {
+        let size = fs::metadata(path).map_err(UniPenError::Io)?.len();
+        if size > limit {
+            return Err(UniPenError::FileTooLarge {
+                path: path.to_string_lossy().into_owned(),
+                size,
+                limit,
+            });
+        }
+    }
+    fs::read(path).map_err(UniPenError::Io)
+}
+
+/// Resolves included files on the filesystem. A file is looked up relative to the directory of the file including it
+/// first, as most corpora include files next to their own, and relative to the include directory if it is not there.
+/// Absolute paths are used as they are.
+#[derive(Debug, Clone)]
+pub struct FileSystemResolver {
+    include: Option<PathBuf>,
+    encoding: Encoding,
+    max_file_bytes: Option<u64>,
+    include_dir_only: bool,
+    globs: IncludeGlobs,
+}
+
+impl FileSystemResolver {
+    /// Creates a resolver that reads UTF-8 encoded included files from the directory of the including file or from the
+    /// include directory.
+    #[must_use]
+    pub fn new(include: &Path) -> Self {
+        Self {
+            include: Some(include.to_path_buf()),
+            ..Self::without_include_dir()
+        }
+    }
+
+    /// Creates a resolver that reads UTF-8 encoded included files from the directory of the including file only.
+    #[must_use]
+    pub const fn without_include_dir() -> Self {
+        Self {
+            include: None,
+            encoding: Encoding::Utf8,
+            max_file_bytes: None,
+            include_dir_only: false,
+            globs: IncludeGlobs::Literal,
+        }
+    }
+
+    /// Sets the encoding of the included files.
+    #[must_use]
+    pub const fn encoding(mut self, encoding: Encoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    /// Sets the largest included file that is read. Larger files fail with `UniPenError::FileTooLarge`.
+    #[must_use]
+    pub const fn max_file_bytes(mut self, max_file_bytes: u64) -> Self {
+        self.max_file_bytes = Some(max_file_bytes);
+        self
+    }
+
+    /// Sets whether included files are only looked up in the include directory, ignoring the directory of the including
+    /// file, as before relative includes were supported.
+    #[must_use]
+    pub const fn include_dir_only(mut self, include_dir_only: bool) -> Self {
+        self.include_dir_only = include_dir_only;
+        self
+    }
+
+    /// Sets how include paths with wildcards are treated.
+    #[must_use]
+    pub const fn globs(mut self, globs: IncludeGlobs) -> Self {
+        self.globs = globs;
+        self
+    }
+
+    // The path of an included file, next to the including file if it exists there
+    fn locate(&self, path: &str, including: Option<&str>) -> PathBuf {
+        // Joining would also keep an absolute path, but would look for it twice
+        if Path::new(path).is_absolute() {
+            return PathBuf::from(path);
+        }
+        let relative = including
+            .filter(|_| !self.include_dir_only)
+            .and_then(|including| Path::new(including).parent())
+            .map(|directory| directory.join(path));
+        match (relative, &self.include) {
+            (Some(relative), Some(include)) if !relative.is_file() => include.join(path),
+            (Some(relative), _) => relative,
+            (None, Some(include)) => include.join(path),
+            (None, None) => PathBuf::from(path),
+        }
+    }
+
+    fn read(&self, path: &Path) -> Result<String, UniPenError> {
+        debug!("Reading included file {:?}", path);
+        let bytes = read_file(path, self.max_file_bytes)?;
+        self.encoding.decode(bytes, path.to_string_lossy().as_ref())
+    }
+}
+
+impl IncludeResolver for FileSystemResolver {
+    fn resolve(&self, path: &str) -> Result<String, UniPenError> {
+        self.read(&self.locate(path, None))
+    }
+
+    fn resolved_name(&self, path: &str) -> String {
+        self.locate(path, None).to_string_lossy().into_owned()
+    }
+
+    fn resolve_from(&self, path: &str, including: &str) -> Result<String, UniPenError> {
+        self.read(&self.locate(path, Some(including)))
+    }
+
+    fn resolved_name_from(&self, path: &str, including: &str) -> String {
+        self.locate(path, Some(including)).to_string_lossy().into_owned()
+    }
+
+    // Falls back to the resolved name for files that do not exist, which fail to be read anyway
+    fn canonical_name(&self, path: &str, including: &str) -> String {
+        let path = self.locate(path, Some(including));
+        fs::canonicalize(&path).unwrap_or(path).to_string_lossy().into_owned()
+    }
+
+    // Like `locate`, the matches next to the including file are used if there are any. The expanded paths are relative to
+    // the same directory, so that `locate` finds them there again.
+    fn expand(&self, path: &str, including: &str) -> Result<Vec<String>, UniPenError> {
+        if self.globs == IncludeGlobs::Literal || !path.contains(['*', '?']) {
+            return Ok(vec![path.to_owned()]);
+        }
+        let relative = Some(including)
+            .filter(|_| !self.include_dir_only)
+            .and_then(|including| Path::new(including).parent());
+        let matches = [relative, self.include.as_deref()]
+            .into_iter()
+            .flatten()
+            .map(|directory| glob(directory, path))
+            .find(|matches| !matches.is_empty())
+            .unwrap_or_default();
+        if matches.is_empty() {
+            if self.globs == IncludeGlobs::ExpandStrict {
+                return Err(UniPenError::Io(std::io::Error::new(
+                    std::io::ErrorKind::NotFound,
+                    format!("No files match the include path {path}"),
+                )));
+            }
+            warn!("No files match the include path {path} of {including}");
+        }
+        Ok(matches.into_iter().map(|path| path.to_string_lossy().into_owned()).collect())
+    }
+}
+
+/// Options for parsing UniPen keyword statements.
+#[derive(Debug, Clone, Default)]
+pub struct ParseOptions {
+    include: Option<PathBuf>,
+    max_depth: Option<usize>,
+    strict: bool,
+    encoding: Encoding,
+    omit_synthetic_includes: bool,
+    max_file_bytes: Option<u64>,
+    lenient_keywords: bool,
+    include_dir_only: bool,
+    include_globs: IncludeGlobs,
+    reuse_includes: bool,
+}
+
+impl ParseOptions {
+    /// Creates the default options: no include directory, unlimited include depth, lenient parsing, UTF-8 files,
+    /// synthetic `.INCLUDE` statements and no custom keywords.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the directory that included files are resolved against when they are not next to the file including them.
+    #[must_use]
+    pub fn include_dir(mut self, include: &Path) -> Self {
+        self.include = Some(include.to_path_buf());
+        self
+    }
+
+    /// Sets how deeply includes may be nested. The parsed file itself is at depth 0, so a depth of 0 forbids includes.
+    #[must_use]
+    pub const fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = Some(max_depth);
+        self
+    }
+
+    /// Sets whether parsing is strict. When strict, statement arguments that could not be converted and repeated statements
+    /// that may only appear once, such as `.VERSION`, are errors. When lenient, such arguments are dropped with a warning and
+    /// repeated statements are kept.
+    #[must_use]
+    pub const fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Sets the encoding of the parsed file and the included files.
+    #[must_use]
+    pub const fn encoding(mut self, encoding: Encoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    /// Sets whether the synthetic `.INCLUDE` and `EndOfInput` statements marking the start and end of the parsed file and
+    /// each included file are generated. Without them, only the statements present in the files are returned.
+    #[must_use]
+    pub const fn synthetic_includes(mut self, synthetic_includes: bool) -> Self {
+        self.omit_synthetic_includes = !synthetic_includes;
+        self
+    }
+
+    /// Sets the largest file that is read, so that huge files fail before they are read into memory. The size of each
+    /// file is checked before reading it. When parsing lazily, the limit also applies to the total size of the parsed file
+    /// and the included files read so far, which is reported as the size of the file that exceeded it.
+    #[must_use]
+    pub const fn max_file_bytes(mut self, max_file_bytes: u64) -> Self {
+        self.max_file_bytes = Some(max_file_bytes);
+        self
+    }
+
+    /// Sets whether keywords that the grammar does not know are accepted when they were declared with `.KEYWORD` earlier in
+    /// the file or its included files. When lenient, such statements are parsed as `Keyword::Custom` with the rest of the
+    /// statement as a single free text argument, so that vendor extensions can be read. Unknown keywords that were not
+    /// declared are still errors.
+    #[must_use]
+    pub const fn lenient_keywords(mut self, lenient_keywords: bool) -> Self {
+        self.lenient_keywords = lenient_keywords;
+        self
+    }
+
+    // The grammar rule of a whole file
+    const fn file_rule(&self) -> Rule {
+        if self.lenient_keywords {
+            Rule::file_custom
+        } else {
+            Rule::file
+        }
+    }
+
+    /// Sets whether included files are only resolved against the include directory, ignoring the directory of the
+    /// including file. Without an include directory, files containing `.INCLUDE` then fail to parse.
+    #[must_use]
+    pub const fn include_dir_only(mut self, include_dir_only: bool) -> Self {
+        self.include_dir_only = include_dir_only;
+        self
+    }
+
+    /// Sets how include paths with wildcards are treated. By default, they are file names.
+    #[must_use]
+    pub const fn include_globs(mut self, include_globs: IncludeGlobs) -> Self {
+        self.include_globs = include_globs;
+        self
+    }
+
+    /// Sets whether the statements of a file that is included more than once are reused instead of reading and parsing
+    /// the file again. Files are recognized by `IncludeResolver::canonical_name`, and the reused statements keep the names
+    /// of the first inclusion. Parsing lazily does not reuse statements, as they are not kept.
+    #[must_use]
+    pub const fn reuse_includes(mut self, reuse_includes: bool) -> Self {
+        self.reuse_includes = reuse_includes;
+        self
+    }
+
+    // The resolver of included files, unless includes may only be resolved against a missing include directory
+    fn resolver(&self) -> Option<FileSystemResolver> {
+        let resolver = match self.include.as_deref() {
+            Some(include) => FileSystemResolver::new(include),
+            None if self.include_dir_only => return None,
+            None => FileSystemResolver::without_include_dir(),
+        }
+        .encoding(self.encoding)
+        .include_dir_only(self.include_dir_only)
+        .globs(self.include_globs);
+        Some(match self.max_file_bytes {
+            Some(max_file_bytes) => resolver.max_file_bytes(max_file_bytes),
+            None => resolver,
+        })
+    }
+}
+
+/// Parses the UniPen keyword statements from a file. Recursively parses included files, which are looked up next to the
+/// file including them, and in the include directory if provided.
+/// `.INCLUDE` statements are generated for each file parsed. The data in the original `.INCLUDE` statement are not outputted.
+/// Each file ends with an `EndOfInput` statement naming it, so the statements of included files are enclosed by the pair.
+/// The generated statements are marked as `synthetic`.
 ///
 /// # Arguments
 ///
@@ -25,46 +502,670 @@ struct StatementParser;
 ///
 /// # Errors
 ///
+/// See `parse_with_options`. Included files are resolved even without an include directory, so
+/// `UniPenError::MissingInclude` is not returned, and an included file that cannot be found is a `UniPenError::Io` error.
+///
+pub fn parse(path: &Path, include: Option<&Path>) -> Result<Vec<Statement>, UniPenError> {
+    let options = match include {
+        Some(include) => ParseOptions::new().include_dir(include),
+        None => ParseOptions::new(),
+    };
+    parse_with_options(path, &options)
+}
+
+/// Parses the UniPen keyword statements from a file with the given options. Recursively parses included files.
+/// `.INCLUDE` statements are generated for each file parsed, unless disabled with `ParseOptions::synthetic_includes`.
+/// The data in the original `.INCLUDE` statement are not outputted.
+/// Each file ends with an `EndOfInput` statement naming it, so the statements of included files are enclosed by the pair.
+/// The generated statements are marked as `synthetic`.
+///
+/// # Arguments
+///
+/// * `path` - The path to the file to parse.
+/// * `options` - The parse options.
+///
+/// # Returns
+///
+/// The UniPen keyword statements parsed from the file.
+///
+/// # Errors
+///
 /// * `UniPenError::Io` - If an I/O error occurs while reading the file.
-/// * `UniPenError::PestRule` - If the file does not conform to the grammar.
-/// * `UniPenError::MissingInclude` - If the file contains an include directive, but no include directory was provided.
+/// * `UniPenError::InvalidUtf8` - If a file is not valid UTF-8 when the encoding is UTF-8.
+/// * `UniPenError::MissingInclude` - If a file contains an include directive, but included files are only resolved
+///   against the include directory and none was provided. Otherwise, included files that cannot be found are
+///   `UniPenError::Io` errors.
+/// * `UniPenError::Validation` - If parsing is strict and a statement that may only appear once is repeated, or if keywords
+///   are lenient and a statement has a keyword that was not declared, or a known keyword with arguments that do not
+///   conform to the grammar.
+/// * See `parse_str` for the other errors.
+///
+pub fn parse_with_options(path: &Path, options: &ParseOptions) -> Result<Vec<Statement>, UniPenError> {
+    parse_with_stats(path, options).map(|(statements, _)| statements)
+}
+
+/// Counts of what was parsed from a file and its included files.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ParseStats {
+    /// The statements read from the files. `.INCLUDE` statements are counted by `include_count` instead, and generated
+    /// statements are not counted.
+    pub statement_count: usize,
+    /// The included files, counting a file each time it is included.
+    pub include_count: usize,
+    /// How deeply includes were nested. The parsed file itself is at depth 0.
+    pub max_include_depth: usize,
+    /// The coordinates of the `.PEN_DOWN` and `.PEN_UP` statements, as declared by the preceding `.COORD` statement.
+    pub coordinate_count: usize,
+}
+
+// Counts while parsing, remembering the number of values per coordinate across included files
+#[derive(Default)]
+struct StatsCollector {
+    stats: ParseStats,
+    coordinate_values: usize,
+}
+
+impl StatsCollector {
+    fn count(&mut self, statement: &Statement) {
+        self.stats.statement_count += 1;
+        match statement.keyword {
+            Keyword::Coordinate => self.coordinate_values = statement.arguments.len(),
+            Keyword::PenDown | Keyword::PenUp if self.coordinate_values > 0 => {
+                self.stats.coordinate_count += statement.arguments.len() / self.coordinate_values;
+            }
+            _ => {}
+        }
+    }
+
+    fn include(&mut self, depth: usize) {
+        self.stats.include_count += 1;
+        self.stats.max_include_depth = self.stats.max_include_depth.max(depth);
+    }
+
+    // Counts an included file whose statements are reused as if it was parsed again
+    fn reuse(&mut self, depth: usize, include: &ParsedInclude) {
+        self.include(depth);
+        self.stats.include_count += include.include_count;
+        self.stats.max_include_depth = self.stats.max_include_depth.max(depth + include.nested_depth);
+        for statement in include.statements.iter().filter(|statement| !statement.synthetic) {
+            self.count(statement);
+        }
+    }
+}
+
+// The statements of an included file, for `ParseOptions::reuse_includes`
+struct ParsedInclude {
+    statements: Rc<[Statement]>,
+    // The files it includes, directly or not, and how deeply they are nested below it
+    include_count: usize,
+    nested_depth: usize,
+}
+
+/// Parses the UniPen keyword statements from a file with the given options, counting what was parsed. See
+/// `parse_with_options`.
+///
+/// # Arguments
+///
+/// * `path` - The path to the file to parse.
+/// * `options` - The parse options.
+///
+/// # Returns
+///
+/// The UniPen keyword statements parsed from the file, and the counts of what was parsed.
+///
+/// # Errors
+///
+/// See `parse_with_options`.
+///
+pub fn parse_with_stats(path: &Path, options: &ParseOptions) -> Result<(Vec<Statement>, ParseStats), UniPenError> {
+    let (statements, stats) = parse_file(path, options)?;
+    if options.strict {
+        let mut singletons = SingletonTracker::default();
+        for statement in &statements {
+            singletons.check(statement)?;
+        }
+    }
+    Ok((statements, stats))
+}
+
+// Parses a file and its included files, without checking for repeated statements
+fn parse_file(path: &Path, options: &ParseOptions) -> Result<(Vec<Statement>, ParseStats), UniPenError> {
+    debug!("Parsing statements from {:?}", path);
+    let bytes = read_file(path, options.max_file_bytes)?;
+    debug!("Finished reading {} bytes from {:?}", bytes.len(), path);
+    let content = options.encoding.decode(bytes, path.to_string_lossy().as_ref())?;
+    let resolver = options.resolver();
+    let mut stats = StatsCollector::default();
+    let statements = parse_content(
+        path.to_string_lossy().as_ref(),
+        &content,
+        resolver.as_ref().map(|resolver| resolver as &dyn IncludeResolver),
+        options,
+        0,
+        &mut stats,
+        &mut HashMap::new(),
+    )?;
+    if options.lenient_keywords {
+        let mut declarations = KeywordDeclarations::default();
+        for statement in &statements {
+            declarations.check(statement)?;
+        }
+    }
+    Ok((statements, stats.stats))
+}
+
+/// Checks a file for as many errors as possible, instead of stopping at the first one. The file is parsed and built as
+/// by `parse_with_options` and `UniPenBuilder::build`, but repeated statements, statements that could not be added to
+/// the builder, conflicting units, implausible ages and missing mandatory statements are all reported. Errors of the
+/// grammar, reading files or including files still stop the check.
+///
+/// # Arguments
+///
+/// * `path` - The path to the file to check.
+/// * `options` - The parse options. Repeated statements are reported whether or not parsing is strict.
+///
+/// # Returns
+///
+/// The errors found, in the order of the checks: repeated statements, statements that could not be added, the problems
+/// found before building, and the error of building, unless it was already reported. Empty if the file is valid.
+///
+#[must_use]
+pub fn validate(path: &Path, options: &ParseOptions) -> Vec<UniPenError> {
+    let statements = match parse_file(path, options) {
+        Ok((statements, _)) => statements,
+        Err(err) => return vec![err],
+    };
+    let mut singletons = SingletonTracker::default();
+    let mut errors: Vec<UniPenError> = statements
+        .iter()
+        .filter_map(|statement| singletons.check(statement).err())
+        .collect();
+    let mut builder = UniPen::builder().strict(options.strict);
+    errors.extend(
+        statements
+            .iter()
+            .filter_map(|statement| builder.add_statement(statement).err()),
+    );
+    let problems = builder.problems();
+    let reported: Vec<String> = problems.iter().map(ToString::to_string).collect();
+    errors.extend(problems);
+    // Building stops at the first problem, which is already reported
+    if let Err(err) = builder.build() {
+        if !reported.contains(&err.to_string()) {
+            errors.push(err);
+        }
+    }
+    errors
+}
+
+// Tracks the statements that may only appear once in a file, including its included files
+#[derive(Default)]
+struct SingletonTracker(HashSet<Discriminant<Keyword>>);
+
+impl SingletonTracker {
+    fn check(&mut self, statement: &Statement) -> Result<(), UniPenError> {
+        let singleton = matches!(
+            statement.keyword,
+            Keyword::Version
+                | Keyword::DataSource
+                | Keyword::DataId
+                | Keyword::Coordinate
+                | Keyword::Hierarchy
+                | Keyword::RecSource
+                | Keyword::RecId
+        );
+        if singleton && !self.0.insert(discriminant(&statement.keyword)) {
+            let position = statement
+                .span
+                .map(|(line, column)| format!(" (line {line}, column {column})"))
+                .unwrap_or_default();
+            return Err(UniPenError::Validation(format!(
+                "Duplicate {} statement{position}",
+                statement.keyword
+            )));
+        }
+        Ok(())
+    }
+}
+
+// Tracks the keywords declared with .KEYWORD in a file, including its included files, as custom keywords must be declared
+// before they are used
+#[derive(Default)]
+struct KeywordDeclarations(HashSet<Rc<str>>);
+
+impl KeywordDeclarations {
+    fn check(&mut self, statement: &Statement) -> Result<(), UniPenError> {
+        match &statement.keyword {
+            Keyword::Keyword => {
+                if let Some(name) = statement.string_args().next() {
+                    self.0.insert(name.trim_start_matches('.').into());
+                }
+            }
+            Keyword::Custom(name) if !self.0.contains(name) => {
+                let position = statement
+                    .span
+                    .map(|(line, column)| format!(" (line {line}, column {column})"))
+                    .unwrap_or_default();
+                return Err(UniPenError::Validation(format!("Undeclared keyword .{name}{position}")));
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+/// Parses the UniPen keyword statements from a string. If the content contains an include directive, an include resolver
+/// must be provided. Recursively parses included files.
+/// `.INCLUDE` statements are generated for the content and for each included file, using `name` and the included paths
+/// as named by the include resolver. The data in the original `.INCLUDE` statement are not outputted.
+/// Each file ends with an `EndOfInput` statement naming it, so the statements of included files are enclosed by the pair.
+/// The generated statements are marked as `synthetic`.
+///
+/// # Arguments
+///
+/// * `name` - The logical name of the content, used in the generated `.INCLUDE` statement and in errors.
+/// * `content` - The UniPen content to parse.
+/// * `include_resolver` - The resolver of included files.
+///
+/// # Returns
+///
+/// The UniPen keyword statements parsed from the content.
+///
+/// # Errors
+///
+/// * `UniPenError::PestRule` - If the content does not conform to the grammar.
+/// * `UniPenError::MissingInclude` - If the content contains an include directive, but no include resolver was provided.
+///   `FileSystemResolver::without_include_dir` resolves includes without an include directory.
+/// * `UniPenError::IncludeDepth` - If includes are nested deeper than the maximum depth.
 /// * `UniPenError::ParseInt` - If a number could not be parsed as an integer.
 /// * `UniPenError::ParseFloat` - If a number could not be parsed as a float.
 /// * `UniPenError::Translation` - If a translation error occurs.
+/// * Any error returned by the include resolver.
 ///
-pub fn parse(path: &Path, include: Option<&Path>) -> Result<Vec<Statement>, UniPenError> {
-    debug!("Parsing statements from {:?}", path);
-    let content = fs::read_to_string(path).map_err(UniPenError::Io)?;
-    debug!("Finished reading {} bytes from {:?}", content.len(), path);
-    let statement_pairs = StatementParser::parse(Rule::file, content.as_str())
-        .map_err(|err| UniPenError::PestRule(Box::new(err.with_path(path.to_string_lossy().as_ref()))))?
+pub fn parse_str(
+    name: &str,
+    content: &str,
+    include_resolver: Option<&dyn IncludeResolver>,
+) -> Result<Vec<Statement>, UniPenError> {
+    parse_str_with_options(name, content, include_resolver, &ParseOptions::new())
+}
+
+/// Parses the UniPen keyword statements from a string with the given options. See `parse_str`. The include directory,
+/// encoding and file size limit of the options are not used, as included files are read by the include resolver.
+///
+/// # Arguments
+///
+/// * `name` - The logical name of the content, used in the generated `.INCLUDE` statement and in errors.
+/// * `content` - The UniPen content to parse.
+/// * `include_resolver` - The resolver of included files.
+/// * `options` - The parse options.
+///
+/// # Returns
+///
+/// The UniPen keyword statements parsed from the content.
+///
+/// # Errors
+///
+/// * `UniPenError::Validation` - As for `parse_with_options`.
+/// * See `parse_str` for the other errors.
+///
+pub fn parse_str_with_options(
+    name: &str,
+    content: &str,
+    include_resolver: Option<&dyn IncludeResolver>,
+    options: &ParseOptions,
+) -> Result<Vec<Statement>, UniPenError> {
+    let statements = parse_content(
+        name,
+        content,
+        include_resolver,
+        options,
+        0,
+        &mut StatsCollector::default(),
+        &mut HashMap::new(),
+    )?;
+    let mut singletons = SingletonTracker::default();
+    let mut declarations = KeywordDeclarations::default();
+    for statement in &statements {
+        if options.strict {
+            singletons.check(statement)?;
+        }
+        if options.lenient_keywords {
+            declarations.check(statement)?;
+        }
+    }
+    Ok(statements)
+}
+
+/// Parses UniPen content with the grammar, without converting the result to statements. `.INCLUDE` statements are not
+/// followed.
+///
+/// # Arguments
+///
+/// * `content` - The UniPen content to parse.
+///
+/// # Returns
+///
+/// The pairs of the top level statements, followed by an `EOI` pair. The rules of the pairs are the statement rules of the
+/// grammar, such as `Rule::s_pen_down`.
+///
+/// # Errors
+///
+/// * `UniPenError::PestRule` - If the content does not conform to the grammar.
+/// * `UniPenError::Translation` - If the grammar did not produce a file.
+///
+pub fn parse_pairs(content: &str) -> Result<Pairs<'_, Rule>, UniPenError> {
+    Ok(StatementParser::parse(Rule::file, content)
+        .map_err(|err| UniPenError::PestRule(Box::new(err)))?
+        .next()
+        .ok_or(translation_err!("Did not parser file"))?
+        .into_inner())
+}
+
+fn parse_content(
+    name: &str,
+    content: &str,
+    include_resolver: Option<&dyn IncludeResolver>,
+    options: &ParseOptions,
+    depth: usize,
+    stats: &mut StatsCollector,
+    // The included files by canonical name, when they are reused
+    parsed_includes: &mut HashMap<String, ParsedInclude>,
+) -> Result<Vec<Statement>, UniPenError> {
+    if let Some(max_depth) = options.max_depth {
+        if depth > max_depth {
+            return Err(UniPenError::IncludeDepth(max_depth));
+        }
+    }
+    let statement_pairs = StatementParser::parse(options.file_rule(), content)
+        .map_err(|err| UniPenError::PestRule(Box::new(err.with_path(name))))?
         .next()
         .ok_or(translation_err!("Did not parser file"))?
         .into_inner();
     let mut statements = Vec::new();
-    statements.push(Statement {
-        keyword: Keyword::Include,
-        arguments: vec![StatementArgument::String(path.to_string_lossy().into())],
-    });
+    if !options.omit_synthetic_includes {
+        statements.push(Statement {
+            keyword: Keyword::Include,
+            arguments: vec![StatementArgument::String(name.into())],
+            synthetic: true,
+            span: None,
+        });
+    }
     for statement_pair in statement_pairs {
         match statement_pair.as_rule() {
             Rule::s_include => {
-                let mut include_statements = parse(
-                    &include
-                        .ok_or(UniPenError::MissingInclude)?
-                        .join(Path::new(parse_include_path(statement_pair)?)),
-                    include,
-                )?;
-                statements.append(&mut include_statements);
+                let resolver = include_resolver.ok_or(UniPenError::MissingInclude)?;
+                for include_path in resolver.expand(parse_include_path(statement_pair)?, name)? {
+                    let canonical_name = options.reuse_includes.then(|| resolver.canonical_name(&include_path, name));
+                    let parsed = canonical_name
+                        .as_ref()
+                        .and_then(|canonical_name| parsed_includes.get(canonical_name));
+                    if let Some(parsed) = parsed {
+                        if let Some(max_depth) = options.max_depth {
+                            if depth + 1 + parsed.nested_depth > max_depth {
+                                return Err(UniPenError::IncludeDepth(max_depth));
+                            }
+                        }
+                        debug!("Reusing the statements of {include_path} included from {name}");
+                        stats.reuse(depth + 1, parsed);
+                        statements.extend(parsed.statements.iter().cloned());
+                        continue;
+                    }
+                    let include_content = resolver.resolve_from(&include_path, name)?;
+                    let include_name = resolver.resolved_name_from(&include_path, name);
+                    stats.include(depth + 1);
+                    // Counts the nested includes of the file alone
+                    let include_count = stats.stats.include_count;
+                    let max_include_depth = std::mem::take(&mut stats.stats.max_include_depth);
+                    let mut include_statements = parse_content(
+                        &include_name,
+                        &include_content,
+                        Some(resolver),
+                        options,
+                        depth + 1,
+                        stats,
+                        parsed_includes,
+                    )?;
+                    let nested_depth = stats.stats.max_include_depth.saturating_sub(depth + 1);
+                    stats.stats.max_include_depth = stats.stats.max_include_depth.max(max_include_depth);
+                    if let Some(canonical_name) = canonical_name {
+                        parsed_includes.insert(
+                            canonical_name,
+                            ParsedInclude {
+                                statements: include_statements.as_slice().into(),
+                                include_count: stats.stats.include_count - include_count,
+                                nested_depth,
+                            },
+                        );
+                    }
+                    statements.append(&mut include_statements);
+                }
+            }
+            Rule::EOI => {
+                if !options.omit_synthetic_includes {
+                    statements.push(end_of_input(name));
+                }
+            }
+            _ => {
+                let line_col = statement_pair.line_col();
+                let statement = Statement::from_pair(statement_pair, options.strict, line_col)?;
+                stats.count(&statement);
+                statements.push(statement);
             }
-            _ => statements.push(Statement::try_from(statement_pair)?),
         }
     }
-    debug!("Finished parsing {} statements from {:?}", statements.len(), path);
+    debug!("Finished parsing {} statements from {}", statements.len(), name);
     Ok(statements)
 }
 
-fn parse_include_path(include_expression: Pair<Rule>) -> Result<&str, UniPenError> {
+/// Parses the UniPen keyword statements from a file lazily. See `parse`.
+///
+/// # Arguments
+///
+/// * `path` - The path to the file to parse.
+/// * `include` - The path to the include directory.
+///
+/// # Returns
+///
+/// An iterator over the UniPen keyword statements, in the same order as `parse`. The iterator ends after the first error.
+/// See `parse_with_options` for the errors.
+///
+pub fn parse_iter(path: &Path, include: Option<&Path>) -> StatementIter {
+    let options = match include {
+        Some(include) => ParseOptions::new().include_dir(include),
+        None => ParseOptions::new(),
+    };
+    parse_iter_with_options(path, &options)
+}
+
+/// Parses the UniPen keyword statements from a file lazily with the given options. See `parse_with_options`.
+///
+/// # Arguments
+///
+/// * `path` - The path to the file to parse.
+/// * `options` - The parse options.
+///
+/// # Returns
+///
+/// An iterator over the UniPen keyword statements, in the same order as `parse_with_options`. The iterator ends after the
+/// first error. See `parse_with_options` for the errors.
+///
+pub fn parse_iter_with_options(path: &Path, options: &ParseOptions) -> StatementIter {
+    let mut iter = StatementIter {
+        stack: Vec::new(),
+        resolver: options.resolver(),
+        options: options.clone(),
+        next: None,
+        singletons: SingletonTracker::default(),
+        declarations: KeywordDeclarations::default(),
+        bytes_read: 0,
+    };
+    debug!("Parsing statements lazily from {:?}", path);
+    let content =
+        read_file(path, options.max_file_bytes).and_then(|bytes| options.encoding.decode(bytes, path.to_string_lossy().as_ref()));
+    iter.next = content
+        .and_then(|content| iter.open(path.to_string_lossy().as_ref(), content))
+        .transpose();
+    iter
+}
+
+// A file being parsed lazily. Pairs borrow the content, so only the spans of the statements are kept, and each statement is
+// parsed again from its span when it is reached.
+struct StatementFrame {
+    name: Rc<str>,
+    content: String,
+    statements: std::vec::IntoIter<(Rule, Range<usize>)>,
+    // The files of an expanded .INCLUDE that are still to be included
+    includes: VecDeque<String>,
+}
+
+/// An iterator over the UniPen keyword statements of a file and its included files. Created by `parse_iter`.
+pub struct StatementIter {
+    // The files being parsed, the innermost include last
+    stack: Vec<StatementFrame>,
+    resolver: Option<FileSystemResolver>,
+    options: ParseOptions,
+    // The statement or error to yield before continuing with the stack
+    next: Option<Result<Statement, UniPenError>>,
+    singletons: SingletonTracker,
+    declarations: KeywordDeclarations,
+    // The total size of the files opened, for `ParseOptions::max_file_bytes`
+    bytes_read: u64,
+}
+
+impl StatementIter {
+    // Starts parsing a file, returning its synthetic .INCLUDE statement unless they are omitted
+    fn open(&mut self, name: &str, content: String) -> Result<Option<Statement>, UniPenError> {
+        if let Some(max_depth) = self.options.max_depth {
+            if self.stack.len() > max_depth {
+                return Err(UniPenError::IncludeDepth(max_depth));
+            }
+        }
+        self.bytes_read += content.len() as u64;
+        if let Some(limit) = self.options.max_file_bytes {
+            if self.bytes_read > limit {
+                return Err(UniPenError::FileTooLarge {
+                    path: name.into(),
+                    size: self.bytes_read,
+                    limit,
+                });
+            }
+        }
+        let statements = StatementParser::parse(self.options.file_rule(), &content)
+            .map_err(|err| UniPenError::PestRule(Box::new(err.with_path(name))))?
+            .next()
+            .ok_or(translation_err!("Did not parser file"))?
+            .into_inner()
+            .map(|pair| (pair.as_rule(), pair.as_span().start()..pair.as_span().end()))
+            .collect::<Vec<_>>();
+        self.stack.push(StatementFrame {
+            name: name.into(),
+            content,
+            statements: statements.into_iter(),
+            includes: VecDeque::new(),
+        });
+        Ok((!self.options.omit_synthetic_includes).then(|| Statement {
+            keyword: Keyword::Include,
+            arguments: vec![StatementArgument::String(name.into())],
+            synthetic: true,
+            span: None,
+        }))
+    }
+
+    fn next_statement(&mut self) -> Option<Result<Statement, UniPenError>> {
+        loop {
+            let frame = self.stack.last_mut()?;
+            if let Some(include_path) = frame.includes.pop_front() {
+                let including = frame.name.clone();
+                let include = self
+                    .resolver
+                    .as_ref()
+                    .ok_or(UniPenError::MissingInclude)
+                    .and_then(|resolver| {
+                        Ok((
+                            resolver.resolved_name_from(&include_path, &including),
+                            resolver.resolve_from(&include_path, &including)?,
+                        ))
+                    });
+                match include.and_then(|(include_path, content)| self.open(&include_path, content)) {
+                    Ok(None) => continue,
+                    opened => return opened.transpose(),
+                }
+            }
+            let Some((rule, span)) = frame.statements.next() else {
+                debug!("Finished parsing statements lazily from {}", frame.name);
+                self.stack.pop();
+                continue;
+            };
+            // Pairs parsed from the span have positions relative to it
+            let Some(line_col) = pest::Position::new(&frame.content, span.start).map(|position| position.line_col()) else {
+                return Some(Err(translation_err!("Statement span is outside of the file")));
+            };
+            if rule == Rule::EOI {
+                if self.options.omit_synthetic_includes {
+                    continue;
+                }
+                return Some(Ok(end_of_input(&frame.name)));
+            }
+            // The span ends at the start of the next statement or the end of input, so the statement parses on its own
+            let pair = match StatementParser::parse(rule, &frame.content[span]) {
+                Ok(mut pairs) => pairs.next()?,
+                Err(err) => return Some(Err(UniPenError::PestRule(Box::new(err.with_path(&frame.name))))),
+            };
+            if rule != Rule::s_include {
+                return Some(Statement::from_pair(pair, self.options.strict, line_col));
+            }
+            // The files are included one at a time, each after the previous one ends
+            let include_paths = parse_include_path(pair).and_then(|include_path| {
+                let resolver = self.resolver.as_ref().ok_or(UniPenError::MissingInclude)?;
+                resolver.expand(include_path, &frame.name)
+            });
+            match include_paths {
+                Ok(include_paths) => frame.includes.extend(include_paths),
+                Err(err) => return Some(Err(err)),
+            }
+        }
+    }
+}
+
+impl Iterator for StatementIter {
+    type Item = Result<Statement, UniPenError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut next = self.next.take().or_else(|| self.next_statement())?;
+        if self.options.strict {
+            if let Ok(statement) = &next {
+                if let Err(err) = self.singletons.check(statement) {
+                    next = Err(err);
+                }
+            }
+        }
+        if self.options.lenient_keywords {
+            if let Ok(statement) = &next {
+                if let Err(err) = self.declarations.check(statement) {
+                    next = Err(err);
+                }
+            }
+        }
+        if next.is_err() {
+            self.stack.clear();
+        }
+        Some(next)
+    }
+}
+
+// The synthetic statement ending a parsed file
+fn end_of_input(name: &str) -> Statement {
+    Statement {
+        keyword: Keyword::EndOfInput,
+        arguments: vec![StatementArgument::String(name.into())],
+        synthetic: true,
+        span: None,
+    }
+}
+
+fn parse_include_path(include_expression: Pair<'_, Rule>) -> Result<&str, UniPenError> {
     match include_expression.as_rule() {
         Rule::s_include => Ok(include_expression
             .into_inner()
@@ -75,30 +1176,190 @@ fn parse_include_path(include_expression: Pair<Rule>) -> Result<&str, UniPenErro
     }
 }
 
+/// A UniPen keyword statement. Cloning is cheap, as text arguments are shared through `Rc`.
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
-#[derive(Debug)]
+#[derive(Clone)]
 pub struct Statement {
     pub keyword: Keyword,
     pub arguments: Vec<StatementArgument>,
+    /// Whether the statement was generated by the parser instead of read from the source.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub synthetic: bool,
+    /// The line and column where the statement starts in its source file, both starting at 1. `None` for synthetic statements.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub span: Option<(usize, usize)>,
+}
+
+/// Lists the fields of the statement. The arguments of statements with more than eight arguments, such as pen data, are
+/// elided like the lists of `ComponentSet`. The alternate form `{:#?}` shows every argument.
+impl fmt::Debug for Statement {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Statement")
+            .field("keyword", &self.keyword)
+            .field("arguments", &ElidedList(&self.arguments))
+            .field("synthetic", &self.synthetic)
+            .field("span", &self.span)
+            .finish()
+    }
+}
+
+/// Writes the statement as a UniPen source line, without the trailing newline.
+///
+/// Synthetic `.INCLUDE` statements are written as `.COMMENT` statements, so that the written source does not include the
+/// file a second time. The end of input is written as an empty line.
+impl Display for Statement {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.keyword {
+            Keyword::EndOfInput => return Ok(()),
+            Keyword::Include if self.synthetic => f.write_str(".COMMENT .INCLUDE")?,
+            _ => write!(f, "{}", self.keyword)?,
+        }
+        for argument in &self.arguments {
+            match argument {
+                // Free text statements without a separator in the grammar keep the leading whitespace of their text
+                StatementArgument::FreeText(text) if text.starts_with(char::is_whitespace) => {}
+                _ => f.write_str(" ")?,
+            }
+            write!(f, "{argument}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Writes statements as UniPen source, one statement per line. See the `Display` implementation of `Statement`; end of input
+/// statements are skipped, as they have no source text.
+///
+/// # Arguments
+///
+/// * `statements` - The statements to write.
+/// * `writer` - The writer to write to.
+///
+/// # Errors
+///
+/// * `UniPenError::Io` - If writing fails.
+///
+pub fn write_unipen<W: Write>(statements: &[Statement], mut writer: W) -> Result<(), UniPenError> {
+    for statement in statements {
+        if !matches!(statement.keyword, Keyword::EndOfInput) {
+            writeln!(writer, "{statement}")?;
+        }
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Writes statements as a JSON array.
+///
+/// # Arguments
+///
+/// * `statements` - The statements to write.
+/// * `writer` - The writer to write to.
+/// * `pretty` - Whether the JSON is indented over multiple lines.
+///
+/// # Errors
+///
+/// * `UniPenError::Serialize` - If serializing or writing fails.
+/// * `UniPenError::Io` - If flushing the writer fails.
+///
+#[cfg(feature = "serde")]
+pub fn write_json<W: Write>(statements: &[Statement], mut writer: W, pretty: bool) -> Result<(), UniPenError> {
+    if pretty {
+        serde_json::to_writer_pretty(&mut writer, statements)?;
+    } else {
+        serde_json::to_writer(&mut writer, statements)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+impl Statement {
+    /// Returns the arguments of the statement as numbers.
+    ///
+    /// # Returns
+    ///
+    /// An iterator over the arguments, converted to floats. Arguments that are not numbers are
+    /// `UniPenError::Translation` errors naming the argument, as the grammar only produces numbers where numbers are
+    /// expected.
+    pub fn number_args(&self) -> impl Iterator<Item = Result<f64, UniPenError>> + '_ {
+        self.arguments.iter().map(|argument| match argument {
+            StatementArgument::Number(number) => Ok(f64::from(number)),
+            _ => Err(translation_err!(format!(
+                "Statement of {} has invalid argument: expected a number, got {argument}",
+                self.keyword
+            ))),
+        })
+    }
+
+    /// Returns the text of the string, free text and label arguments of the statement, skipping the other arguments.
+    /// Labels are returned unescaped, without their quotes.
+    pub fn string_args(&self) -> impl Iterator<Item = &str> {
+        self.arguments.iter().filter_map(|argument| match argument {
+            StatementArgument::String(text) | StatementArgument::FreeText(text) | StatementArgument::Label(text) => {
+                Some(text.as_ref())
+            }
+            _ => None,
+        })
+    }
+
+    // Arguments that could not be converted are dropped with a warning, unless strict. The position of the pair may be
+    // relative to a statement that was parsed on its own, so the position in the file is passed separately.
+    fn from_pair(value: Pair<'_, Rule>, strict: bool, (line, column): (usize, usize)) -> Result<Self, UniPenError> {
+        let rule = value.as_rule();
+        let mut pairs = value.into_inner();
+        let keyword = if rule == Rule::s_custom {
+            let name = pairs
+                .next()
+                .ok_or(translation_err!("No keyword name in custom statement"))?
+                .as_str();
+            // A known keyword only ends up here when its arguments do not match its own rule
+            if Keyword::from_str(&format!(".{name}")).is_ok() {
+                return Err(UniPenError::Validation(format!(
+                    "Invalid .{name} statement (line {line}, column {column})"
+                )));
+            }
+            Keyword::Custom(name.into())
+        } else {
+            Keyword::try_from(rule)?
+        };
+        let arguments = pairs.map(|pair| {
+            let text = pair.as_str();
+            StatementArgument::try_from(pair).map_err(|err| (err, text))
+        });
+        let arguments = if strict {
+            arguments
+                .map(|argument| argument.map_err(|(err, _)| err))
+                .collect::<Result<Vec<_>, _>>()?
+        } else {
+            arguments
+                .filter_map(|argument| {
+                    argument
+                        .map_err(|(err, text)| {
+                            warn!("Dropped argument {text:?} of {keyword} statement at line {line}, column {column}: {err}");
+                        })
+                        .ok()
+                })
+                .collect()
+        };
+        Ok(Self {
+            keyword,
+            arguments,
+            synthetic: false,
+            span: Some((line, column)),
+        })
+    }
 }
 
 impl TryFrom<Pair<'_, Rule>> for Statement {
     type Error = UniPenError;
 
     fn try_from(value: Pair<Rule>) -> Result<Self, UniPenError> {
-        Ok(Self {
-            keyword: Keyword::try_from(value.as_rule())?,
-            arguments: value
-                .into_inner()
-                .map(StatementArgument::try_from)
-                .filter_map(std::result::Result::ok)
-                .collect(),
-        })
+        let line_col = value.line_col();
+        Self::from_pair(value, false, line_col)
     }
 }
 
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Keyword {
     Keyword,
     Reserve,
@@ -161,6 +1422,10 @@ pub enum Keyword {
     RecLabels,
     RecScores,
     EndOfInput,
+    /// A keyword that the grammar does not know, named without its leading dot. Only parsed when keywords are lenient, see
+    /// `ParseOptions::lenient_keywords`. Written with its leading dot, but not parsed back by `FromStr`, which only knows the
+    /// keywords of the grammar. `UniPenBuilder` skips statements with custom keywords.
+    Custom(Rc<str>),
 }
 
 impl TryFrom<Rule> for Keyword {
@@ -236,8 +1501,154 @@ impl TryFrom<Rule> for Keyword {
     }
 }
 
+// The keyword as written in the source
+fn keyword_name(keyword: &Keyword) -> Cow<'static, str> {
+    let name = match keyword {
+        Keyword::Keyword => ".KEYWORD",
+        Keyword::Reserve => ".RESERVE",
+        Keyword::Comment => ".COMMENT",
+        Keyword::Include => ".INCLUDE",
+        Keyword::Version => ".VERSION",
+        Keyword::DataSource => ".DATA_SOURCE",
+        Keyword::DataId => ".DATA_ID",
+        Keyword::Coordinate => ".COORD",
+        Keyword::Hierarchy => ".HIERARCHY",
+        Keyword::DataContact => ".DATA_CONTACT",
+        Keyword::DataInfo => ".DATA_INFO",
+        Keyword::Setup => ".SETUP",
+        Keyword::Pad => ".PAD",
+        Keyword::Alphabet => ".ALPHABET",
+        Keyword::AlphabetFreq => ".ALPHABET_FREQ",
+        Keyword::LexiconSource => ".LEXICON_SOURCE",
+        Keyword::LexiconId => ".LEXICON_ID",
+        Keyword::LexiconContact => ".LEXICON_CONTACT",
+        Keyword::LexiconInfo => ".LEXICON_INFO",
+        Keyword::Lexicon => ".LEXICON",
+        Keyword::LexiconFreq => ".LEXICON_FREQ",
+        Keyword::XDimension => ".X_DIM",
+        Keyword::YDimension => ".Y_DIM",
+        Keyword::HLine => ".H_LINE",
+        Keyword::VLine => ".V_LINE",
+        Keyword::XPointsPerInch => ".X_POINTS_PER_INCH",
+        Keyword::YPointsPerInch => ".Y_POINTS_PER_INCH",
+        Keyword::ZPointsPerInch => ".Z_POINTS_PER_INCH",
+        Keyword::XPointsPerMm => ".X_POINTS_PER_MM",
+        Keyword::YPointsPerMm => ".Y_POINTS_PER_MM",
+        Keyword::ZPointsPerMm => ".Z_POINTS_PER_MM",
+        Keyword::PointsPerGram => ".POINTS_PER_GRAM",
+        Keyword::PointsPerSecond => ".POINTS_PER_SECOND",
+        Keyword::PenDown => ".PEN_DOWN",
+        Keyword::PenUp => ".PEN_UP",
+        Keyword::Dt => ".DT",
+        Keyword::Date => ".DATE",
+        Keyword::Style => ".STYLE",
+        Keyword::WriterId => ".WRITER_ID",
+        Keyword::Country => ".COUNTRY",
+        Keyword::Hand => ".HAND",
+        Keyword::Age => ".AGE",
+        Keyword::Sex => ".SEX",
+        Keyword::Skill => ".SKILL",
+        Keyword::WriterInfo => ".WRITER_INFO",
+        Keyword::Segment => ".SEGMENT",
+        Keyword::StartSet => ".START_SET",
+        Keyword::StartBox => ".START_BOX",
+        Keyword::RecSource => ".REC_SOURCE",
+        Keyword::RecId => ".REC_ID",
+        Keyword::RecContact => ".REC_CONTACT",
+        Keyword::RecInfo => ".REC_INFO",
+        Keyword::Implement => ".IMPLEMENT",
+        Keyword::TrainingSet => ".TRAINING_SET",
+        Keyword::TestSet => ".TEST_SET",
+        Keyword::AdaptSet => ".ADAPT_SET",
+        Keyword::LexiconSet => ".LEXICON_SET",
+        Keyword::RecTime => ".REC_TIME",
+        Keyword::RecLabels => ".REC_LABELS",
+        Keyword::RecScores => ".REC_SCORES",
+        Keyword::EndOfInput => "",
+        Keyword::Custom(name) => return format!(".{name}").into(),
+    };
+    name.into()
+}
+
+impl Display for Keyword {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(&keyword_name(self))
+    }
+}
+
+impl FromStr for Keyword {
+    type Err = UniPenError;
+
+    fn from_str(s: &str) -> Result<Self, UniPenError> {
+        match s {
+            ".KEYWORD" => Ok(Self::Keyword),
+            ".RESERVE" => Ok(Self::Reserve),
+            ".COMMENT" => Ok(Self::Comment),
+            ".INCLUDE" => Ok(Self::Include),
+            ".VERSION" => Ok(Self::Version),
+            ".DATA_SOURCE" => Ok(Self::DataSource),
+            ".DATA_ID" => Ok(Self::DataId),
+            ".COORD" => Ok(Self::Coordinate),
+            ".HIERARCHY" => Ok(Self::Hierarchy),
+            ".DATA_CONTACT" => Ok(Self::DataContact),
+            ".DATA_INFO" => Ok(Self::DataInfo),
+            ".SETUP" => Ok(Self::Setup),
+            ".PAD" => Ok(Self::Pad),
+            ".ALPHABET" => Ok(Self::Alphabet),
+            ".ALPHABET_FREQ" => Ok(Self::AlphabetFreq),
+            ".LEXICON_SOURCE" => Ok(Self::LexiconSource),
+            ".LEXICON_ID" => Ok(Self::LexiconId),
+            ".LEXICON_CONTACT" => Ok(Self::LexiconContact),
+            ".LEXICON_INFO" => Ok(Self::LexiconInfo),
+            ".LEXICON" => Ok(Self::Lexicon),
+            ".LEXICON_FREQ" => Ok(Self::LexiconFreq),
+            ".X_DIM" => Ok(Self::XDimension),
+            ".Y_DIM" => Ok(Self::YDimension),
+            ".H_LINE" => Ok(Self::HLine),
+            ".V_LINE" => Ok(Self::VLine),
+            ".X_POINTS_PER_INCH" => Ok(Self::XPointsPerInch),
+            ".Y_POINTS_PER_INCH" => Ok(Self::YPointsPerInch),
+            ".Z_POINTS_PER_INCH" => Ok(Self::ZPointsPerInch),
+            ".X_POINTS_PER_MM" => Ok(Self::XPointsPerMm),
+            ".Y_POINTS_PER_MM" => Ok(Self::YPointsPerMm),
+            ".Z_POINTS_PER_MM" => Ok(Self::ZPointsPerMm),
+            ".POINTS_PER_GRAM" => Ok(Self::PointsPerGram),
+            ".POINTS_PER_SECOND" => Ok(Self::PointsPerSecond),
+            ".PEN_DOWN" => Ok(Self::PenDown),
+            ".PEN_UP" => Ok(Self::PenUp),
+            ".DT" => Ok(Self::Dt),
+            ".DATE" => Ok(Self::Date),
+            ".STYLE" => Ok(Self::Style),
+            ".WRITER_ID" => Ok(Self::WriterId),
+            ".COUNTRY" => Ok(Self::Country),
+            ".HAND" => Ok(Self::Hand),
+            ".AGE" => Ok(Self::Age),
+            ".SEX" => Ok(Self::Sex),
+            ".SKILL" => Ok(Self::Skill),
+            ".WRITER_INFO" => Ok(Self::WriterInfo),
+            ".SEGMENT" => Ok(Self::Segment),
+            ".START_SET" => Ok(Self::StartSet),
+            ".START_BOX" => Ok(Self::StartBox),
+            ".REC_SOURCE" => Ok(Self::RecSource),
+            ".REC_ID" => Ok(Self::RecId),
+            ".REC_CONTACT" => Ok(Self::RecContact),
+            ".REC_INFO" => Ok(Self::RecInfo),
+            ".IMPLEMENT" => Ok(Self::Implement),
+            ".TRAINING_SET" => Ok(Self::TrainingSet),
+            ".TEST_SET" => Ok(Self::TestSet),
+            ".ADAPT_SET" => Ok(Self::AdaptSet),
+            ".LEXICON_SET" => Ok(Self::LexiconSet),
+            ".REC_TIME" => Ok(Self::RecTime),
+            ".REC_LABELS" => Ok(Self::RecLabels),
+            ".REC_SCORES" => Ok(Self::RecScores),
+            "" => Ok(Self::EndOfInput),
+            _ => Err(translation_err!(format!("Unknown keyword {s:?}"))),
+        }
+    }
+}
+
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum StatementArgument {
     Number(Number),
     String(Rc<str>),
@@ -258,31 +1669,74 @@ impl TryFrom<Pair<'_, Rule>> for StatementArgument {
                 .ok_or(translation_err!("Number rule did not contain a number"))
                 .and_then(|pair| Number::try_from(pair).map(StatementArgument::Number)),
             Rule::t_string => Ok(Self::String(value.as_str().into())),
-            Rule::t_free_text => Ok(Self::FreeText(value.as_str().into())),
-            Rule::t_label => {
-                let value = value.as_str();
-                let whitespace_regex = regex!(r"\s|\t|\r|\n");
-                let escape_regex = regex!(r"\\(.)");
-                let normalized = whitespace_regex.replace_all(value, " ");
-                let escaped = escape_regex.replace_all(&normalized, |captures: &regex::Captures| -> String {
-                    match captures.get(1).map(|m| m.as_str()) {
-                        Some("n") => "\n".into(),
-                        Some("t") => "\t".into(),
-                        Some(c) => c.into(),
-                        None => String::new(),
-                    }
-                });
-                // Based on the grammar, the label should always be enclosed in quotes, so we can safely remove the first and last characters.
-                Ok(Self::Label((&escaped[1..escaped.len() - 1]).into()))
-            }
+            Rule::t_free_text => Ok(Self::FreeText(normalize_newlines(value.as_str()))),
+            Rule::t_date => Ok(Self::String(value.as_str().into())),
+            Rule::t_label => Ok(Self::Label(unescape_label(value.as_str()))),
             Rule::r_list => Ok(Self::List(ComponentList::try_from(value)?)),
             rule => Ok(Self::Reserved(Reserved::try_from(rule)?)),
         }
     }
 }
 
+/// Converts a quoted label as written in UniPen source to its text. This is the inverse of `writer::escape_label`.
+///
+/// The quotes are removed, each whitespace character becomes a space, and the escapes `\n` and `\t` become a newline and a
+/// tab. Any other escaped character stands for itself, such as `\"` and `\\`.
+///
+/// # Arguments
+///
+/// * `raw` - The label, including its enclosing quotes.
+///
+/// # Returns
+///
+/// The text of the label.
+#[must_use]
+pub fn unescape_label(raw: &str) -> Rc<str> {
+    let inner = raw.strip_prefix('"').and_then(|raw| raw.strip_suffix('"')).unwrap_or(raw);
+    // Most labels are plain words, which are taken as they are without running the regexes
+    if !inner.contains(|c: char| c == '\\' || (c.is_whitespace() && c != ' ')) {
+        return inner.into();
+    }
+    // A line break of a multi-line label is a single space, whichever line ending the file uses
+    let whitespace_regex = regex!(r"\r\n|\s|\t|\r|\n");
+    let escape_regex = regex!(r"\\(.)");
+    let normalized = whitespace_regex.replace_all(inner, " ");
+    let escaped = escape_regex.replace_all(&normalized, |captures: &regex::Captures| -> String {
+        match captures.get(1).map(|m| m.as_str()) {
+            Some("n") => "\n".into(),
+            Some("t") => "\t".into(),
+            Some(c) => c.into(),
+            None => String::new(),
+        }
+    });
+    escaped.as_ref().into()
+}
+
+// Line breaks within free text are kept as `\n`, whichever line ending the file uses
+fn normalize_newlines(text: &str) -> Rc<str> {
+    if text.contains('\r') {
+        text.replace("\r\n", "\n").replace('\r', "\n").into()
+    } else {
+        text.into()
+    }
+}
+
+/// Writes the argument as UniPen source text. Labels are quoted and escaped, and free text is escaped.
+impl Display for StatementArgument {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Number(number) => write!(f, "{number}"),
+            Self::String(string) => f.write_str(string),
+            Self::FreeText(text) => f.write_str(&escape_free_text(text)),
+            Self::Reserved(reserved) => write!(f, "{reserved}"),
+            Self::Label(label) => f.write_str(&escape_label(label)),
+            Self::List(list) => write!(f, "{list}"),
+        }
+    }
+}
+
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub enum Reserved {
     Type,
     X,
@@ -342,8 +1796,40 @@ impl TryFrom<Rule> for Reserved {
     }
 }
 
+/// Writes the reserved word as it appears in the source. The parser does not keep the letter of a type, so types are
+/// written as `[.]`.
+impl Display for Reserved {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Type => "[.]",
+            Self::X => "X",
+            Self::Y => "Y",
+            Self::Time => "T",
+            Self::Pressure => "P",
+            Self::Z => "Z",
+            Self::Button => "BUTTON",
+            Self::Rho => "RHO",
+            Self::Theta => "THETA",
+            Self::Phi => "PHI",
+            Self::LeftHand => "L",
+            Self::RightHand => "R",
+            Self::Male => "M",
+            Self::Female => "F",
+            Self::Bad => "BAD",
+            Self::Ok => "OK",
+            Self::Good => "GOOD",
+            Self::Unknown => "?",
+            Self::Printed => "PRINTED",
+            Self::Cursive => "CURSIVE",
+            Self::Mixed => "MIXED",
+            Self::Accept => "ACCEPT",
+            Self::Reject => "REJECT",
+        })
+    }
+}
+
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub enum Number {
     Integer(i32),
     Decimal(f64),
@@ -368,6 +1854,59 @@ impl From<&Number> for f64 {
     }
 }
 
+impl From<Number> for i32 {
+    fn from(value: Number) -> Self {
+        Self::from(&value)
+    }
+}
+
+impl From<Number> for f64 {
+    fn from(value: Number) -> Self {
+        Self::from(&value)
+    }
+}
+
+/// Indices and counts must be non-negative whole numbers; decimals are accepted when they have no
+/// fractional part.
+impl TryFrom<&Number> for usize {
+    type Error = UniPenError;
+
+    fn try_from(value: &Number) -> Result<Self, UniPenError> {
+        match value {
+            Number::Integer(n) => {
+                Self::try_from(*n).map_err(|_| UniPenError::Validation(format!("Expected a non-negative index, got {n}")))
+            }
+            Number::Decimal(n) if !n.is_finite() || *n < 0.0 => {
+                Err(UniPenError::Validation(format!("Expected a non-negative index, got {n}")))
+            }
+            Number::Decimal(n) if n.fract() != 0.0 => {
+                Err(UniPenError::Validation(format!("Expected a whole number index, got {n}")))
+            }
+            #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+            Number::Decimal(n) => Ok(*n as Self),
+        }
+    }
+}
+
+impl TryFrom<Number> for usize {
+    type Error = UniPenError;
+
+    fn try_from(value: Number) -> Result<Self, UniPenError> {
+        Self::try_from(&value)
+    }
+}
+
+/// Writes decimals with a decimal point, so that they are parsed as decimals again.
+impl Display for Number {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Integer(n) => write!(f, "{n}"),
+            Self::Decimal(n) if n.is_finite() && n.fract() == 0.0 => write!(f, "{n:.1}"),
+            Self::Decimal(n) => write!(f, "{n}"),
+        }
+    }
+}
+
 impl TryFrom<Pair<'_, Rule>> for Number {
     type Error = UniPenError;
 
@@ -389,7 +1928,7 @@ impl TryFrom<Pair<'_, Rule>> for Number {
 }
 
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ComponentList(pub Vec<ComponentItem>);
 
 impl TryFrom<Pair<'_, Rule>> for ComponentList {
@@ -405,8 +1944,24 @@ impl TryFrom<Pair<'_, Rule>> for ComponentList {
     }
 }
 
+/// Writes the list in the component list syntax of the grammar, e.g. `0,2:1-3:4`.
+impl Display for ComponentList {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for (i, item) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str(",")?;
+            }
+            match item {
+                ComponentItem::Single(point) => write!(f, "{point}")?,
+                ComponentItem::Range(range) => write!(f, "{}-{}", range.start, range.end)?,
+            }
+        }
+        Ok(())
+    }
+}
+
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum ComponentItem {
     Single(ComponentPoint),
     Range(ComponentRange),
@@ -427,7 +1982,7 @@ impl TryFrom<Pair<'_, Rule>> for ComponentItem {
 }
 
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ComponentRange {
     pub start: ComponentPoint,
     pub end: ComponentPoint,
@@ -454,7 +2009,7 @@ impl TryFrom<Pair<'_, Rule>> for ComponentRange {
 }
 
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ComponentPoint {
     pub component: usize,
     pub point: Point,
@@ -464,22 +2019,38 @@ impl TryFrom<Pair<'_, Rule>> for ComponentPoint {
     type Error = UniPenError;
 
     fn try_from(value: Pair<'_, Rule>) -> Result<Self, UniPenError> {
+        let to_index = |pair: Pair<'_, Rule>| -> Result<usize, UniPenError> {
+            let number = pair
+                .into_inner()
+                .flatten()
+                .find(|pair| matches!(pair.as_rule(), Rule::integer | Rule::decimal))
+                .ok_or(translation_err!("Component point rule did not contain a number"))?;
+            usize::try_from(Number::try_from(number)?)
+        };
         let mut inner = value.into_inner();
         let component = inner
             .next()
             .ok_or(translation_err!("Component point rule did not contain a component"))
-            .and_then(|pair| pair.as_str().parse::<usize>().map_err(UniPenError::ParseInt))?;
+            .and_then(to_index)?;
         let point = match inner.next() {
-            Some(n) => Point::Index(n.as_str().parse::<usize>().map_err(UniPenError::ParseInt)?),
+            Some(n) => Point::Index(to_index(n)?),
             None => Point::All,
         };
-        #[allow(clippy::cast_sign_loss)]
         Ok(Self { component, point })
     }
 }
 
+impl Display for ComponentPoint {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.point {
+            Point::All => write!(f, "{}", self.component),
+            Point::Index(point) => write!(f, "{}:{point}", self.component),
+        }
+    }
+}
+
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Point {
     All,
     Index(usize),
@@ -0,0 +1,64 @@
+use std::rc::Rc;
+
+use crate::model::{BoundingBox, CoordinateType, UniPen};
+
+/// An overview of a document, aggregated across its component sets. Created by `UniPen::summary`.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, PartialEq)]
+pub struct DocumentSummary {
+    pub set_count: usize,
+    /// The number of coordinates of all sets, of pen down and pen up components alike.
+    pub total_coordinates: usize,
+    /// The number of `.PEN_DOWN` components of all sets, as counted by `ComponentSet::stroke_count`.
+    pub total_strokes: usize,
+    /// Whether any coordinate has a pressure.
+    pub has_pressure: bool,
+    pub coordinate_order: Vec<CoordinateType>,
+    /// The IDs of the writers of the sets, in the order of the first set of each writer. Sets recorded before any
+    /// `.WRITER_ID` have no writer and are not counted.
+    pub writers: Vec<Rc<str>>,
+    /// The box covering the coordinates of all sets, or `None` if there are no coordinates. Its coordinate ranges are
+    /// empty, as they would refer to the coordinates of different sets.
+    pub bounding_box: Option<BoundingBox>,
+}
+
+impl UniPen {
+    /// Summarizes the document for quick inspection.
+    #[must_use]
+    pub fn summary(&self) -> DocumentSummary {
+        let mut writers: Vec<Rc<str>> = Vec::new();
+        for writer_id in self.sets.iter().filter_map(|set| set.writer_id.as_ref()) {
+            if !writers.contains(writer_id) {
+                writers.push(writer_id.clone());
+            }
+        }
+        let bounding_box = self
+            .sets
+            .iter()
+            .filter_map(|set| set.extent())
+            .reduce(|total, extent| BoundingBox {
+                x_min: total.x_min.min(extent.x_min),
+                y_min: total.y_min.min(extent.y_min),
+                x_max: total.x_max.max(extent.x_max),
+                y_max: total.y_max.max(extent.y_max),
+                coordinates: total.coordinates,
+            })
+            .map(|total| BoundingBox {
+                coordinates: Rc::new([]),
+                ..total
+            });
+        DocumentSummary {
+            set_count: self.sets.len(),
+            total_coordinates: self.sets.iter().map(|set| set.coordinates.len()).sum(),
+            total_strokes: self.sets.iter().map(|set| set.stroke_count()).sum(),
+            has_pressure: self
+                .sets
+                .iter()
+                .flat_map(|set| set.coordinates.iter())
+                .any(|coordinate| coordinate.pressure.is_some()),
+            coordinate_order: self.coordinate_order.clone(),
+            writers,
+            bounding_box,
+        }
+    }
+}
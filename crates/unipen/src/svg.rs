@@ -0,0 +1,123 @@
+use std::fmt::Write;
+
+use crate::model::{Component, ComponentSet, Coordinate};
+
+/// Options for drawing a `ComponentSet` as SVG.
+#[derive(Debug, Clone)]
+pub struct SvgOptions {
+    flip_y: bool,
+    stroke_width: f64,
+    padding: f64,
+}
+
+impl Default for SvgOptions {
+    fn default() -> Self {
+        Self {
+            flip_y: true,
+            stroke_width: 1.0,
+            padding: 0.0,
+        }
+    }
+}
+
+impl SvgOptions {
+    /// Creates the default options: the Y axis is flipped, strokes are 1 unit wide, and the view box is not padded.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets whether the Y axis is flipped. UniPen data usually has its origin at the bottom left, while SVG has its origin at
+    /// the top left.
+    #[must_use]
+    pub const fn flip_y(mut self, flip_y: bool) -> Self {
+        self.flip_y = flip_y;
+        self
+    }
+
+    /// Sets the stroke width, in coordinate units.
+    #[must_use]
+    pub const fn stroke_width(mut self, stroke_width: f64) -> Self {
+        self.stroke_width = stroke_width;
+        self
+    }
+
+    /// Sets the padding around the bounding box of the drawn coordinates, in coordinate units.
+    #[must_use]
+    pub const fn padding(mut self, padding: f64) -> Self {
+        self.padding = padding;
+        self
+    }
+}
+
+impl ComponentSet {
+    /// Draws the pen down components as SVG paths. Pen up components are not drawn.
+    ///
+    /// # Arguments
+    ///
+    /// * `options` - The drawing options.
+    ///
+    /// # Returns
+    ///
+    /// An SVG document with one `<path>` for each non-empty pen down component. The view box fits the drawn coordinates.
+    #[must_use]
+    pub fn to_svg(&self, options: &SvgOptions) -> String {
+        let strokes: Vec<&[Coordinate]> = self
+            .components
+            .iter()
+            .filter_map(|component| match component {
+                Component::PenDown(range) => self.coordinates.get(range.clone()),
+                _ => None,
+            })
+            .filter(|stroke| !stroke.is_empty())
+            .collect();
+
+        let (mut x_min, mut y_min, mut x_max, mut y_max) = (f64::INFINITY, f64::INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY);
+        for coordinate in strokes.iter().flat_map(|stroke| stroke.iter()) {
+            x_min = x_min.min(coordinate.x_position);
+            y_min = y_min.min(coordinate.y_position);
+            x_max = x_max.max(coordinate.x_position);
+            y_max = y_max.max(coordinate.y_position);
+        }
+        if strokes.is_empty() {
+            (x_min, y_min, x_max, y_max) = (0.0, 0.0, 0.0, 0.0);
+        }
+        // Mirroring around the middle of the bounding box keeps the view box in place
+        let y = |coordinate: &Coordinate| {
+            if options.flip_y {
+                y_min + y_max - coordinate.y_position
+            } else {
+                coordinate.y_position
+            }
+        };
+
+        let mut svg = String::new();
+        // Writing to a String cannot fail
+        let _ = writeln!(
+            svg,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="{} {} {} {}">"#,
+            x_min - options.padding,
+            y_min - options.padding,
+            2.0f64.mul_add(options.padding, x_max - x_min),
+            2.0f64.mul_add(options.padding, y_max - y_min)
+        );
+        for stroke in strokes {
+            svg.push_str(r#"<path d=""#);
+            for (i, coordinate) in stroke.iter().enumerate() {
+                let command = if i == 0 { "M" } else { " L" };
+                let _ = write!(svg, "{command}{} {}", coordinate.x_position, y(coordinate));
+            }
+            // A lone move is not drawn, so single points are drawn as a line to themselves
+            if let [coordinate] = stroke {
+                let _ = write!(svg, " L{} {}", coordinate.x_position, y(coordinate));
+            }
+            let _ = writeln!(
+                svg,
+                r#"" fill="none" stroke="black" stroke-width="{}" stroke-linecap="round" stroke-linejoin="round"/>"#,
+                options.stroke_width
+            );
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
+}
@@ -0,0 +1,88 @@
+use std::time::Duration;
+
+use crate::model::{Component, ComponentSet, Coordinate};
+
+/// How `ComponentSet::fix_timestamps` repairs times that go backwards.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TimePolicy {
+    /// Raises each time that is earlier than the time before it to that time.
+    Clamp,
+    /// Gives the points of each pen component with a backward time new times that are `1 / points_per_second` apart. The
+    /// component starts at its first time, or at the last time of the component before it if that is later.
+    Reinterpolate { points_per_second: f64 },
+}
+
+fn is_monotonic(coordinates: &[Coordinate]) -> bool {
+    coordinates.windows(2).all(|pair| pair[0].time <= pair[1].time)
+}
+
+impl ComponentSet {
+    /// Returns whether the times of the coordinates of the pen components never decrease, in the order of the components.
+    #[must_use]
+    pub fn has_monotonic_time(&self) -> bool {
+        let times: Vec<Duration> = self.coordinates_iter().map(|coordinate| coordinate.time).collect();
+        times.windows(2).all(|pair| pair[0] <= pair[1])
+    }
+
+    /// Repairs times that go backwards, so that `has_monotonic_time` holds for the result. Components, segments, and bounding
+    /// boxes are unchanged.
+    ///
+    /// # Arguments
+    ///
+    /// * `policy` - How to repair the times.
+    ///
+    /// # Panics
+    ///
+    /// If the `points_per_second` of `TimePolicy::Reinterpolate` is not positive and finite.
+    #[must_use]
+    pub fn fix_timestamps(&self, policy: TimePolicy) -> Self {
+        let interval = match policy {
+            TimePolicy::Clamp => None,
+            TimePolicy::Reinterpolate { points_per_second } => {
+                assert!(
+                    points_per_second > 0.0 && points_per_second.is_finite(),
+                    "Points per second must be positive, got {points_per_second}"
+                );
+                Some(Duration::from_secs_f64(1.0 / points_per_second))
+            }
+        };
+        let mut coordinates = self.coordinates.to_vec();
+        // The last time of the components so far
+        let mut previous: Option<Duration> = None;
+        for component in self.components.iter() {
+            let (Component::PenDown(range) | Component::PenUp(range)) = component else {
+                continue;
+            };
+            // The range of an empty component may lie past the end of the coordinates
+            let Some(stroke) = coordinates.get_mut(range.clone()) else {
+                continue;
+            };
+            let Some(first) = stroke.first() else {
+                continue;
+            };
+            let starts_early = previous.is_some_and(|previous| first.time < previous);
+            match interval {
+                Some(interval) if starts_early || !is_monotonic(stroke) => {
+                    let mut time = previous.map_or(first.time, |previous| previous.max(first.time));
+                    for coordinate in stroke.iter_mut() {
+                        coordinate.time = time;
+                        time += interval;
+                    }
+                }
+                Some(_) => {}
+                None => {
+                    let mut latest = previous.unwrap_or_default();
+                    for coordinate in stroke.iter_mut() {
+                        latest = latest.max(coordinate.time);
+                        coordinate.time = latest;
+                    }
+                }
+            }
+            previous = stroke.last().map(|coordinate| coordinate.time);
+        }
+        Self {
+            coordinates: coordinates.into(),
+            ..self.clone()
+        }
+    }
+}
@@ -0,0 +1,492 @@
+use std::{
+    fmt::{self, Display, Formatter, Write},
+    ops::RangeInclusive,
+    time::Duration,
+};
+
+use crate::model::{
+    Acceptance, AlphabetItem, Component, ComponentIndex, ComponentSet, Coordinate, CoordinateIndex, CoordinateType,
+    DataDocumentation, Date, Hand, Layout, Lexicon, Quality, SetReference, Sex, Skill, Style, UniPen, Units, Writer,
+};
+
+/// Escapes a label so that the parser un-escapes it to the same text. The result is enclosed in quotes.
+#[must_use]
+pub fn escape_label(label: &str) -> String {
+    let mut escaped = String::with_capacity(label.len() + 2);
+    escaped.push('"');
+    for character in label.chars() {
+        match character {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+/// Escapes free text so that no line of it can be mistaken for the start of a statement.
+#[must_use]
+pub fn escape_free_text(text: &str) -> String {
+    text.replace("\n.", "\n .")
+}
+
+// Free text statements without a separator in the grammar keep the leading whitespace of their text
+fn write_free_text(f: &mut Formatter<'_>, keyword: &str, text: &str) -> fmt::Result {
+    let separator = if text.starts_with(char::is_whitespace) { "" } else { " " };
+    writeln!(f, "{keyword}{separator}{}", escape_free_text(text))
+}
+
+// Raw time values are in the units of .POINTS_PER_SECOND when it is declared, and in milliseconds otherwise
+fn raw_time(duration: Duration, points_per_second: Option<f64>) -> f64 {
+    // Avoids the rounding noise of `as_secs_f64() * 1000.0`
+    #[allow(clippy::cast_precision_loss)]
+    let nanos = duration.as_nanos() as f64;
+    match points_per_second {
+        Some(points_per_second) => nanos * points_per_second / 1_000_000_000.0,
+        None => nanos / 1_000_000.0,
+    }
+}
+
+pub(crate) const fn coordinate_name(coordinate_type: &CoordinateType) -> &'static str {
+    match coordinate_type {
+        CoordinateType::XPosition => "X",
+        CoordinateType::YPosition => "Y",
+        CoordinateType::Time => "T",
+        CoordinateType::Pressure => "P",
+        CoordinateType::ZPosition => "Z",
+        CoordinateType::Button => "BUTTON",
+        CoordinateType::Rho => "RHO",
+        CoordinateType::Theta => "THETA",
+        CoordinateType::Phi => "PHI",
+    }
+}
+
+fn coordinate_value(coordinate: &Coordinate, coordinate_type: &CoordinateType, points_per_second: Option<f64>) -> Option<f64> {
+    match coordinate_type {
+        CoordinateType::XPosition => Some(coordinate.x_position),
+        CoordinateType::YPosition => Some(coordinate.y_position),
+        CoordinateType::Time => Some(raw_time(coordinate.time, points_per_second)),
+        CoordinateType::Pressure => coordinate.pressure,
+        CoordinateType::ZPosition => coordinate.z_position,
+        CoordinateType::Button => coordinate.button,
+        CoordinateType::Rho => coordinate.rho,
+        CoordinateType::Theta => coordinate.theta,
+        CoordinateType::Phi => coordinate.phi,
+    }
+}
+
+fn write_data_documentation(f: &mut Formatter<'_>, data_documentation: &DataDocumentation) -> fmt::Result {
+    let statements = [
+        (".DATA_CONTACT", &data_documentation.data_contact),
+        (".DATA_INFO", &data_documentation.data_info),
+        (".SETUP", &data_documentation.setup),
+        (".PAD", &data_documentation.pad),
+    ];
+    for (keyword, text) in statements {
+        if let Some(text) = text {
+            write_free_text(f, keyword, text)?;
+        }
+    }
+    Ok(())
+}
+
+fn write_layout(f: &mut Formatter<'_>, layout: &Layout) -> fmt::Result {
+    if let Some(x_dimension) = layout.x_dimension {
+        writeln!(f, ".X_DIM {x_dimension}")?;
+    }
+    if let Some(y_dimension) = layout.y_dimension {
+        writeln!(f, ".Y_DIM {y_dimension}")?;
+    }
+    for (keyword, lines) in [(".H_LINE", &layout.h_lines), (".V_LINE", &layout.v_lines)] {
+        if let Some(lines) = lines {
+            f.write_str(keyword)?;
+            for line in lines {
+                write!(f, " {line}")?;
+            }
+            writeln!(f)?;
+        }
+    }
+    Ok(())
+}
+
+fn write_units(f: &mut Formatter<'_>, units: &Units) -> fmt::Result {
+    let statements = [
+        (".X_POINTS_PER_INCH", units.x_points_per_inch),
+        (".Y_POINTS_PER_INCH", units.y_points_per_inch),
+        (".Z_POINTS_PER_INCH", units.z_points_per_inch),
+        (".X_POINTS_PER_MM", units.x_points_per_mm),
+        (".Y_POINTS_PER_MM", units.y_points_per_mm),
+        (".Z_POINTS_PER_MM", units.z_points_per_mm),
+        (".POINTS_PER_GRAM", units.points_per_gram),
+        (".POINTS_PER_SECOND", units.points_per_second),
+    ];
+    for (keyword, value) in statements {
+        if let Some(value) = value {
+            writeln!(f, "{keyword} {value}")?;
+        }
+    }
+    Ok(())
+}
+
+// Written as month, day and year separated by spaces, with `?` for the unknown parts
+fn write_date(f: &mut Formatter<'_>, date: &Date) -> fmt::Result {
+    f.write_str(".DATE")?;
+    for part in [date.month, date.day, date.year] {
+        match part {
+            Some(part) => write!(f, " {part}")?,
+            None => f.write_str(" ?")?,
+        }
+    }
+    writeln!(f)
+}
+
+fn write_writer(f: &mut Formatter<'_>, writer: &Writer) -> fmt::Result {
+    writeln!(f, ".WRITER_ID {}", writer.writer_id)?;
+    if let Some(country) = &writer.country {
+        writeln!(f, ".COUNTRY {}", escape_free_text(country))?;
+    }
+    if let Some(hand) = &writer.hand {
+        let hand = match hand {
+            Hand::Left => "L",
+            Hand::Right => "R",
+        };
+        writeln!(f, ".HAND {hand}")?;
+    }
+    if let Some(age) = writer.age {
+        writeln!(f, ".AGE {age}")?;
+    }
+    if let Some(sex) = &writer.sex {
+        let sex = match sex {
+            Sex::Male => "M",
+            Sex::Female => "F",
+        };
+        writeln!(f, ".SEX {sex}")?;
+    }
+    if let Some(skill) = &writer.skill {
+        let skill = match skill {
+            Skill::Bad => "BAD",
+            Skill::Ok => "OK",
+            Skill::Good => "GOOD",
+        };
+        writeln!(f, ".SKILL {skill}")?;
+    }
+    if let Some(writer_info) = &writer.writer_info {
+        write_free_text(f, ".WRITER_INFO", writer_info)?;
+    }
+    Ok(())
+}
+
+fn write_index_ranges(f: &mut Formatter<'_>, ranges: &[RangeInclusive<ComponentIndex>]) -> fmt::Result {
+    for (i, range) in ranges.iter().enumerate() {
+        if i > 0 {
+            f.write_char(',')?;
+        }
+        if range.start() == range.end() {
+            write!(f, "{}", range.start())?;
+        } else {
+            write!(f, "{}-{}", range.start(), range.end())?;
+        }
+    }
+    Ok(())
+}
+
+fn write_set_references(f: &mut Formatter<'_>, keyword: &str, sets: &[SetReference]) -> fmt::Result {
+    for set in sets {
+        write!(f, "{keyword} {} {}", set.source, set.id)?;
+        if let Some(hierarchy) = &set.hierarchy {
+            write!(f, " {hierarchy}")?;
+        }
+        f.write_char(' ')?;
+        write_index_ranges(f, &set.components)?;
+        writeln!(f)?;
+    }
+    Ok(())
+}
+
+struct ComponentSetWriter<'a> {
+    component_set: &'a ComponentSet,
+    // Coordinate ranges of the numbered components
+    component_ranges: Vec<RangeInclusive<CoordinateIndex>>,
+}
+
+impl<'a> ComponentSetWriter<'a> {
+    fn new(component_set: &'a ComponentSet) -> Self {
+        let component_ranges = component_set
+            .components
+            .iter()
+            .filter_map(|component| match component {
+                Component::PenDown(range) | Component::PenUp(range) if !range.is_empty() => Some(range.clone()),
+                _ => None,
+            })
+            .collect();
+        Self {
+            component_set,
+            component_ranges,
+        }
+    }
+
+    // Returns the component and point of a coordinate
+    fn locate(&self, index: CoordinateIndex) -> Result<(ComponentIndex, usize), fmt::Error> {
+        self.component_ranges
+            .iter()
+            .position(|range| range.contains(&index))
+            .map(|component| (component, index - self.component_ranges[component].start()))
+            .ok_or(fmt::Error)
+    }
+
+    fn write_coordinate_ranges(&self, f: &mut Formatter<'_>, ranges: &[RangeInclusive<CoordinateIndex>]) -> fmt::Result {
+        for (i, range) in ranges.iter().enumerate() {
+            if i > 0 {
+                f.write_char(',')?;
+            }
+            let (start_component, start_point) = self.locate(*range.start())?;
+            let (end_component, end_point) = self.locate(*range.end())?;
+            let whole = start_point == 0 && range.end() == self.component_ranges[end_component].end();
+            if whole && start_component == end_component {
+                write!(f, "{start_component}")?;
+            } else if whole {
+                write!(f, "{start_component}-{end_component}")?;
+            } else if range.start() == range.end() {
+                write!(f, "{start_component}:{start_point}")?;
+            } else {
+                write!(f, "{start_component}:{start_point}-{end_component}:{end_point}")?;
+            }
+        }
+        Ok(())
+    }
+
+    fn write(&self, f: &mut Formatter<'_>, coordinate_order: &[CoordinateType], points_per_second: Option<f64>) -> fmt::Result {
+        let component_set = self.component_set;
+        if component_set.name.is_empty() {
+            writeln!(f, ".START_SET")?;
+        } else {
+            writeln!(f, ".START_SET {}", component_set.name)?;
+        }
+        for (index, component) in component_set.components.iter().enumerate() {
+            let keyword = match component {
+                Component::PenDown(_) => ".PEN_DOWN",
+                Component::PenUp(_) => ".PEN_UP",
+                Component::Dt(dt) => {
+                    writeln!(f, ".DT {}", raw_time(*dt, points_per_second))?;
+                    continue;
+                }
+            };
+            f.write_str(keyword)?;
+            for coordinate in component_set.points_for_component(index).unwrap_or_default() {
+                writeln!(f)?;
+                for (i, coordinate_type) in coordinate_order.iter().enumerate() {
+                    let separator = if i > 0 { " " } else { "" };
+                    let value = coordinate_value(coordinate, coordinate_type, points_per_second).ok_or(fmt::Error)?;
+                    write!(f, "{separator}{value}")?;
+                }
+            }
+            writeln!(f)?;
+        }
+        for segment in component_set.segments.iter() {
+            write!(f, ".SEGMENT {} ", segment.hierarchy)?;
+            self.write_coordinate_ranges(f, &segment.coordinates)?;
+            match segment.quality {
+                Some(Quality::Ok) => f.write_str(" OK")?,
+                Some(Quality::Good) => f.write_str(" GOOD")?,
+                None => {}
+            }
+            if let Some(label) = &segment.label {
+                write!(f, " {}", escape_label(label))?;
+            }
+            writeln!(f)?;
+        }
+        for bounding_box in component_set.bounding_boxes.iter() {
+            write!(
+                f,
+                ".START_BOX {} {} {} {}",
+                bounding_box.x_min, bounding_box.y_min, bounding_box.x_max, bounding_box.y_max
+            )?;
+            if !bounding_box.coordinates.is_empty() {
+                f.write_char(' ')?;
+                self.write_coordinate_ranges(f, &bounding_box.coordinates)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+// Frequencies are only written when every character has one, as .ALPHABET_FREQ pairs them by position
+fn write_alphabet(f: &mut Formatter<'_>, alphabet: &[AlphabetItem]) -> fmt::Result {
+    f.write_str(".ALPHABET")?;
+    for item in alphabet {
+        write!(f, " {}", escape_label(&item.character.to_string()))?;
+    }
+    writeln!(f)?;
+    if let Some(frequencies) = alphabet.iter().map(|item| item.frequency).collect::<Option<Vec<_>>>() {
+        f.write_str(".ALPHABET_FREQ")?;
+        for frequency in frequencies {
+            write!(f, " {frequency}")?;
+        }
+        writeln!(f)?;
+    }
+    Ok(())
+}
+
+fn write_lexicon(f: &mut Formatter<'_>, lexicon: &Lexicon) -> fmt::Result {
+    if let Some(source) = &lexicon.lexicon_source {
+        write_free_text(f, ".LEXICON_SOURCE", source)?;
+    }
+    if let Some(id) = &lexicon.lexicon_id {
+        writeln!(f, ".LEXICON_ID {id}")?;
+    }
+    if let Some(contact) = &lexicon.lexicon_contact {
+        write_free_text(f, ".LEXICON_CONTACT", contact)?;
+    }
+    if let Some(info) = &lexicon.lexicon_info {
+        write_free_text(f, ".LEXICON_INFO", info)?;
+    }
+    let Some(items) = &lexicon.lexicon else {
+        return Ok(());
+    };
+    f.write_str(".LEXICON")?;
+    for item in items {
+        write!(f, " {}", escape_label(&item.label))?;
+    }
+    writeln!(f)?;
+    // Like .ALPHABET_FREQ, frequencies are only written when every word has one
+    if let Some(frequencies) = items.iter().map(|item| item.frequency).collect::<Option<Vec<_>>>() {
+        f.write_str(".LEXICON_FREQ")?;
+        for frequency in frequencies {
+            write!(f, " {frequency}")?;
+        }
+        writeln!(f)?;
+    }
+    Ok(())
+}
+
+/// Writes the UniPen data as UniPen keyword statements.
+///
+/// Times are written in the units of `.POINTS_PER_SECOND` when it is declared, and in milliseconds otherwise. A coordinate
+/// type of `.COORD` that any coordinate lacks is left out of `.COORD` and the pen data. `.REC_TIME` is not written, as the
+/// components it applies to are not kept in the model.
+impl Display for UniPen {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let coordinate_order: Vec<CoordinateType> = self
+            .coordinate_order
+            .iter()
+            .filter(|coordinate_type| {
+                self.all_coordinates()
+                    .all(|(_, coordinate)| coordinate_value(coordinate, coordinate_type, None).is_some())
+            })
+            .copied()
+            .collect();
+        writeln!(f, ".VERSION {}", self.version)?;
+        // Only the declared names are kept in the model
+        for keyword in &self.declared_keywords {
+            writeln!(f, ".KEYWORD {keyword}")?;
+        }
+        for reserved in &self.declared_reserved {
+            writeln!(f, ".RESERVE {reserved}")?;
+        }
+        writeln!(f, ".DATA_SOURCE {}", escape_free_text(&self.data_source))?;
+        writeln!(f, ".DATA_ID {}", self.data_id)?;
+        f.write_str(".COORD")?;
+        for coordinate_type in &coordinate_order {
+            write!(f, " {}", coordinate_name(coordinate_type))?;
+        }
+        writeln!(f)?;
+        f.write_str(".HIERARCHY")?;
+        for hierarchy in &self.hierarchy_order {
+            write!(f, " {hierarchy}")?;
+        }
+        writeln!(f)?;
+        for comment in &self.comments {
+            write_free_text(f, ".COMMENT", comment)?;
+        }
+        write_data_documentation(f, &self.data_documentation)?;
+        if let Some(alphabet) = &self.alphabet {
+            write_alphabet(f, alphabet)?;
+        }
+        if let Some(lexicon) = &self.lexicon {
+            write_lexicon(f, lexicon)?;
+        }
+        write_layout(f, &self.layout)?;
+        write_units(f, &self.units)?;
+        if let Some(date) = &self.date {
+            write_date(f, date)?;
+        }
+        if let Some(writer) = &self.writer {
+            write_writer(f, writer)?;
+        }
+
+        if let Some(recognizer) = &self.recognizer {
+            writeln!(f, ".REC_SOURCE {}", recognizer.recognizer_source)?;
+            writeln!(f, ".REC_ID {}", recognizer.recognizer_id)?;
+            if let Some(contact) = &recognizer.recognizer_contact {
+                write_free_text(f, ".REC_CONTACT", contact)?;
+            }
+            if let Some(info) = &recognizer.recognizer_info {
+                write_free_text(f, ".REC_INFO", info)?;
+            }
+            if let Some(implementation) = &recognizer.recognizer_implementation {
+                write_free_text(f, ".IMPLEMENT", implementation)?;
+            }
+        }
+        write_set_references(f, ".TRAINING_SET", &self.sets_membership.training_sets)?;
+        write_set_references(f, ".TEST_SET", &self.sets_membership.test_sets)?;
+        write_set_references(f, ".ADAPT_SET", &self.sets_membership.adapt_sets)?;
+        write_set_references(f, ".LEXICON_SET", &self.sets_membership.lexicon_sets)?;
+
+        // Sets recorded with another writer are preceded by their .WRITER_ID, and the writer of the header is restored after.
+        // The style is only kept per set, so it is written where it changes.
+        let header_writer_id = self.writer.as_ref().map(|writer| &writer.writer_id);
+        let mut writer_id = header_writer_id;
+        let mut style = None;
+        for component_set in &self.sets {
+            if let Some(set_writer_id) = &component_set.writer_id {
+                if writer_id != Some(set_writer_id) {
+                    writeln!(f, ".WRITER_ID {set_writer_id}")?;
+                    writer_id = Some(set_writer_id);
+                }
+            }
+            if component_set.style != style {
+                let name = match component_set.style {
+                    Some(Style::Printed) => "PRINTED",
+                    Some(Style::Cursive) => "CURSIVE",
+                    Some(Style::Mixed) => "MIXED",
+                    None => "?",
+                };
+                writeln!(f, ".STYLE {name}")?;
+                style = component_set.style;
+            }
+            ComponentSetWriter::new(component_set).write(f, &coordinate_order, self.units.points_per_second)?;
+        }
+        if let Some(header_writer_id) = header_writer_id.filter(|header_writer_id| writer_id != Some(header_writer_id)) {
+            writeln!(f, ".WRITER_ID {header_writer_id}")?;
+        }
+
+        for rec_label in &self.rec_labels {
+            write!(f, ".REC_LABELS {} ", rec_label.segment_ref.hierarchy)?;
+            write_index_ranges(f, &rec_label.segment_ref.components)?;
+            let acceptance = match rec_label.acceptance {
+                Some(Acceptance::Accept) => "ACCEPT",
+                Some(Acceptance::Reject) => "REJECT",
+                None => "?",
+            };
+            writeln!(f, " {acceptance} {}", escape_label(&rec_label.label))?;
+        }
+        for rec_score in &self.rec_scores {
+            write!(f, ".REC_SCORES {} ", rec_score.segment_ref.hierarchy)?;
+            write_index_ranges(f, &rec_score.segment_ref.components)?;
+            writeln!(f, " {} {}", rec_score.score, escape_label(&rec_score.label))?;
+        }
+        Ok(())
+    }
+}
+
+impl UniPen {
+    /// Writes the UniPen data as UniPen keyword statements. See the `Display` implementation.
+    #[must_use]
+    pub fn to_unipen_string(&self) -> String {
+        self.to_string()
+    }
+}
@@ -0,0 +1,514 @@
+mod common;
+
+use std::time::Duration;
+
+use common::{build, build_with, n, parse_text, rebuild, s, st, temp_dir, temp_file, HEAD};
+use unipen::error::UniPenError;
+use unipen::model::{Acceptance, Component, CoordinateType, Quality, UniPen};
+use unipen::statements::{self, Keyword, Reserved, StatementArgument};
+
+#[test]
+fn component_set_from_pen_statements() {
+    let statements = [
+        st(Keyword::Version, vec![n(1)]),
+        st(Keyword::DataSource, vec![StatementArgument::FreeText("src".into())]),
+        st(Keyword::DataId, vec![s("id")]),
+        st(
+            Keyword::Coordinate,
+            [Reserved::X, Reserved::Y, Reserved::Time]
+                .map(StatementArgument::Reserved)
+                .to_vec(),
+        ),
+        st(Keyword::Hierarchy, vec![s("WORD")]),
+        st(
+            Keyword::PenDown,
+            vec![n(1), n(2), n(10), n(3), n(4), n(20), n(5), n(6), n(30)],
+        ),
+        st(Keyword::PenUp, vec![n(7), n(8), n(40)]),
+    ];
+    let unipen = UniPen::builder().statements(statements).unwrap().build().unwrap();
+    let set = &unipen.sets()[0];
+    assert_eq!(set.coordinates.len(), 4);
+    assert_eq!(set.coordinates[2].x_position, 5.0);
+    assert_eq!(set.coordinates[2].y_position, 6.0);
+    assert_eq!(set.coordinates[3].time.as_millis(), 40);
+    assert!(
+        matches!(&set.components[..], [Component::PenDown(down), Component::PenUp(up)] if *down == (0..=2) && *up == (3..=3))
+    );
+}
+
+#[test]
+fn component_set_keeps_component_order() {
+    let unipen = build(".PEN_UP\n1 1 0\n.PEN_DOWN\n2 2 10\n3 3 20\n.PEN_UP\n4 4 30\n").unwrap();
+    let components = &unipen.sets()[0].components;
+    assert!(matches!(&components[0], Component::PenUp(range) if *range == (0..=0)));
+    assert!(matches!(&components[1], Component::PenDown(range) if *range == (1..=2)));
+    assert!(matches!(&components[2], Component::PenUp(range) if *range == (3..=3)));
+}
+
+#[test]
+fn build_fills_headers() {
+    let unipen = build(".PEN_DOWN\n1 2 3\n").unwrap();
+    assert_eq!(unipen.version(), 1.0);
+    assert_eq!(unipen.data_source().trim(), "src");
+    assert_eq!(unipen.data_id(), "id");
+    assert_eq!(
+        unipen.coordinate_order(),
+        [CoordinateType::XPosition, CoordinateType::YPosition, CoordinateType::Time]
+    );
+    assert_eq!(
+        unipen.hierarchy_order().iter().map(|level| &**level).collect::<Vec<_>>(),
+        ["WORD"]
+    );
+    assert_eq!(unipen.sets().len(), 1);
+}
+
+#[test]
+fn build_requires_mandatory_statements() {
+    let error = UniPen::builder().build().err().unwrap();
+    assert!(matches!(&error, UniPenError::Validation(message) if message == "Missing mandatory .VERSION statement"));
+    let error = build_with(".VERSION 1.0\n.DATA_SOURCE src\n.COORD X Y T\n.HIERARCHY WORD\n", "")
+        .err()
+        .unwrap();
+    assert!(matches!(&error, UniPenError::Validation(message) if message == "Missing mandatory .DATA_ID statement"));
+}
+
+#[test]
+fn pen_statement_makes_one_coordinate_per_point() {
+    let unipen = build_with(
+        ".VERSION 1.0\n.DATA_SOURCE src\n.DATA_ID id\n.COORD X Y T P\n.HIERARCHY WORD\n",
+        ".PEN_DOWN\n1 2 0 5\n3 4 10 6\n",
+    )
+    .unwrap();
+    let coordinates = &unipen.sets()[0].coordinates;
+    assert_eq!(coordinates.len(), 2);
+    let values: Vec<_> = coordinates
+        .iter()
+        .map(|coordinate| (coordinate.x_position, coordinate.y_position, coordinate.pressure))
+        .collect();
+    assert_eq!(values, [(1.0, 2.0, Some(5.0)), (3.0, 4.0, Some(6.0))]);
+}
+
+#[test]
+fn pen_statement_with_incomplete_point() {
+    let error = build(".PEN_DOWN\n1 2 3\n4 5\n").err().unwrap();
+    assert!(matches!(&error, UniPenError::Validation(message) if message.starts_with("Not enough numbers for coordinate order")));
+    let error = UniPen::builder()
+        .statements(common::parse_text(common::HEAD))
+        .unwrap()
+        .statement(&st(Keyword::PenDown, vec![n(1), s("two"), n(3)]))
+        .err()
+        .unwrap();
+    assert!(matches!(error, UniPenError::Translation(_)));
+}
+
+#[test]
+fn dt_adds_time_component() {
+    let unipen = build(".PEN_DOWN\n1 2 10\n.DT 50\n.PEN_UP\n3 4 70\n").unwrap();
+    let components = &unipen.sets()[0].components;
+    assert_eq!(components.len(), 3);
+    assert!(matches!(&components[1], Component::Dt(dt) if dt.as_millis() == 50));
+    let error = UniPen::builder()
+        .statements(common::parse_text(common::HEAD))
+        .unwrap()
+        .statement(&st(Keyword::Dt, vec![s("x")]))
+        .err()
+        .unwrap();
+    assert!(matches!(error, UniPenError::Translation(_)));
+}
+
+#[test]
+fn date_forms() {
+    let date = |text: &str| {
+        let unipen = build(&format!(".DATE {text}\n"))?;
+        Ok::<_, UniPenError>(unipen.date().map(|date| (date.month(), date.day(), date.year())))
+    };
+    assert_eq!(date("10 12 1993").unwrap(), Some((Some(10), Some(12), Some(1993))));
+    assert_eq!(date("10/12/1993").unwrap(), Some((Some(10), Some(12), Some(1993))));
+    assert_eq!(date("25-12-1993").unwrap(), Some((Some(12), Some(25), Some(1993))));
+    assert_eq!(date("? 12 ?").unwrap(), Some((None, Some(12), None)));
+    assert_eq!(date("?").unwrap(), None);
+    assert!(matches!(date("13/45/1993"), Err(UniPenError::Validation(_))));
+    assert!(matches!(date("25/12/1993"), Err(UniPenError::Validation(_))));
+    assert!(build("").unwrap().date().is_none());
+}
+
+#[test]
+fn date_is_written_back() {
+    for text in ["10 12 1993", "? 12 ?"] {
+        let unipen = build(&format!(".DATE {text}\n")).unwrap();
+        assert!(unipen.to_unipen_string().contains(&format!("\n.DATE {text}\n")));
+        assert!(rebuild(&unipen) == unipen);
+    }
+}
+
+#[test]
+fn segments_resolve_component_lists() {
+    let unipen = build(
+        ".PEN_DOWN 1 2 3 4 5 6\n.PEN_UP 7 8 9\n.PEN_DOWN 1 1 1 2 2 2 3 3 3\n.SEGMENT WORD 0-2\n.SEGMENT WORD 0,1 OK\n\
+         .SEGMENT WORD 2:1 \"lbl\"\n.SEGMENT WORD 0:1-2:0 GOOD \"x y\"\n",
+    )
+    .unwrap();
+    let segments = &unipen.sets()[0].segments;
+    assert_eq!(segments.len(), 4);
+    assert_eq!(&*segments[0].coordinates, [0..=5]);
+    assert!(segments[0].quality.is_none() && segments[0].label.is_none());
+    assert_eq!(&*segments[1].coordinates, [0..=1, 2..=2]);
+    assert!(matches!(segments[1].quality, Some(Quality::Ok)));
+    assert_eq!(&*segments[2].coordinates, [4..=4]);
+    assert_eq!(segments[2].label.as_deref(), Some("lbl"));
+    assert_eq!(&*segments[3].coordinates, [1..=3]);
+    assert!(matches!(segments[3].quality, Some(Quality::Good)));
+    assert_eq!(segments[3].label.as_deref(), Some("x y"));
+}
+
+#[test]
+fn segment_of_missing_component() {
+    let error = build(".PEN_DOWN 1 2 3\n.SEGMENT WORD 3\n").err().unwrap();
+    assert!(
+        matches!(&error, UniPenError::Validation(message) if message.starts_with("Segment references nonexistent component 3"))
+    );
+    let error = build(".PEN_DOWN 1 2 3\n.SEGMENT WORD 0:1\n").err().unwrap();
+    assert!(matches!(&error, UniPenError::Validation(message) if message.starts_with("Segment references nonexistent point 1")));
+}
+
+#[test]
+fn start_set_rolls_over() {
+    let unipen = build(".START_SET a\n.PEN_DOWN 1 2 3\n.START_SET\n.START_SET b\n.PEN_DOWN 1 2 3\n.PEN_UP 1 2 3\n").unwrap();
+    let names: Vec<_> = unipen.sets().iter().map(|set| &*set.name).collect();
+    assert_eq!(names, ["a", "b"]);
+    assert_eq!(unipen.sets()[1].coordinates.len(), 2);
+}
+
+#[test]
+fn start_box_bounding_boxes() {
+    let unipen =
+        build(".PEN_DOWN 1 2 3 4 5 6\n.PEN_UP 7 8 9\n.START_BOX 0 0 10 10 0-1\n.START_BOX 0 0 10.5 10 1\n.START_BOX -1 -1 0 0\n")
+            .unwrap();
+    let bounding_boxes = &unipen.sets()[0].bounding_boxes;
+    assert_eq!(bounding_boxes.len(), 3);
+    assert_eq!(&*bounding_boxes[0].coordinates, [0..=2]);
+    assert_eq!(bounding_boxes[1].x_max, 10.5);
+    assert_eq!(&*bounding_boxes[1].coordinates, [2..=2]);
+    assert_eq!((bounding_boxes[2].x_min, bounding_boxes[2].y_min), (-1.0, -1.0));
+    assert!(bounding_boxes[2].coordinates.is_empty());
+    assert!(matches!(build(".START_BOX 10 0 0 10\n"), Err(UniPenError::Validation(_))));
+    assert!(build(".START_BOX 0 0 10\n").is_err());
+}
+
+#[test]
+fn recognizer_documentation() {
+    assert!(build("").unwrap().recognizer().is_none());
+    let unipen =
+        build(".REC_SOURCE src\n.REC_ID rid\n.REC_CONTACT me@example.com\n.REC_INFO info here\n.IMPLEMENT impl\n").unwrap();
+    let recognizer = unipen.recognizer().unwrap();
+    assert_eq!(recognizer.recognizer_source(), "src");
+    assert_eq!(recognizer.recognizer_id(), "rid");
+    assert_eq!(recognizer.recognizer_contact().map(str::trim), Some("me@example.com"));
+    assert_eq!(recognizer.recognizer_info().map(str::trim), Some("info here"));
+    assert_eq!(recognizer.recognizer_implementation().map(str::trim), Some("impl"));
+    let error = build(".REC_INFO info\n").err().unwrap();
+    assert!(matches!(&error, UniPenError::Validation(message) if message == "Missing mandatory .REC_SOURCE statement"));
+}
+
+#[test]
+fn set_membership() {
+    let unipen = build(
+        ".PEN_DOWN 1 2 3\n.PEN_UP 1 2 3\n.START_SET b\n.PEN_DOWN 1 2 3\n\
+         .TRAINING_SET SRC ID WORD 0-1,2 SRC ID2 WORD 1\n.LEXICON_SET LS LI 5-90\n",
+    )
+    .unwrap();
+    let training_sets = unipen.sets_membership().training_sets();
+    assert_eq!(training_sets.len(), 2);
+    assert_eq!((training_sets[0].source(), training_sets[0].id()), ("SRC", "ID"));
+    assert_eq!(training_sets[0].hierarchy(), Some("WORD"));
+    assert_eq!(training_sets[0].components(), [0..=1, 2..=2]);
+    assert_eq!(training_sets[1].components(), [1..=1]);
+    assert!(unipen.sets_membership().test_sets().is_empty());
+    let lexicon_sets = unipen.sets_membership().lexicon_sets();
+    assert_eq!(lexicon_sets[0].hierarchy(), None);
+    assert_eq!(lexicon_sets[0].components(), [5..=90]);
+    assert!(matches!(
+        build(".PEN_DOWN 1 2 3\n.TEST_SET SRC ID WORD 0-1\n"),
+        Err(UniPenError::Validation(_))
+    ));
+}
+
+#[test]
+fn recognition_labels_and_scores() {
+    let unipen = build(
+        ".PEN_DOWN 1 2 3\n.PEN_UP 1 2 3\n.REC_LABELS WORD 0-1 ACCEPT \"hello\" \"hallo\"\n.REC_LABELS WORD 1 ? \"x\"\n\
+         .REC_SCORES WORD 0 -12.5 \"hello\" -20 \"hallo\"\n",
+    )
+    .unwrap();
+    let rec_labels = unipen.rec_labels();
+    assert_eq!(rec_labels.len(), 3);
+    assert!(matches!(rec_labels[0].acceptance(), Some(Acceptance::Accept)));
+    assert_eq!(rec_labels[1].label(), "hallo");
+    assert_eq!(rec_labels[1].segment_ref().hierarchy(), "WORD");
+    assert_eq!(rec_labels[1].segment_ref().components(), [0..=1]);
+    assert!(rec_labels[2].acceptance().is_none());
+    let rec_scores = unipen.rec_scores();
+    assert_eq!(rec_scores.len(), 2);
+    assert_eq!((rec_scores[0].score(), rec_scores[0].label()), (-12.5, "hello"));
+    assert_eq!((rec_scores[1].score(), rec_scores[1].label()), (-20.0, "hallo"));
+    assert_eq!(rec_scores[1].segment_ref().components(), [0..=0]);
+    assert!(matches!(
+        build(".PEN_DOWN 1 2 3\n.REC_LABELS WORD 4 ACCEPT \"x\"\n"),
+        Err(UniPenError::Validation(_))
+    ));
+}
+
+#[test]
+fn recognizer_time() {
+    assert!(build(".PEN_DOWN 1 2 3\n").unwrap().recognizer_time().is_none());
+    let unipen = build(".PEN_DOWN 1 2 3\n.REC_TIME 0 250\n").unwrap();
+    assert_eq!(unipen.recognizer_time(), Some(Duration::from_millis(250)));
+    let unipen = build(".POINTS_PER_SECOND 100\n.PEN_DOWN 1 2 3\n.REC_TIME 0 250\n").unwrap();
+    assert_eq!(unipen.recognizer_time(), Some(Duration::from_millis(2500)));
+}
+
+#[test]
+fn times_from_points_per_second() {
+    let millis = |unipen: &UniPen| {
+        unipen.sets()[0]
+            .coordinates
+            .iter()
+            .map(|c| c.time.as_millis())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(millis(&build(".PEN_DOWN 1 2 30 1 2 40\n").unwrap()), [30, 40]);
+    assert_eq!(
+        millis(&build(".POINTS_PER_SECOND 100\n.PEN_DOWN 1 2 30 1 2 40\n").unwrap()),
+        [300, 400]
+    );
+    let head = ".VERSION 1.0\n.DATA_SOURCE src\n.DATA_ID id\n.COORD X Y\n.HIERARCHY WORD\n";
+    let unipen = build_with(head, ".POINTS_PER_SECOND 100\n.PEN_DOWN 1 2 1 2 1 2\n.DT 5\n.PEN_UP 1 2\n").unwrap();
+    assert_eq!(millis(&unipen), [0, 10, 20, 80]);
+    let error = build_with(head, ".PEN_DOWN 1 2\n").err().unwrap();
+    let expected = "Pen statement without Time coordinate or .POINTS_PER_SECOND";
+    assert!(matches!(&error, UniPenError::Validation(message) if message.starts_with(expected)));
+}
+
+#[test]
+fn statements_fold_over_an_iterator() {
+    let file = temp_file(format!("{HEAD}.PEN_DOWN\n0 0 0\n5 5 10\n.PEN_UP\n7 7 20\n"));
+    let streamed = UniPen::builder()
+        .statements(statements::parse_iter(&file, None).map(Result::unwrap))
+        .unwrap()
+        .build()
+        .unwrap();
+    let mut builder = UniPen::builder();
+    for statement in &statements::parse(&file, None).unwrap() {
+        builder = builder.statement(statement).unwrap();
+    }
+    let folded = builder.build().unwrap();
+    assert!(streamed == folded);
+    assert_eq!(streamed.sets()[0].coordinates.len(), 3);
+
+    // Statements after the first error are not read
+    let statements = parse_text(".VERSION 1.0\n.PEN_DOWN\n0 0 0\n.COORD X Y T\n");
+    let mut read = 0;
+    let error = UniPen::builder()
+        .statements(statements.into_iter().inspect(|_| read += 1))
+        .err()
+        .unwrap();
+    assert!(matches!(error, UniPenError::Validation(_)));
+    assert_eq!(read, 3);
+}
+
+#[test]
+fn errors_report_the_statement_position() {
+    let error = build(".PEN_DOWN\n0 0\n").err().unwrap();
+    assert!(
+        matches!(&error, UniPenError::Validation(message) if message.contains("(line 6, column 1)")),
+        "{error}"
+    );
+
+    let file = temp_file(".VERSION 1.0\n.COMMENT x\n\n.DT 1\n");
+    let eager: Vec<_> = statements::parse(&file, None)
+        .unwrap()
+        .iter()
+        .map(|statement| statement.span)
+        .collect();
+    let lazy: Vec<_> = statements::parse_iter(&file, None)
+        .map(|statement| statement.unwrap().span)
+        .collect();
+    assert_eq!(eager, [None, Some((1, 1)), Some((2, 1)), Some((4, 1)), None]);
+    assert_eq!(lazy, eager);
+}
+
+#[test]
+fn coordinate_order_needs_x_and_y_once() {
+    let error = UniPen::builder().statements(parse_text(".COORD X T\n")).err().unwrap();
+    assert!(
+        matches!(&error, UniPenError::Validation(message) if message.contains("Missing Y coordinate in .COORD")),
+        "{error}"
+    );
+    let error = UniPen::builder().statements(parse_text(".COORD X Y X\n")).err().unwrap();
+    assert!(
+        matches!(&error, UniPenError::Validation(message) if message.contains("Duplicate X coordinate in .COORD")),
+        "{error}"
+    );
+    let unipen = build_with(&HEAD.replace(".COORD X Y T", ".COORD Y X T P"), ".PEN_DOWN\n1 2 3 4\n").unwrap();
+    let coordinate = &unipen.sets()[0].coordinates[0];
+    assert_eq!(
+        (coordinate.x_position, coordinate.y_position, coordinate.pressure),
+        (2.0, 1.0, Some(4.0))
+    );
+}
+
+#[test]
+fn unknown_lists_are_unset() {
+    let head = HEAD.replace(".HIERARCHY WORD", ".HIERARCHY ?");
+    let unipen = build_with(&head, ".ALPHABET \"A\" \"B\"\n.ALPHABET ?\n").unwrap();
+    assert!(unipen.hierarchy_order().is_empty());
+    assert!(unipen.alphabet().is_none());
+    let unipen = build(".ALPHABET \"A\" \"B\"\n").unwrap();
+    assert_eq!(unipen.alphabet().map(<[_]>::len), Some(2));
+    // An unknown hierarchy still counts as declared, unlike a missing one
+    assert!(build_with(&HEAD.replace(".HIERARCHY WORD\n", ""), "").is_err());
+}
+
+#[test]
+fn included_files_make_named_sets() {
+    let dir = temp_dir();
+    std::fs::write(dir.join("top"), format!("{HEAD}.INCLUDE a\n.INCLUDE empty\n.INCLUDE b\n")).unwrap();
+    std::fs::write(dir.join("a"), ".PEN_DOWN\n1 2 0\n3 4 10\n.PEN_UP\n").unwrap();
+    std::fs::write(dir.join("empty"), ".COMMENT nothing\n").unwrap();
+    std::fs::write(dir.join("b"), ".PEN_DOWN\n5 6 20\n.PEN_UP\n").unwrap();
+    let unipen = UniPen::builder()
+        .statements(statements::parse(&dir.join("top"), Some(&dir)).unwrap())
+        .unwrap()
+        .build()
+        .unwrap();
+    let names: Vec<_> = unipen.sets().iter().map(|set| set.name.to_string()).collect();
+    // The include without pen data makes no set
+    assert_eq!(names, [dir.join("a").to_string_lossy(), dir.join("b").to_string_lossy()]);
+    assert_eq!(unipen.sets()[0].coordinates.len(), 2);
+    assert_eq!(unipen.sets()[1].coordinates.len(), 1);
+}
+
+#[test]
+fn reset_builder_is_independent() {
+    let first = parse_text(&format!("{HEAD}.WRITER_ID a\n.AGE 5\n.DT 40\n.PEN_DOWN\n1 1 0\n"));
+    let second = parse_text(&format!("{HEAD}.PEN_DOWN\n2 2 5\n3 3 15\n"));
+    let mut builder = UniPen::builder().age_range(0..=10);
+    // Reset in the middle of the first file, with its set and include still open
+    for statement in &first[..first.len() - 1] {
+        builder = builder.statement(statement).unwrap();
+    }
+    builder.reset();
+    let built = builder.statements(second.clone()).unwrap().build().unwrap();
+    let fresh = UniPen::builder().statements(second).unwrap().build().unwrap();
+    assert!(built == fresh);
+    assert!(built.writer().is_none());
+    assert_eq!(built.sets()[0].coordinates[0].time, Duration::from_millis(5));
+
+    // The options are kept
+    let mut builder = UniPen::builder().age_range(0..=10);
+    builder.reset();
+    let result = builder
+        .statements(parse_text(&format!("{HEAD}.WRITER_ID a\n.AGE 20\n")))
+        .unwrap()
+        .build();
+    assert!(matches!(result.err(), Some(UniPenError::Validation(_))));
+}
+
+#[test]
+fn empty_pen_components() {
+    let unipen = build(".PEN_UP\n.PEN_DOWN\n1 1 0\n2 2 10\n.PEN_UP\n.SEGMENT WORD 0\n").unwrap();
+    let set = &unipen.sets()[0];
+    assert_eq!(set.components.len(), 3);
+    assert!(matches!(&set.components[0], Component::PenUp(range) if range.is_empty()));
+    assert!(matches!(&set.components[1], Component::PenDown(range) if *range == (0..=1)));
+    assert!(matches!(&set.components[2], Component::PenUp(range) if range.is_empty()));
+    assert_eq!(set.points_for_component(0), Some(&[][..]));
+    assert_eq!(set.points_for_component(1).map(<[_]>::len), Some(2));
+    assert_eq!(set.points_for_component(2), Some(&[][..]));
+    // Component lists skip empty components, so the segment refers to the pen down component
+    assert_eq!(&*set.segments[0].coordinates, [0..=1]);
+    let text = unipen.to_unipen_string();
+    assert!(text.contains(".PEN_UP\n.PEN_DOWN\n"), "{text}");
+}
+
+#[test]
+fn unknown_segment_quality() {
+    let unipen = build(".PEN_DOWN\n0 0 0\n.SEGMENT WORD 0 ? \"a\"\n.SEGMENT WORD 0 ?\n.SEGMENT WORD 0 OK\n").unwrap();
+    let segments = &unipen.sets()[0].segments;
+    assert!(segments[0].quality.is_none());
+    assert_eq!(segments[0].label.as_deref(), Some("a"));
+    assert!(segments[1].quality.is_none());
+    assert!(segments[1].label.is_none());
+    assert!(matches!(segments[2].quality, Some(Quality::Ok)));
+}
+
+#[test]
+fn explicit_plus_signs() {
+    let unipen = build(".WRITER_ID w\n.AGE +30\n.PEN_DOWN\n+1 +2.5 0\n-3 +4. +10\n").unwrap();
+    assert_eq!(unipen.writer().unwrap().age(), Some(30.0));
+    let coordinates: Vec<_> = unipen.sets()[0]
+        .coordinates
+        .iter()
+        .map(|coordinate| (coordinate.x_position, coordinate.y_position, coordinate.time))
+        .collect();
+    assert_eq!(
+        coordinates,
+        [(1.0, 2.5, Duration::ZERO), (-3.0, 4.0, Duration::from_millis(10))]
+    );
+}
+
+#[test]
+fn scientific_notation() {
+    let unipen = build(".X_POINTS_PER_MM 1.0e2\n.Y_POINTS_PER_MM 2.5E-1\n.PEN_DOWN\n1e3 -2.5E-4 0\n1.e1 3 10\n").unwrap();
+    assert_eq!(unipen.units().x_points_per_mm(), Some(100.0));
+    assert_eq!(unipen.units().y_points_per_mm(), Some(0.25));
+    let positions: Vec<_> = unipen.sets()[0]
+        .coordinates
+        .iter()
+        .map(|coordinate| (coordinate.x_position, coordinate.y_position))
+        .collect();
+    assert_eq!(positions, [(1000.0, -0.00025), (10.0, 3.0)]);
+}
+
+#[test]
+fn comments_are_kept_in_order() {
+    let unipen = build(".COMMENT first one\n.PEN_DOWN\n0 0 0\n.COMMENT second\n").unwrap();
+    let comments: Vec<&str> = unipen.comments().iter().map(|comment| comment.trim()).collect();
+    assert_eq!(comments, ["first one", "second"]);
+    let text = unipen.to_unipen_string();
+    assert!(text.contains(".COMMENT first one\n.COMMENT second\n"), "{text}");
+    assert!(rebuild(&unipen) == unipen);
+
+    // A comment without text is reported rather than indexed
+    let result = UniPen::builder().statement(&st(Keyword::Comment, vec![]));
+    assert!(matches!(result.err(), Some(UniPenError::Translation(_))));
+}
+
+#[test]
+fn declarations_are_captured() {
+    let unipen =
+        build(".KEYWORD MY_KEY S  my description\n.RESERVE FOO a reserved word\n.KEYWORD OTHER\n.PEN_DOWN\n0 0 0\n").unwrap();
+    let keywords: Vec<&str> = unipen.declared_keywords().iter().map(|name| &**name).collect();
+    assert_eq!(keywords, ["MY_KEY", "OTHER"]);
+    let reserved: Vec<&str> = unipen.declared_reserved().iter().map(|name| &**name).collect();
+    assert_eq!(reserved, ["FOO"]);
+    assert!(rebuild(&unipen) == unipen);
+
+    // Declarations without a name are reported rather than indexed
+    for keyword in [Keyword::Keyword, Keyword::Reserve] {
+        let result = UniPen::builder().statement(&st(keyword, vec![]));
+        assert!(matches!(result.err(), Some(UniPenError::Translation(_))));
+    }
+}
+
+#[test]
+fn pen_data_before_coordinates() {
+    for pen in [".PEN_DOWN\n0 0 0\n", ".PEN_UP\n"] {
+        let result = UniPen::builder().statements(parse_text(&format!(".VERSION 1.0\n.DATA_SOURCE src\n{pen}.COORD X Y T\n")));
+        assert_eq!(
+            format!("{:?}", result.err().unwrap()),
+            "Validation(\"Pen data before .COORD declaration (line 3, column 1)\")"
+        );
+    }
+}
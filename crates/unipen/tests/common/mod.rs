@@ -0,0 +1,87 @@
+#![allow(dead_code)]
+
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use unipen::error::UniPenError;
+use unipen::model::UniPen;
+use unipen::statements::{self, Keyword, Number, Statement, StatementArgument};
+
+/// The headers most tests need before pen data.
+pub const HEAD: &str = ".VERSION 1.0\n.DATA_SOURCE src\n.DATA_ID id\n.COORD X Y T\n.HIERARCHY WORD\n";
+
+pub fn st(keyword: Keyword, arguments: Vec<StatementArgument>) -> Statement {
+    Statement {
+        keyword,
+        arguments,
+        synthetic: false,
+        span: None,
+    }
+}
+
+pub fn n(value: i32) -> StatementArgument {
+    StatementArgument::Number(Number::Integer(value))
+}
+
+pub fn s(value: &str) -> StatementArgument {
+    StatementArgument::String(Rc::from(value))
+}
+
+fn unique(prefix: &str) -> PathBuf {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    std::env::temp_dir().join(format!(
+        "unipen-{prefix}-{}-{}",
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::SeqCst)
+    ))
+}
+
+/// Writes `content` to a new temporary file.
+pub fn temp_file(content: impl AsRef<[u8]>) -> PathBuf {
+    let path = unique("file");
+    std::fs::write(&path, content).unwrap();
+    path
+}
+
+/// Creates a new empty temporary directory.
+pub fn temp_dir() -> PathBuf {
+    let path = unique("dir");
+    std::fs::create_dir_all(&path).unwrap();
+    path
+}
+
+/// Parses `content` from a temporary file, so that includes and spans behave as for real files.
+pub fn parse_text(content: &str) -> Vec<Statement> {
+    statements::parse(&temp_file(content), None).unwrap()
+}
+
+pub fn build_with(head: &str, body: &str) -> Result<UniPen, UniPenError> {
+    UniPen::builder().statements(parse_text(&format!("{head}{body}")))?.build()
+}
+
+pub fn build(body: &str) -> Result<UniPen, UniPenError> {
+    build_with(HEAD, body)
+}
+
+/// Builds the UniPen text written for `unipen` again.
+pub fn rebuild(unipen: &UniPen) -> UniPen {
+    UniPen::builder()
+        .statements(parse_text(&unipen.to_unipen_string()))
+        .unwrap()
+        .build()
+        .unwrap()
+}
+
+/// A writer that fails on every write.
+pub struct FailingWriter;
+
+impl std::io::Write for FailingWriter {
+    fn write(&mut self, _: &[u8]) -> std::io::Result<usize> {
+        Err(std::io::Error::other("write failed"))
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
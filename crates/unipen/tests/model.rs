@@ -0,0 +1,564 @@
+mod common;
+
+use std::rc::Rc;
+use std::time::Duration;
+
+use common::{build, build_with, n, parse_text, rebuild, st, HEAD};
+use unipen::error::UniPenError;
+use unipen::model::{ComponentSet, Coordinate, CoordinateType, Hand, Segment, Sex, Skill, Style, UniPen};
+use unipen::statements::Keyword;
+
+const HEAD_XY: &str = ".VERSION 1.0\n.DATA_SOURCE src\n.DATA_ID id\n.COORD X Y\n.HIERARCHY WORD\n";
+
+#[test]
+fn coordinates_iter_skips_dt() {
+    let unipen = build_with(
+        HEAD_XY,
+        ".POINTS_PER_SECOND 100\n.PEN_DOWN\n1 1\n2 2\n.DT 5\n.PEN_UP\n3 3\n.PEN_DOWN\n4 4\n",
+    )
+    .unwrap();
+    let set = &unipen.sets()[0];
+    let xs: Vec<_> = set.coordinates_iter().map(|coordinate| coordinate.x_position).collect();
+    assert_eq!(xs, [1.0, 2.0, 3.0, 4.0]);
+    assert_eq!(set.points_for_component(0).unwrap().len(), 2);
+    assert!(set.points_for_component(1).is_none());
+    assert_eq!(set.points_for_component(3).unwrap()[0].x_position, 4.0);
+    assert!(set.points_for_component(9).is_none());
+}
+
+#[test]
+fn document_accessors() {
+    let unipen =
+        build(".X_POINTS_PER_MM 10\n.WRITER_ID w1\n.HAND L\n.AGE 30\n.COMMENT note\n.START_SET first\n.PEN_DOWN\n1 1 0\n")
+            .unwrap();
+    assert_eq!(unipen.version(), 1.0);
+    assert_eq!(unipen.data_id(), "id");
+    assert_eq!(unipen.units().x_points_per_mm(), Some(10.0));
+    assert_eq!(unipen.units().points_per_second(), None);
+    let writer = unipen.writer().unwrap();
+    assert_eq!(writer.writer_id(), "w1");
+    assert!(matches!(writer.hand(), Some(Hand::Left)));
+    assert_eq!(writer.age(), Some(30.0));
+    assert_eq!(
+        unipen.comments().iter().map(|comment| comment.trim()).collect::<Vec<_>>(),
+        ["note"]
+    );
+    assert_eq!(&*unipen.sets()[0].name, "first");
+    assert!(matches!(build(".HAND L\n"), Err(unipen::error::UniPenError::Validation(_))));
+}
+
+#[test]
+fn extent_covers_all_coordinates() {
+    let unipen = build(".PEN_DOWN\n0 -3 0\n10 20 1\n.PEN_UP\n-5 5 2\n").unwrap();
+    let extent = unipen.sets()[0].extent().unwrap();
+    assert_eq!(
+        (extent.x_min, extent.y_min, extent.x_max, extent.y_max),
+        (-5.0, -3.0, 10.0, 20.0)
+    );
+    assert_eq!(&*extent.coordinates, [0..=2]);
+    let empty = ComponentSet {
+        coordinates: Rc::new([]),
+        components: Rc::new([]),
+        ..unipen.sets()[0].clone()
+    };
+    assert!(empty.extent().is_none());
+}
+
+#[test]
+fn unit_conversion() {
+    let unipen = build(".X_POINTS_PER_MM 10\n.Y_POINTS_PER_INCH 254\n").unwrap();
+    assert_eq!(unipen.x_to_mm(50.0), Some(5.0));
+    assert!((unipen.y_to_mm(50.0).unwrap() - 5.0).abs() < 1e-9);
+    assert_eq!(unipen.z_to_mm(50.0), None);
+}
+
+#[test]
+fn clones_share_data_until_replaced() {
+    let unipen = build(".PEN_DOWN\n1 2 0\n3 4 10\n.PEN_UP\n").unwrap();
+    let copy = unipen.clone();
+    let original = &unipen.sets()[0];
+    let mut derived = copy.sets()[0].clone();
+    assert!(Rc::ptr_eq(&original.coordinates, &derived.coordinates));
+    derived.coordinates = derived
+        .coordinates
+        .iter()
+        .map(|coordinate| Coordinate {
+            x_position: coordinate.x_position * 10.0,
+            ..coordinate.clone()
+        })
+        .collect();
+    let extent = original.extent().unwrap();
+    assert_eq!((extent.x_min, extent.x_max), (1.0, 3.0));
+    let extent = derived.extent().unwrap();
+    assert_eq!((extent.x_min, extent.x_max), (10.0, 30.0));
+    assert!(Rc::ptr_eq(&original.components, &derived.components));
+}
+
+#[test]
+fn equality_is_exact() {
+    let statements = parse_text(&format!("{HEAD}.PEN_DOWN\n1 2 0\n3 4 10\n.PEN_UP\n"));
+    let first = UniPen::builder().statements(statements.clone()).unwrap().build().unwrap();
+    let second = UniPen::builder().statements(statements).unwrap().build().unwrap();
+    assert_eq!(first.sets()[0], second.sets()[0]);
+    assert!(first == second);
+    // Sets are named after their file, so the same data from another file differs
+    assert!(first != build(".PEN_DOWN\n1 2 0\n3 4 10\n.PEN_UP\n").unwrap());
+
+    let coordinate = first.sets()[0].coordinates[0].clone();
+    let nan = Coordinate {
+        x_position: f64::NAN,
+        ..coordinate.clone()
+    };
+    assert_ne!(nan, nan.clone());
+    let mut near = Coordinate {
+        x_position: coordinate.x_position + 1e-9,
+        ..coordinate.clone()
+    };
+    assert_ne!(coordinate, near);
+    assert!(coordinate.approx_eq(&near, 1e-6));
+    assert!(!coordinate.approx_eq(&near, 1e-12));
+    near.pressure = Some(1.0);
+    assert!(!coordinate.approx_eq(&near, 1e-6));
+}
+
+#[test]
+fn layout_keeps_fractional_values() {
+    let unipen = build(".X_DIM 123.5\n.Y_DIM 80\n.H_LINE 10 20.5\n.V_LINE 5\n").unwrap();
+    let layout = unipen.layout();
+    assert_eq!(layout.x_dimension(), Some(123.5));
+    assert_eq!(layout.y_dimension(), Some(80.0));
+    assert_eq!(layout.h_lines(), Some(&[10.0, 20.5][..]));
+    assert_eq!(layout.v_lines(), Some(&[5.0][..]));
+    let text = unipen.to_unipen_string();
+    assert!(
+        text.contains(".X_DIM 123.5\n.Y_DIM 80\n.H_LINE 10 20.5\n.V_LINE 5\n"),
+        "{text}"
+    );
+}
+
+#[test]
+fn conflicting_units() {
+    let build_units = |units: &str, strict: bool| {
+        UniPen::builder()
+            .strict(strict)
+            .statements(parse_text(&format!("{HEAD}{units}")))
+            .unwrap()
+            .build()
+    };
+    let unipen = build_units(".X_POINTS_PER_MM 10\n.X_POINTS_PER_INCH 254\n", true).unwrap();
+    assert_eq!(unipen.x_to_mm(20.0), Some(2.0));
+    assert!(build_units(".X_POINTS_PER_MM 10\n.X_POINTS_PER_INCH 254.1\n", true).is_ok());
+    let error = build_units(".Y_POINTS_PER_MM 10\n.Y_POINTS_PER_INCH 100\n", true)
+        .err()
+        .unwrap();
+    assert!(
+        matches!(&error, UniPenError::Validation(message)
+            if message.contains(".Y_POINTS_PER_MM 10 disagrees with .Y_POINTS_PER_INCH 100")),
+        "{error}"
+    );
+    // Lenient mode prefers points per millimeter
+    let unipen = build_units(".Y_POINTS_PER_MM 10\n.Y_POINTS_PER_INCH 100\n", false).unwrap();
+    assert_eq!(unipen.y_to_mm(20.0), Some(2.0));
+    let unipen = build_units(".X_POINTS_PER_INCH 254\n", true).unwrap();
+    assert_eq!(unipen.x_to_mm(20.0), Some(2.0));
+    assert!(build_units(".X_POINTS_PER_MM 100\n", true).is_ok());
+}
+
+#[test]
+fn alphabet_items_pair_frequencies() {
+    let unipen = build(".ALPHABET \"a\" \"b\" \"\\\"\"\n.ALPHABET_FREQ 3 4.5 1\n").unwrap();
+    let items: Vec<_> = unipen
+        .alphabet()
+        .unwrap()
+        .iter()
+        .map(|item| (item.character(), item.frequency()))
+        .collect();
+    assert_eq!(items, [('a', Some(3.0)), ('b', Some(4.5)), ('"', Some(1.0))]);
+    let text = unipen.to_unipen_string();
+    assert!(
+        text.contains(".ALPHABET \"a\" \"b\" \"\\\"\"\n.ALPHABET_FREQ 3 4.5 1\n"),
+        "{text}"
+    );
+
+    let error = build(".ALPHABET \"a\" \"b\"\n.ALPHABET_FREQ 3\n").err().unwrap();
+    assert!(
+        matches!(&error, UniPenError::Validation(message)
+            if message.contains(".ALPHABET has 2 entries, but .ALPHABET_FREQ has 1")),
+        "{error}"
+    );
+    let unipen = build(".ALPHABET \"a\" \"b\"\n").unwrap();
+    assert!(unipen.alphabet().unwrap().iter().all(|item| item.frequency().is_none()));
+    assert!(build(".ALPHABET \"ab\"\n").is_err());
+    assert!(build("").unwrap().alphabet().is_none());
+}
+
+#[test]
+fn lexicon_items_pair_frequencies() {
+    let unipen = build(".LEXICON_SOURCE words\n.LEXICON_ID lex1\n.LEXICON \"hello\" \"world\"\n.LEXICON_FREQ 10 2.5\n").unwrap();
+    let items: Vec<_> = unipen
+        .lexicon()
+        .unwrap()
+        .lexicon()
+        .unwrap()
+        .iter()
+        .map(|item| (item.label().to_owned(), item.frequency()))
+        .collect();
+    assert_eq!(items, [("hello".to_owned(), Some(10.0)), ("world".to_owned(), Some(2.5))]);
+    let text = unipen.to_unipen_string();
+    assert!(
+        text.contains(".LEXICON_SOURCE words\n.LEXICON_ID lex1\n.LEXICON \"hello\" \"world\"\n.LEXICON_FREQ 10 2.5\n"),
+        "{text}"
+    );
+
+    let error = build(".LEXICON \"hello\" \"world\"\n.LEXICON_FREQ 10\n").err().unwrap();
+    assert!(
+        matches!(&error, UniPenError::Validation(message) if message.contains(".LEXICON has 2 entries, but .LEXICON_FREQ has 1")),
+        "{error}"
+    );
+    assert!(build(".LEXICON_FREQ 10\n").is_err());
+}
+
+#[test]
+fn lexicon_is_exposed() {
+    let unipen = build(
+        ".LEXICON_SOURCE words\n.LEXICON_ID lex1\n.LEXICON_CONTACT me\n.LEXICON_INFO about\n.LEXICON \"hello\" \"world\"\n",
+    )
+    .unwrap();
+    let lexicon = unipen.lexicon().unwrap();
+    assert_eq!(lexicon.lexicon_source().map(str::trim), Some("words"));
+    assert_eq!(lexicon.lexicon_id(), Some("lex1"));
+    assert_eq!(lexicon.lexicon_contact().map(str::trim), Some("me"));
+    assert_eq!(lexicon.lexicon_info().map(str::trim), Some("about"));
+    let labels: Vec<_> = lexicon.lexicon().unwrap().iter().map(|item| item.label()).collect();
+    assert_eq!(labels, ["hello", "world"]);
+    assert!(lexicon.lexicon().unwrap().iter().all(|item| item.frequency().is_none()));
+    assert!(build("").unwrap().lexicon().is_none());
+}
+
+#[test]
+fn documentation_layout_units_and_writer() {
+    let unipen = build(
+        ".DATA_CONTACT me\n.DATA_INFO info\n.SETUP tablet\n.PAD wacom\n.X_DIM 100\n.H_LINE 10\n.Y_POINTS_PER_INCH 254\n\
+         .POINTS_PER_GRAM 3\n.WRITER_ID w1\n.COUNTRY NL\n.SEX F\n.SKILL GOOD\n.WRITER_INFO student\n",
+    )
+    .unwrap();
+    let documentation = unipen.data_documentation();
+    assert_eq!(documentation.data_contact().map(str::trim), Some("me"));
+    assert_eq!(documentation.data_info().map(str::trim), Some("info"));
+    assert_eq!(documentation.setup().map(str::trim), Some("tablet"));
+    assert_eq!(documentation.pad().map(str::trim), Some("wacom"));
+    assert_eq!(unipen.layout().x_dimension(), Some(100.0));
+    assert_eq!(unipen.layout().h_lines(), Some(&[10.0][..]));
+    assert_eq!(unipen.layout().v_lines(), None);
+    assert_eq!(unipen.units().y_points_per_inch(), Some(254.0));
+    assert_eq!(unipen.units().points_per_gram(), Some(3.0));
+    assert_eq!(unipen.units().x_points_per_inch(), None);
+    let writer = unipen.writer().unwrap();
+    assert_eq!(writer.writer_id(), "w1");
+    assert_eq!(writer.country(), Some("NL"));
+    assert!(matches!(writer.sex(), Some(Sex::Female)));
+    assert!(matches!(writer.skill(), Some(Skill::Good)));
+    assert_eq!(writer.writer_info().map(str::trim), Some("student"));
+
+    let unipen = build("").unwrap();
+    assert!(unipen.writer().is_none());
+    assert!(unipen.data_documentation().data_contact().is_none());
+    // A writer attribute without a .WRITER_ID
+    let error = build(".COUNTRY NL\n").err().unwrap();
+    assert!(
+        matches!(&error, UniPenError::Validation(message) if message.contains(".WRITER_ID")),
+        "{error}"
+    );
+}
+
+#[test]
+fn segments_at_hierarchy_level() {
+    let unipen = build(
+        ".PEN_DOWN\n1 2 0\n.PEN_UP\n.PEN_DOWN\n3 4 0\n.PEN_UP\n.SEGMENT WORD 0-1 OK \"ab\"\n.SEGMENT CHARACTER 0 OK \"a\"\n\
+         .SEGMENT CHARACTER 1 OK \"b\"\n.SEGMENT character 1 OK \"c\"\n",
+    )
+    .unwrap();
+    let set = &unipen.sets()[0];
+    let labels: Vec<_> = set.segments_at("CHARACTER").map(|segment| segment.label.as_deref()).collect();
+    assert_eq!(labels, [Some("a"), Some("b")]);
+    let labels: Vec<_> = set
+        .segments_at_ignore_ascii_case("Character")
+        .map(|segment| segment.label.as_deref())
+        .collect();
+    assert_eq!(labels, [Some("a"), Some("b"), Some("c")]);
+    assert_eq!(set.segments_at("WORD").count(), 1);
+    assert_eq!(set.segments_at("LINE").count(), 0);
+}
+
+#[test]
+fn segment_points_across_ranges() {
+    let unipen =
+        build(".PEN_DOWN\n1 2 0\n3 4 1\n.PEN_UP\n5 5 2\n.PEN_DOWN\n6 7 3\n.PEN_UP\n.SEGMENT WORD 0,2 OK \"ab\"\n").unwrap();
+    let set = &unipen.sets()[0];
+    let segment = &set.segments[0];
+    assert_eq!(&*segment.coordinates, [0..=1, 3..=3]);
+    let xs: Vec<f64> = set
+        .segment_points(segment)
+        .unwrap()
+        .map(|coordinate| coordinate.x_position)
+        .collect();
+    assert_eq!(xs, [1.0, 3.0, 6.0]);
+
+    let out_of_bounds = Segment {
+        coordinates: Rc::new([0..=1, 3..=9]),
+        ..segment.clone()
+    };
+    let error = set.segment_points(&out_of_bounds).err().unwrap();
+    assert!(
+        matches!(&error, UniPenError::Validation(message) if message.contains("3..=9 are out of bounds of 4 coordinates")),
+        "{error}"
+    );
+}
+
+#[test]
+fn duration_and_sampling_interval() {
+    let unipen = build(".PEN_DOWN\n0 0 10\n1 1 20\n.PEN_UP\n2 2 40\n").unwrap();
+    let set = &unipen.sets()[0];
+    assert_eq!(set.duration(), Some(Duration::from_millis(30)));
+    assert_eq!(set.mean_sampling_interval(), Some(Duration::from_millis(15)));
+
+    let unipen =
+        build(".DT 5\n.PEN_DOWN\n0 0 10\n1 1 20\n.PEN_UP\n2 2 30\n.DT 100\n.PEN_DOWN\n3 3 200\n4 4 210\n.DT 7\n").unwrap();
+    let set = &unipen.sets()[0];
+    // The leading and trailing .DT components add to the time between the first and last coordinate
+    assert_eq!(set.duration(), Some(Duration::from_millis(212)));
+    // The gap across the middle .DT component is not a sampling interval
+    assert_eq!(set.mean_sampling_interval(), Some(Duration::from_millis(10)));
+
+    let empty = ComponentSet {
+        coordinates: Rc::new([]),
+        components: Rc::new([]),
+        ..set.clone()
+    };
+    assert_eq!(empty.duration(), None);
+    assert_eq!(empty.mean_sampling_interval(), None);
+}
+
+#[test]
+fn stroke_statistics() {
+    let unipen =
+        build(".PEN_DOWN\n0 0 10\n3 4 20\n3 10 30\n.PEN_UP\n50 50 40\n.PEN_DOWN\n1 1 50\n.PEN_DOWN\n0 0 60\n1 0 70\n").unwrap();
+    let set = &unipen.sets()[0];
+    assert_eq!(set.stroke_count(), 3);
+    // Pen up movement is not ink, and a single point stroke has no length
+    assert_eq!(set.stroke_lengths(), [11.0, 0.0, 1.0]);
+    assert_eq!(set.total_ink_length(), 12.0);
+}
+
+#[test]
+fn velocity_and_acceleration_profiles() {
+    let unipen =
+        build(".PEN_DOWN\n0 0 0\n3 4 10\n6 8 20\n6 8 20\n9 12 30\n.DT 5\n.PEN_UP\n0 0 100\n0 10 110\n0 30 120\n").unwrap();
+    let set = &unipen.sets()[0];
+    // Uniform spacing and timing, with the repeated point skipped as no time passes
+    assert_eq!(set.velocity_profile(0).unwrap(), [500.0, 500.0, 500.0]);
+    assert_eq!(set.acceleration_profile(0).unwrap(), [0.0, 0.0]);
+    assert!(set.velocity_profile(1).is_none());
+    assert_eq!(set.velocity_profile(2).unwrap(), [1000.0, 2000.0]);
+    let acceleration = set.acceleration_profile(2).unwrap();
+    assert_eq!(acceleration.len(), 1);
+    assert!((acceleration[0] - 100_000.0).abs() < 1e-6);
+    assert!(set.velocity_profile(9).is_none());
+}
+
+#[test]
+fn all_coordinates_across_sets() {
+    let unipen = build(".PEN_DOWN\n0 0 0\n1 1 10\n.START_SET two\n.DT 5\n.PEN_DOWN\n2 2 20\n.PEN_UP\n3 3 30\n4 4 40\n").unwrap();
+    assert_eq!(unipen.sets().len(), 2);
+    let all: Vec<_> = unipen
+        .all_coordinates()
+        .map(|(set, coordinate)| (set, coordinate.x_position))
+        .collect();
+    assert_eq!(all, [(0, 0.0), (0, 1.0), (1, 2.0), (1, 3.0), (1, 4.0)]);
+}
+
+#[test]
+fn writer_attributes_ignore_case() {
+    for (hand, expected) in [
+        ("L", Some(Hand::Left)),
+        ("l", Some(Hand::Left)),
+        ("R", Some(Hand::Right)),
+        ("r", Some(Hand::Right)),
+        ("?", None),
+    ] {
+        let unipen = build(&format!(
+            ".WRITER_ID w\n.HAND {hand}\n.SEX f\n.SKILL Good\n.STYLE cursive\n.PEN_DOWN\n1 1 0\n"
+        ))
+        .unwrap();
+        let writer = unipen.writer().unwrap();
+        assert!(writer.hand().copied() == expected, "{hand}");
+        assert!(writer.sex().copied() == Some(Sex::Female));
+        assert!(writer.skill().copied() == Some(Skill::Good));
+        assert_eq!(unipen.sets()[0].style, Some(Style::Cursive));
+    }
+    for (keyword, value) in [(".HAND", "left"), (".SEX", "X"), (".SKILL", "great"), (".STYLE", "neat")] {
+        let error = build(&format!(".WRITER_ID w\n{keyword} {value}\n")).err().unwrap();
+        assert!(
+            matches!(&error, UniPenError::Validation(message)
+                if message.starts_with(&format!("Unknown {keyword} value \"{value}\""))),
+            "{error}"
+        );
+    }
+    // The source casing is kept in the statements
+    assert_eq!(parse_text(".HAND l\n")[1].to_string(), ".HAND l");
+}
+
+#[test]
+fn age_is_checked() {
+    let unipen = build(".WRITER_ID w\n.AGE 34\n").unwrap();
+    assert_eq!(unipen.writer().unwrap().age(), Some(34.0));
+    let error = build(".WRITER_ID w\n.AGE -3\n").err().unwrap();
+    assert!(
+        matches!(&error, UniPenError::Validation(message)
+            if message.starts_with(".AGE -3 is outside of the plausible range 0..=150")),
+        "{error}"
+    );
+    assert!(build(".WRITER_ID w\n.AGE 151\n").is_err());
+    let unipen = build(".WRITER_ID w\n.AGE ?\n").unwrap();
+    assert_eq!(unipen.writer().unwrap().age(), None);
+    let unipen = UniPen::builder()
+        .age_range(0..=200)
+        .statements(parse_text(&format!("{HEAD}.WRITER_ID w\n.AGE 180\n")))
+        .unwrap()
+        .build()
+        .unwrap();
+    assert_eq!(unipen.writer().unwrap().age(), Some(180.0));
+}
+
+#[test]
+fn repeated_free_text_is_joined() {
+    let unipen = build(".DATA_INFO first line\n.DATA_INFO second\n.SETUP tablet\n.DATA_INFO third\n").unwrap();
+    let documentation = unipen.data_documentation();
+    let lines: Option<Vec<_>> = documentation.data_info().map(|text| text.lines().map(str::trim).collect());
+    assert_eq!(lines, Some(vec!["first line", "second", "third"]));
+    assert_eq!(documentation.setup().map(str::trim), Some("tablet"));
+    let again = rebuild(&unipen);
+    assert_eq!(again.data_documentation().data_info(), documentation.data_info());
+}
+
+#[test]
+fn coordinate_constructor() {
+    let coordinate = Coordinate::new(1.0, 2.0, Duration::from_millis(3));
+    assert_eq!((coordinate.x_position, coordinate.y_position), (1.0, 2.0));
+    assert_eq!(coordinate.time, Duration::from_millis(3));
+    assert_eq!(
+        [
+            coordinate.pressure,
+            coordinate.z_position,
+            coordinate.button,
+            coordinate.rho,
+            coordinate.theta,
+            coordinate.phi
+        ],
+        [None; 6]
+    );
+    let coordinate = coordinate
+        .with_pressure(4.0)
+        .with_z_position(5.0)
+        .with_button(1.0)
+        .with_rho(6.0)
+        .with_theta(7.0)
+        .with_phi(8.0);
+    assert_eq!(
+        [
+            coordinate.pressure,
+            coordinate.z_position,
+            coordinate.button,
+            coordinate.rho,
+            coordinate.theta,
+            coordinate.phi
+        ],
+        [Some(4.0), Some(5.0), Some(1.0), Some(6.0), Some(7.0), Some(8.0)]
+    );
+    let unipen = build_with(&HEAD.replace(".COORD X Y T", ".COORD X Y T P"), ".PEN_DOWN\n1 2 3 4\n").unwrap();
+    assert_eq!(
+        unipen.sets()[0].coordinates[0],
+        Coordinate::new(1.0, 2.0, Duration::from_millis(3)).with_pressure(4.0)
+    );
+}
+
+#[test]
+fn sets_keep_their_writer_and_style() {
+    let unipen = build(
+        ".WRITER_ID a\n.START_SET one\n.STYLE PRINTED\n.PEN_DOWN\n0 0 0\n.STYLE cursive\n.WRITER_ID b\n.START_SET two\n\
+         .PEN_DOWN\n1 1 1\n.START_SET three\n.STYLE ?\n.PEN_DOWN\n2 2 2\n",
+    )
+    .unwrap();
+    let styles: Vec<_> = unipen.sets().iter().map(|set| set.style).collect();
+    assert!(matches!(
+        styles.as_slice(),
+        [Some(Style::Printed), Some(Style::Cursive), None]
+    ));
+    let writer_ids: Vec<_> = unipen.sets().iter().map(|set| set.writer_id.as_deref()).collect();
+    assert_eq!(writer_ids, [Some("a"), Some("b"), Some("b")]);
+    assert!(rebuild(&unipen) == unipen);
+}
+
+#[test]
+fn document_summary() {
+    // The second set declares coordinates without pressure
+    let head = ".VERSION 1.0\n.DATA_SOURCE src\n.DATA_ID id\n.COORD X Y T P\n.HIERARCHY WORD\n";
+    let unipen = build_with(
+        head,
+        ".WRITER_ID w1\n.PEN_DOWN\n0 0 0 5\n10 5 1 6\n.PEN_UP\n11 5 2 0\n.START_SET s2\n.COORD X Y T\n.WRITER_ID w2\n\
+         .PEN_DOWN\n-3 20 3\n.PEN_DOWN\n1 1 4\n",
+    )
+    .unwrap();
+    assert!(unipen.sets()[1]
+        .coordinates
+        .iter()
+        .all(|coordinate| coordinate.pressure.is_none()));
+    let summary = unipen.summary();
+    assert_eq!(summary.set_count, 2);
+    assert_eq!(summary.total_coordinates, 5);
+    assert_eq!(summary.total_strokes, 3);
+    assert!(summary.has_pressure);
+    // The document keeps the last declared coordinate order
+    assert_eq!(
+        summary.coordinate_order,
+        [CoordinateType::XPosition, CoordinateType::YPosition, CoordinateType::Time]
+    );
+    let writers: Vec<&str> = summary.writers.iter().map(|writer| &**writer).collect();
+    assert_eq!(writers, ["w1", "w2"]);
+    let bounding_box = summary.bounding_box.unwrap();
+    assert_eq!(
+        (bounding_box.x_min, bounding_box.y_min, bounding_box.x_max, bounding_box.y_max),
+        (-3.0, 0.0, 11.0, 20.0)
+    );
+    assert!(bounding_box.coordinates.is_empty());
+
+    let without_pressure = build(".PEN_DOWN\n0 0 0\n.START_SET s2\n.PEN_DOWN\n1 1 1\n")
+        .unwrap()
+        .summary();
+    assert_eq!(without_pressure.set_count, 2);
+    assert!(!without_pressure.has_pressure);
+    let empty = build("").unwrap().summary();
+    assert_eq!(empty.total_coordinates, 0);
+    assert!(empty.bounding_box.is_none());
+    assert!(empty.writers.is_empty());
+}
+
+#[test]
+fn debug_elides_long_lists() {
+    let statement = st(Keyword::PenDown, (0..10000).map(n).collect());
+    let short = format!("{statement:?}");
+    assert!(
+        short.contains("arguments: [Number(Integer(0)), Number(Integer(1)), … +9998 more]"),
+        "{short}"
+    );
+    let full = format!("{statement:#?}");
+    assert!(full.contains("9999") && !full.contains("more"));
+    let small = st(Keyword::PenDown, (0..8).map(n).collect());
+    assert!(format!("{small:?}").contains("Integer(7)"));
+
+    let points: String = (0..50).map(|index| format!("{index} {index} {index}\n")).collect();
+    let unipen = build(&format!(".PEN_DOWN\n{points}")).unwrap();
+    let short = format!("{:?}", unipen.sets()[0]);
+    assert!(short.contains("… +48 more]"), "{short}");
+    assert!(!format!("{:#?}", unipen.sets()[0]).contains("more"));
+}
@@ -0,0 +1,325 @@
+mod common;
+
+use std::ops::RangeInclusive;
+use std::path::Path;
+use std::rc::Rc;
+use std::time::Duration;
+
+use common::{build, build_with, HEAD};
+use unipen::error::UniPenError;
+use unipen::fill::FillPolicy;
+use unipen::hierarchy::SegmentNode;
+use unipen::model::{Component, ComponentSet, Coordinate, CoordinateType, UniPen};
+use unipen::statements;
+use unipen::svg::SvgOptions;
+use unipen::timestamps::TimePolicy;
+
+#[test]
+fn svg_paths_per_stroke() {
+    let unipen = build(".PEN_DOWN\n0 0 0\n10 20 1\n.PEN_UP\n5 5 2\n.PEN_DOWN\n3 4 3\n").unwrap();
+    let svg = unipen.sets()[0].to_svg(&SvgOptions::new().padding(1.0));
+    assert_eq!(svg.matches("<path").count(), 2);
+    assert!(svg.contains("viewBox=\"-1 -1 12 22\""), "{svg}");
+    // The Y axis is flipped by default
+    assert!(svg.contains("M0 20 L10 0"), "{svg}");
+    let unflipped = unipen.sets()[0].to_svg(&SvgOptions::new().padding(1.0).flip_y(false));
+    assert!(unflipped.contains("M0 0 L10 20"), "{unflipped}");
+}
+
+#[test]
+fn csv_rows_per_coordinate() {
+    let head = ".VERSION 1.0\n.DATA_SOURCE src\n.DATA_ID id\n.COORD X Y T P\n.HIERARCHY WORD\n";
+    let unipen = build_with(head, ".PEN_DOWN\n0 0 0 5\n10 20 1 6\n.DT 3\n.PEN_UP\n5 5 2 7\n").unwrap();
+    let mut csv = Vec::new();
+    unipen.sets()[0].to_csv(&mut csv).unwrap();
+    let csv = String::from_utf8(csv).unwrap();
+    let lines: Vec<_> = csv.lines().collect();
+    assert_eq!(
+        lines,
+        [
+            "component_index,point_index,x,y,time_ns,pressure,z,button,rho,theta,phi",
+            "0,0,0,0,0,5,,,,,",
+            "0,1,10,20,1000000,6,,,,,",
+            "2,0,5,5,2000000,7,,,,,",
+        ]
+    );
+}
+
+#[test]
+fn resample_spatial_steps_along_strokes() {
+    let unipen = build(".PEN_DOWN\n0 0 0\n3 4 10\n6 8 20\n.PEN_UP\n9 9 30\n.PEN_DOWN\n1 1 40\n.SEGMENT WORD 0:1-1 OK\n").unwrap();
+    let resampled = unipen.sets()[0].resample_spatial(1.0);
+    assert_eq!(resampled.points_for_component(0).unwrap().len(), 11);
+    let point = &resampled.points_for_component(0).unwrap()[7];
+    assert!((point.x_position - 4.2).abs() < 1e-9 && (point.y_position - 5.6).abs() < 1e-9);
+    assert_eq!(point.time.as_millis(), 14);
+    assert_eq!(resampled.points_for_component(1).unwrap().len(), 1);
+    assert_eq!(resampled.points_for_component(2).unwrap().len(), 1);
+    // The segment referred to a single point, so it is widened to the whole stroke
+    assert_eq!(&*resampled.segments[0].coordinates, &[0..=11]);
+    assert_eq!(
+        unipen.sets()[0].resample_spatial(3.0).points_for_component(0).unwrap().len(),
+        4
+    );
+}
+
+#[test]
+fn resample_keeps_empty_components() {
+    let unipen = build(".PEN_DOWN\n0 0 0\n2 0 10\n.PEN_UP\n").unwrap();
+    let resampled = unipen.sets()[0].resample_spatial(1.0).resample_spatial(1.0);
+    assert_eq!(resampled.components.len(), 2);
+    assert_eq!(resampled.points_for_component(0).unwrap().len(), 3);
+    assert_eq!(resampled.points_for_component(1).unwrap().len(), 0);
+}
+
+#[test]
+fn resample_temporal_steps_in_time() {
+    let head = ".VERSION 1.0\n.DATA_SOURCE src\n.DATA_ID id\n.COORD X Y T P\n.HIERARCHY WORD\n";
+    let unipen = build_with(head, ".PEN_DOWN\n0 0 0 0\n10 0 10 100\n30 0 20 200\n").unwrap();
+    let resampled = unipen.sets()[0].resample_temporal(Duration::from_millis(5)).unwrap();
+    let xs: Vec<f64> = resampled.coordinates_iter().map(|coordinate| coordinate.x_position).collect();
+    assert_eq!(xs, [0.0, 5.0, 10.0, 20.0, 30.0]);
+    assert_eq!(resampled.coordinates[1].pressure, Some(50.0));
+    assert_eq!(resampled.coordinates[3].time, Duration::from_millis(15));
+
+    let unipen = build(".PEN_DOWN\n0 0 0\n10 0 10\n30 0 5\n").unwrap();
+    let error = unipen.sets()[0].resample_temporal(Duration::from_millis(5)).err().unwrap();
+    assert!(
+        matches!(&error, UniPenError::Validation(message) if message.contains("coordinate 2")),
+        "{error}"
+    );
+    let error = unipen.sets()[0].resample_temporal(Duration::ZERO).err().unwrap();
+    assert!(matches!(error, UniPenError::Validation(_)));
+}
+
+#[test]
+fn flat_f32_rows_per_point() {
+    let unipen = build_with(
+        &HEAD.replace(".COORD X Y T", ".COORD X Y T P"),
+        ".PEN_DOWN\n1 2 0 7\n3 4 10 8\n5 6 20 9\n",
+    )
+    .unwrap();
+    let set = &unipen.sets()[0];
+    let channels = [CoordinateType::XPosition, CoordinateType::YPosition, CoordinateType::Pressure];
+    assert_eq!(set.shape(&channels), (3, 3));
+    assert_eq!(
+        set.to_flat_f32(&channels, -1.0),
+        [1.0, 2.0, 7.0, 3.0, 4.0, 8.0, 5.0, 6.0, 9.0]
+    );
+    assert_eq!(set.to_flat_f32(&[CoordinateType::Time], 0.0), [0.0, 10.0, 20.0]);
+
+    // Channels the coordinates do not have take the missing value
+    let unipen = build(".PEN_DOWN\n1 2 0\n3 4 10\n5 6 20\n").unwrap();
+    let set = &unipen.sets()[0];
+    assert_eq!(
+        set.to_flat_f32(&channels, -1.0),
+        [1.0, 2.0, -1.0, 3.0, 4.0, -1.0, 5.0, 6.0, -1.0]
+    );
+}
+
+#[test]
+fn smoothing_within_strokes() {
+    let unipen = build(".PEN_DOWN\n0 0 0\n10 4 1\n2 2 2\n12 0 3\n4 8 4\n.PEN_UP\n100 100 5\n.PEN_DOWN\n7 7 6\n").unwrap();
+    let set = &unipen.sets()[0];
+    assert!(set.smooth(1) == *set);
+    let smoothed = set.smooth(3);
+    let xs: Vec<f64> = smoothed.coordinates.iter().map(|coordinate| coordinate.x_position).collect();
+    // The ends of a stroke average over the points there are, and pen up points are left as they are
+    assert_eq!(xs, [5.0, 4.0, 8.0, 6.0, 8.0, 100.0, 7.0]);
+    assert_eq!(smoothed.coordinates[2].y_position, 2.0);
+    // Even windows are rounded up
+    assert!(set.smooth(2) == smoothed);
+    assert_eq!(set.smooth(5).coordinates[2].x_position, 5.6);
+    assert!(smoothed.components == set.components);
+}
+
+#[test]
+fn fix_timestamps_policies() {
+    let unipen = build(".PEN_DOWN\n0 0 0\n1 1 10\n2 2 5\n3 3 20\n.PEN_UP\n4 4 15\n").unwrap();
+    let set = &unipen.sets()[0];
+    assert!(!set.has_monotonic_time());
+    let millis =
+        |set: &ComponentSet| -> Vec<u128> { set.coordinates.iter().map(|coordinate| coordinate.time.as_millis()).collect() };
+    let clamped = set.fix_timestamps(TimePolicy::Clamp);
+    assert!(clamped.has_monotonic_time());
+    assert_eq!(millis(&clamped), [0, 10, 10, 20, 20]);
+    let reinterpolated = set.fix_timestamps(TimePolicy::Reinterpolate {
+        points_per_second: 100.0,
+    });
+    assert!(reinterpolated.has_monotonic_time());
+    assert_eq!(millis(&reinterpolated), [0, 10, 20, 30, 30]);
+
+    let unipen = build(".PEN_DOWN\n0 0 0\n1 1 10\n").unwrap();
+    let set = &unipen.sets()[0];
+    assert!(set.has_monotonic_time());
+    assert!(set.fix_timestamps(TimePolicy::Reinterpolate { points_per_second: 1.0 }) == *set);
+
+    // The range of an empty component may lie past the end of the coordinates
+    let unipen = build(".PEN_DOWN\n0 0 10\n1 1 5\n").unwrap();
+    let set = ComponentSet {
+        components: Rc::new([Component::PenDown(0..=1), Component::PenUp(RangeInclusive::new(3, 2))]),
+        ..unipen.sets()[0].clone()
+    };
+    assert_eq!(millis(&set.fix_timestamps(TimePolicy::Clamp)), [10, 10]);
+}
+
+#[test]
+fn hierarchy_tree_nests_by_containment() {
+    let unipen = build_with(
+        &HEAD.replace(".HIERARCHY WORD", ".HIERARCHY WORD CHARACTER"),
+        ".PEN_DOWN\n0 0 0\n1 1 1\n.PEN_DOWN\n2 2 2\n.PEN_DOWN\n3 3 3\n.SEGMENT CHARACTER 0 \"a\"\n.SEGMENT WORD 0-1 \"ab\"\n\
+         .SEGMENT CHARACTER 1 \"b\"\n.SEGMENT CHARACTER 2 \"c\"\n.SEGMENT LINE 0-2 \"abc\"\n",
+    )
+    .unwrap();
+    let tree = unipen.sets()[0].hierarchy_tree(unipen.hierarchy_order());
+    let label = |node: &SegmentNode| node.segment.label.as_deref().map(str::to_owned);
+    // The character outside of any word is a root, and the undeclared LINE level is left out
+    assert_eq!(
+        tree.iter().map(label).collect::<Vec<_>>(),
+        [Some("ab".into()), Some("c".into())]
+    );
+    let children: Vec<_> = tree[0].children.iter().map(label).collect();
+    assert_eq!(children, [Some("a".into()), Some("b".into())]);
+    assert!(tree[0].children.iter().all(|child| child.children.is_empty()));
+    assert!(tree[1].children.is_empty());
+}
+
+#[test]
+fn merge_moves_the_other_components() {
+    let first = build(".PEN_DOWN\n0 0 0\n.PEN_UP\n1 1 1\n").unwrap();
+    let second = build_with(
+        ".VERSION 1.0\n.DATA_SOURCE other\n.DATA_ID id\n.COORD X Y T\n.HIERARCHY WORD\n.WRITER_ID w\n",
+        ".TRAINING_SET S T WORD 0\n.TEST_SET S T WORD 0-1\n.ADAPT_SET S T WORD 1\n.LEXICON_SET L I 0-4\n\
+         .PEN_DOWN\n5 5 5\n.PEN_UP\n6 6 6\n.REC_LABELS WORD 0 ACCEPT \"x\"\n.REC_SCORES WORD 1 -0.5 \"y\"\n",
+    )
+    .unwrap();
+    let merged = first.clone().merge(second).unwrap();
+    assert_eq!(merged.sets().len(), 2);
+    assert_eq!(merged.data_source().trim(), "src");
+    assert_eq!(merged.writer().unwrap().writer_id(), "w");
+    assert_eq!(merged.rec_labels()[0].segment_ref().components(), [2..=2]);
+    assert_eq!(merged.rec_scores()[0].segment_ref().components(), [3..=3]);
+    let membership = merged.sets_membership();
+    assert_eq!(membership.training_sets()[0].components(), [2..=2]);
+    assert_eq!(membership.test_sets()[0].components(), [2..=3]);
+    assert_eq!(membership.adapt_sets()[0].components(), [3..=3]);
+    // Lexicon sets count lexicon entries rather than components
+    assert_eq!(membership.lexicon_sets()[0].components(), [0..=4]);
+
+    let other_order = build_with(
+        ".VERSION 1.0\n.DATA_SOURCE src\n.DATA_ID id\n.COORD X Y\n.HIERARCHY WORD\n.POINTS_PER_SECOND 100\n",
+        ".PEN_DOWN\n5 5\n",
+    )
+    .unwrap();
+    assert!(matches!(
+        first.merge(other_order).err(),
+        Some(UniPenError::Validation(message)) if message == "Cannot merge documents with coordinate orders X Y T and X Y"
+    ));
+}
+
+#[test]
+fn fill_pressure_within_strokes() {
+    let mut coordinates: Vec<Coordinate> = (0..7_u32)
+        .map(|index| Coordinate::new(f64::from(index), 0.0, Duration::from_millis(index.into())))
+        .collect();
+    coordinates[1].pressure = Some(2.0);
+    coordinates[4].pressure = Some(8.0);
+    coordinates[5].pressure = Some(10.0);
+    coordinates.extend((0..2).map(|index| Coordinate::new(f64::from(index), 0.0, Duration::ZERO)));
+    let set = ComponentSet {
+        name: "set".into(),
+        coordinates: coordinates.into(),
+        components: vec![
+            Component::PenDown(0..=6),
+            Component::PenDown(7..=8),
+            // An empty component after the last coordinate
+            Component::PenDown(RangeInclusive::new(10, 9)),
+        ]
+        .into(),
+        segments: Vec::new().into(),
+        bounding_boxes: Vec::new().into(),
+        writer_id: None,
+        style: None,
+    };
+    let pressures = |set: &ComponentSet| {
+        set.coordinates
+            .iter()
+            .map(|coordinate| coordinate.pressure)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        pressures(&set.fill_pressure(FillPolicy::LinearInterpolate)),
+        [
+            Some(2.0),
+            Some(2.0),
+            Some(4.0),
+            Some(6.0),
+            Some(8.0),
+            Some(10.0),
+            Some(10.0),
+            None,
+            None
+        ]
+    );
+    assert_eq!(
+        pressures(&set.fill_pressure(FillPolicy::Constant(1.0))),
+        [
+            Some(1.0),
+            Some(2.0),
+            Some(1.0),
+            Some(1.0),
+            Some(8.0),
+            Some(10.0),
+            Some(1.0),
+            None,
+            None
+        ]
+    );
+}
+
+#[test]
+fn split_by_writer_renumbers_components() {
+    let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/two_writers.txt");
+    let unipen = UniPen::builder()
+        .statements(statements::parse(&fixture, None).unwrap())
+        .unwrap()
+        .build()
+        .unwrap();
+    let writer_ids: Vec<_> = unipen.sets().iter().map(|set| set.writer_id.as_deref()).collect();
+    assert_eq!(writer_ids, [Some("a"), Some("a"), Some("b"), Some("b")]);
+
+    let documents = unipen.split_by_writer();
+    assert_eq!(documents.len(), 2);
+    let [first, second] = documents.as_slice() else {
+        unreachable!()
+    };
+    assert_eq!(first.writer().unwrap().writer_id(), "a");
+    assert_eq!(first.writer().unwrap().age(), None);
+    assert_eq!(second.writer().unwrap().writer_id(), "b");
+    assert_eq!(second.writer().unwrap().age(), Some(30.0));
+    let names: Vec<&str> = first.sets().iter().map(|set| &*set.name).collect();
+    assert_eq!(names, ["one", "two"]);
+    let names: Vec<&str> = second.sets().iter().map(|set| &*set.name).collect();
+    assert_eq!(names, ["three", "four"]);
+
+    // Results and sets that span both writers are dropped
+    let labels = |document: &UniPen| {
+        document
+            .rec_labels()
+            .iter()
+            .map(|rec_label| (rec_label.label().to_string(), rec_label.segment_ref().components().to_vec()))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(labels(first), [("y".to_string(), vec![1..=2])]);
+    assert_eq!(labels(second), [("x".to_string(), vec![0..=0])]);
+    let membership = first.sets_membership();
+    assert_eq!(membership.training_sets()[0].components(), [1..=2]);
+    assert!(membership.test_sets().is_empty());
+    assert!(membership.adapt_sets().is_empty());
+    assert_eq!(membership.lexicon_sets()[0].components(), [0..=9]);
+    let membership = second.sets_membership();
+    assert!(membership.training_sets().is_empty());
+    assert_eq!(membership.test_sets()[0].components(), [0..=1]);
+    assert!(membership.adapt_sets().is_empty());
+    assert_eq!(membership.lexicon_sets()[0].components(), [0..=9]);
+}
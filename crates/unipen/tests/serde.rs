@@ -0,0 +1,49 @@
+#![cfg(feature = "serde")]
+
+mod common;
+
+use common::{build, FailingWriter};
+use unipen::error::UniPenError;
+use unipen::model::{ComponentSet, UniPen};
+use unipen::statements;
+
+#[test]
+fn model_round_trips_through_json() {
+    let unipen = build(".DATE 10 12 1993\n.PEN_DOWN 1 2 30 1.5 2 41\n.DT 7\n.SEGMENT WORD 0 OK \"a\"\n.REC_TIME 0 12\n").unwrap();
+    let json = serde_json::to_string(&unipen.sets()[0]).unwrap();
+    let set: ComponentSet = serde_json::from_str(&json).unwrap();
+    assert!(set == unipen.sets()[0]);
+    assert_eq!(serde_json::to_string(&set).unwrap(), json);
+    let json = serde_json::to_string(&unipen).unwrap();
+    let back: UniPen = serde_json::from_str(&json).unwrap();
+    assert!(back == unipen);
+    assert_eq!(back.recognizer_time(), unipen.recognizer_time());
+}
+
+#[test]
+fn write_json_to_buffer() {
+    let parsed = statements::parse_str("memory", ".VERSION 1.0\n.DATA_ID x\n", None).unwrap();
+    let mut compact = Vec::new();
+    statements::write_json(&parsed, &mut compact, false).unwrap();
+    let value: serde_json::Value = serde_json::from_slice(&compact).unwrap();
+    assert_eq!(value.as_array().unwrap().len(), 4);
+    assert!(!compact.contains(&b'\n'));
+    let mut pretty = Vec::new();
+    statements::write_json(&parsed, &mut pretty, true).unwrap();
+    assert_eq!(serde_json::from_slice::<serde_json::Value>(&pretty).unwrap(), value);
+    assert!(pretty.contains(&b'\n'));
+    let error = statements::write_json(&parsed, FailingWriter, false).unwrap_err();
+    assert!(
+        matches!(&error, UniPenError::Serialize(message) if message.contains("write failed")),
+        "{error}"
+    );
+}
+
+#[test]
+fn serialization_errors_convert() {
+    let json_error = serde_json::from_str::<ComponentSet>("[").unwrap_err();
+    let message = json_error.to_string();
+    let error = UniPenError::from(json_error);
+    assert!(matches!(&error, UniPenError::Serialize(text) if *text == message));
+    assert_eq!(error.to_string(), format!("Serialization error: {message}"));
+}
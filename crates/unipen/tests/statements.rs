@@ -0,0 +1,898 @@
+mod common;
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Mutex;
+
+use pest::Parser;
+
+use common::{parse_text, temp_dir, temp_file, FailingWriter, HEAD};
+use unipen::error::UniPenError;
+use unipen::model::UniPen;
+use unipen::statements::{
+    self, unescape_label, Encoding, IncludeGlobs, IncludeResolver, Keyword, Number, ParseOptions, ParseStats, Reserved, Rule,
+    Statement, StatementArgument, StatementParser,
+};
+use unipen::writer::escape_label;
+
+struct MemoryResolver;
+
+impl IncludeResolver for MemoryResolver {
+    fn resolve(&self, path: &str) -> Result<String, UniPenError> {
+        match path {
+            "inc.txt" => Ok(".DATA_ID inc\n".into()),
+            _ => Err(UniPenError::MissingInclude),
+        }
+    }
+}
+
+fn to_text(statements: &[Statement]) -> String {
+    statements.iter().map(|statement| format!("{statement}\n")).collect()
+}
+
+#[test]
+fn statements_display_as_source_text() {
+    let source = ".VERSION 1.0\n.DATA_SOURCE some \"src\"\n.DATA_ID id\n.COMMENT hello\n  there\n.COORD X Y T\n.HIERARCHY WORD\n\
+                  .SEX F\n.DT 2.5\n.PEN_DOWN\n1 2 3\n4 5 6\n.PEN_UP\n1 2 3\n.SEGMENT WORD 0:1-1 OK \"a\\\"b\"\n";
+    let parsed = parse_text(source);
+    let text = to_text(&parsed);
+    assert!(text.starts_with(".COMMENT .INCLUDE "));
+    assert!(text.contains("\n.VERSION 1.0\n.DATA_SOURCE some \"src\"\n"), "{text}");
+    assert!(text.contains("\n.SEGMENT WORD 0:1-1 OK \"a\\\"b\"\n"), "{text}");
+    // The synthetic include of the first parse is parsed as a comment
+    let again = parse_text(&text);
+    assert_eq!(again.len(), parsed.len() + 1);
+    assert_eq!(to_text(&again[1..]), text);
+}
+
+#[test]
+fn arguments_display_as_source_text() {
+    assert_eq!(StatementArgument::Number(Number::Decimal(3.0)).to_string(), "3.0");
+    assert_eq!(StatementArgument::Number(Number::Integer(-3)).to_string(), "-3");
+    assert_eq!(StatementArgument::Reserved(Reserved::Unknown).to_string(), "?");
+    assert_eq!(StatementArgument::FreeText("x\n.y".into()).to_string(), "x\n .y");
+    assert_eq!(StatementArgument::Label("a \"b\"".into()).to_string(), "\"a \\\"b\\\"\"");
+}
+
+#[test]
+fn parse_str_with_resolver() {
+    let parsed = statements::parse_str(
+        "memory",
+        ".VERSION 1.0\n.INCLUDE inc.txt\n.COORD X Y\n",
+        Some(&MemoryResolver),
+    )
+    .unwrap();
+    let text: Vec<_> = parsed.iter().map(ToString::to_string).collect();
+    assert_eq!(
+        text,
+        [
+            ".COMMENT .INCLUDE memory",
+            ".VERSION 1.0",
+            ".COMMENT .INCLUDE inc.txt",
+            ".DATA_ID inc",
+            "",
+            ".COORD X Y",
+            ""
+        ]
+    );
+    assert!(parsed[0].synthetic && parsed[2].synthetic && !parsed[3].synthetic);
+    assert!(matches!(
+        statements::parse_str("memory", ".INCLUDE inc.txt\n", None),
+        Err(UniPenError::MissingInclude)
+    ));
+    assert!(matches!(
+        statements::parse_str("memory", ".INCLUDE other.txt\n", Some(&MemoryResolver)),
+        Err(UniPenError::MissingInclude)
+    ));
+}
+
+#[test]
+fn parse_options() {
+    let dir = temp_dir();
+    std::fs::write(dir.join("top"), ".INCLUDE mid\n").unwrap();
+    std::fs::write(dir.join("mid"), ".INCLUDE leaf\n").unwrap();
+    std::fs::write(dir.join("leaf"), ".DT 99999999999\n").unwrap();
+    let top = dir.join("top");
+    let options = ParseOptions::new().include_dir(&dir);
+    let parsed = statements::parse_with_options(&top, &options).unwrap();
+    assert_eq!(parsed.len(), 7);
+    // Outside of strict mode, a number that does not fit is dropped
+    assert!(parsed
+        .iter()
+        .any(|statement| matches!(statement.keyword, Keyword::Dt) && statement.arguments.is_empty()));
+    assert!(statements::parse_with_options(&top, &options.clone().max_depth(2)).is_ok());
+    let error = statements::parse_with_options(&top, &options.clone().max_depth(1)).unwrap_err();
+    assert!(matches!(error, UniPenError::IncludeDepth(1)));
+    let error = statements::parse_with_options(&top, &options.clone().strict(true)).unwrap_err();
+    assert!(matches!(error, UniPenError::ParseInt(_)));
+    let error = statements::parse_with_options(&top, &ParseOptions::new().include_dir_only(true)).unwrap_err();
+    assert!(matches!(error, UniPenError::MissingInclude));
+}
+
+#[test]
+fn latin1_encoding() {
+    let file = temp_file(b".WRITER_INFO Jos\xE9 M\xFCller\n");
+    let error = statements::parse_with_options(&file, &ParseOptions::new()).unwrap_err();
+    assert!(error.to_string().contains("offset 16"), "{error}");
+    let parsed = statements::parse_with_options(&file, &ParseOptions::new().encoding(Encoding::Latin1)).unwrap();
+    assert_eq!(parsed[1].to_string(), ".WRITER_INFO José Müller");
+}
+
+#[test]
+fn parse_iter_matches_parse() {
+    let dir = temp_dir();
+    std::fs::write(
+        dir.join("top"),
+        ".VERSION 1.0\n.COMMENT multi\n line\n.INCLUDE mid\n.DATA_ID x\n.SEGMENT W 0:1-1 OK \"l\"\n",
+    )
+    .unwrap();
+    std::fs::write(dir.join("mid"), ".INCLUDE leaf\n.COORD X Y\n").unwrap();
+    std::fs::write(dir.join("leaf"), ".PEN_DOWN\n1 2\n3 4\n").unwrap();
+    let top = dir.join("top");
+    let eager = statements::parse(&top, Some(&dir)).unwrap();
+    let lazy = statements::parse_iter(&top, Some(&dir))
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(format!("{lazy:?}"), format!("{eager:?}"));
+
+    // The iterator ends after the first error
+    let mut iter = statements::parse_iter_with_options(&top, &ParseOptions::new().include_dir_only(true));
+    assert!(iter.next().unwrap().is_ok());
+    assert!(matches!(
+        iter.by_ref().find_map(Result::err),
+        Some(UniPenError::MissingInclude)
+    ));
+    assert!(iter.next().is_none());
+}
+
+#[test]
+fn strict_rejects_duplicates() {
+    let file = temp_file(".VERSION 1.0\n.COMMENT x\n.VERSION 2.0\n");
+    let parsed = statements::parse_with_options(&file, &ParseOptions::new()).unwrap();
+    assert_eq!(parsed.len(), 5);
+    let error = statements::parse_with_options(&file, &ParseOptions::new().strict(true)).unwrap_err();
+    assert!(
+        matches!(&error, UniPenError::Validation(message) if message == "Duplicate .VERSION statement (line 3, column 1)"),
+        "{error}"
+    );
+    let lazy: Vec<_> = statements::parse_iter_with_options(&file, &ParseOptions::new().strict(true)).collect();
+    assert_eq!(lazy.len(), 4);
+    assert!(matches!(lazy[3], Err(UniPenError::Validation(_))));
+}
+
+struct WarningCapture;
+
+static WARNINGS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+impl log::Log for WarningCapture {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= log::Level::Warn
+    }
+
+    fn log(&self, record: &log::Record) {
+        if self.enabled(record.metadata()) {
+            WARNINGS.lock().unwrap().push(record.args().to_string());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+#[test]
+fn lenient_warns_of_dropped_arguments() {
+    log::set_logger(&WarningCapture).unwrap();
+    log::set_max_level(log::LevelFilter::Warn);
+    let parsed = parse_text(".VERSION 1.0\n.DT 99999999999\n");
+    assert!(matches!(parsed[2].keyword, Keyword::Dt) && parsed[2].arguments.is_empty());
+    let warnings = WARNINGS.lock().unwrap();
+    assert!(
+        warnings
+            .iter()
+            .any(|warning| warning.contains("\"99999999999\" of .DT statement at line 2, column 1")),
+        "{warnings:?}"
+    );
+}
+
+#[test]
+fn keywords_round_trip_through_their_names() {
+    let keywords = [
+        Keyword::Keyword,
+        Keyword::Reserve,
+        Keyword::Comment,
+        Keyword::Include,
+        Keyword::Version,
+        Keyword::DataSource,
+        Keyword::DataId,
+        Keyword::Coordinate,
+        Keyword::Hierarchy,
+        Keyword::DataContact,
+        Keyword::DataInfo,
+        Keyword::Setup,
+        Keyword::Pad,
+        Keyword::Alphabet,
+        Keyword::AlphabetFreq,
+        Keyword::LexiconSource,
+        Keyword::LexiconId,
+        Keyword::LexiconContact,
+        Keyword::LexiconInfo,
+        Keyword::Lexicon,
+        Keyword::LexiconFreq,
+        Keyword::XDimension,
+        Keyword::YDimension,
+        Keyword::HLine,
+        Keyword::VLine,
+        Keyword::XPointsPerInch,
+        Keyword::YPointsPerInch,
+        Keyword::ZPointsPerInch,
+        Keyword::XPointsPerMm,
+        Keyword::YPointsPerMm,
+        Keyword::ZPointsPerMm,
+        Keyword::PointsPerGram,
+        Keyword::PointsPerSecond,
+        Keyword::PenDown,
+        Keyword::PenUp,
+        Keyword::Dt,
+        Keyword::Date,
+        Keyword::Style,
+        Keyword::WriterId,
+        Keyword::Country,
+        Keyword::Hand,
+        Keyword::Age,
+        Keyword::Sex,
+        Keyword::Skill,
+        Keyword::WriterInfo,
+        Keyword::Segment,
+        Keyword::StartSet,
+        Keyword::StartBox,
+        Keyword::RecSource,
+        Keyword::RecId,
+        Keyword::RecContact,
+        Keyword::RecInfo,
+        Keyword::Implement,
+        Keyword::TrainingSet,
+        Keyword::TestSet,
+        Keyword::AdaptSet,
+        Keyword::LexiconSet,
+        Keyword::RecTime,
+        Keyword::RecLabels,
+        Keyword::RecScores,
+        Keyword::EndOfInput,
+    ];
+    for keyword in keywords {
+        let parsed: Keyword = keyword.to_string().parse().unwrap();
+        assert_eq!(format!("{parsed:?}"), format!("{keyword:?}"));
+    }
+    assert_eq!(Keyword::PenDown.to_string(), ".PEN_DOWN");
+    assert_eq!(Keyword::XPointsPerInch.to_string(), ".X_POINTS_PER_INCH");
+    assert!(matches!(".NOPE".parse::<Keyword>(), Err(UniPenError::Translation(_))));
+}
+
+#[test]
+fn numbers_convert_to_indices() {
+    assert!(matches!(
+        usize::try_from(&Number::Integer(-1)),
+        Err(UniPenError::Validation(_))
+    ));
+    assert!(matches!(
+        usize::try_from(&Number::Decimal(1.5)),
+        Err(UniPenError::Validation(_))
+    ));
+    assert!(matches!(
+        usize::try_from(&Number::Decimal(-2.0)),
+        Err(UniPenError::Validation(_))
+    ));
+    assert_eq!(usize::try_from(&Number::Integer(7)).unwrap(), 7);
+    assert_eq!(usize::try_from(&Number::Decimal(3.0)).unwrap(), 3);
+
+    let parsed = parse_text(".SEGMENT WORD 0:2-1.0:3 ? \"a\"\n");
+    assert_eq!(parsed[1].to_string(), ".SEGMENT WORD 0:2-1:3 ? \"a\"");
+    let file = temp_file(".SEGMENT WORD -1:2 ? \"a\"\n");
+    let error = statements::parse_with_options(&file, &ParseOptions::new().strict(true)).unwrap_err();
+    assert!(
+        matches!(&error, UniPenError::Validation(message) if message.contains("non-negative")),
+        "{error}"
+    );
+}
+
+#[test]
+fn statements_clone() {
+    let parsed = parse_text(".VERSION 1.0\n.COMMENT some text\n");
+    let copy = parsed.clone();
+    assert_eq!(to_text(&copy), to_text(&parsed));
+    let (StatementArgument::FreeText(original), StatementArgument::FreeText(cloned)) =
+        (&parsed[2].arguments[0], &copy[2].arguments[0])
+    else {
+        panic!("{parsed:?}");
+    };
+    assert!(std::rc::Rc::ptr_eq(original, cloned));
+}
+
+#[test]
+fn synthetic_includes_can_be_left_out() {
+    let dir = temp_dir();
+    std::fs::write(dir.join("top"), ".VERSION 1.0\n.INCLUDE leaf\n.DATA_ID x\n").unwrap();
+    std::fs::write(dir.join("leaf"), ".DT 5\n").unwrap();
+    let top = dir.join("top");
+    let with = ParseOptions::new().include_dir(&dir);
+    let without = with.clone().synthetic_includes(false);
+    let synthetic = statements::parse_with_options(&top, &with).unwrap();
+    let source = statements::parse_with_options(&top, &without).unwrap();
+    assert_eq!(synthetic.len(), 7);
+    assert_eq!(to_text(&source), ".VERSION 1.0\n.DT 5\n.DATA_ID x\n");
+    assert!(source.iter().all(|statement| !statement.synthetic));
+    let real: Vec<_> = synthetic.iter().filter(|statement| !statement.synthetic).cloned().collect();
+    assert_eq!(format!("{source:?}"), format!("{real:?}"));
+    let lazy = statements::parse_iter_with_options(&top, &without)
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(format!("{lazy:?}"), format!("{source:?}"));
+}
+
+fn file_name(statement: &Statement) -> String {
+    match statement.arguments.as_slice() {
+        [StatementArgument::String(name)] => name.to_string(),
+        _ => panic!("{statement:?}"),
+    }
+}
+
+#[test]
+fn include_markers_are_balanced() {
+    let dir = temp_dir();
+    std::fs::write(dir.join("top"), ".VERSION 1.0\n.INCLUDE mid\n.DATA_ID x\n").unwrap();
+    std::fs::write(dir.join("mid"), ".INCLUDE leaf\n.DT 3\n.INCLUDE leaf\n").unwrap();
+    std::fs::write(dir.join("leaf"), ".DT 5\n").unwrap();
+    let top = dir.join("top");
+    let options = ParseOptions::new().include_dir(&dir);
+    let lazy = statements::parse_iter_with_options(&top, &options)
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    for parsed in [statements::parse_with_options(&top, &options).unwrap(), lazy] {
+        let mut open = Vec::new();
+        let mut begun = Vec::new();
+        for statement in parsed.iter().filter(|statement| statement.synthetic) {
+            match statement.keyword {
+                Keyword::Include => {
+                    open.push(file_name(statement));
+                    begun.push(file_name(statement));
+                }
+                Keyword::EndOfInput => assert_eq!(open.pop(), Some(file_name(statement))),
+                _ => panic!("{statement:?}"),
+            }
+        }
+        assert!(open.is_empty());
+        let files: Vec<_> = ["top", "mid", "leaf", "leaf"]
+            .iter()
+            .map(|name| dir.join(name).to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(begun, files);
+        assert_eq!(parsed.len(), 13);
+    }
+}
+
+#[test]
+fn write_unipen_to_buffer() {
+    let parsed = statements::parse_str("memory", ".VERSION 1.0\n.DATA_ID x\n", None).unwrap();
+    let mut buffer = Vec::new();
+    statements::write_unipen(&parsed, &mut buffer).unwrap();
+    assert_eq!(
+        String::from_utf8(buffer).unwrap(),
+        ".COMMENT .INCLUDE memory\n.VERSION 1.0\n.DATA_ID x\n"
+    );
+    assert!(matches!(
+        statements::write_unipen(&parsed, FailingWriter),
+        Err(UniPenError::Io(_))
+    ));
+}
+
+#[test]
+fn parse_stats_count_statements_and_points() {
+    let dir = temp_dir();
+    std::fs::write(dir.join("top"), ".VERSION 1.0\n.COORD X Y T\n.INCLUDE pen\n.PEN_UP\n5 5 50\n").unwrap();
+    std::fs::write(dir.join("pen"), ".PEN_DOWN\n1 1 0\n2 2 10\n3 3 20\n.DT 5\n").unwrap();
+    let (parsed, stats) = statements::parse_with_stats(&dir.join("top"), &ParseOptions::new().include_dir(&dir)).unwrap();
+    assert_eq!(
+        stats,
+        ParseStats {
+            statement_count: 5,
+            include_count: 1,
+            max_include_depth: 1,
+            coordinate_count: 4,
+        }
+    );
+    assert_eq!(parsed.len(), 9);
+}
+
+#[test]
+fn validate_reports_every_error() {
+    let file = temp_file(
+        ".VERSION 1.0\n.VERSION 2.0\n.DATA_SOURCE s\n.COORD X Y\n.X_POINTS_PER_MM 10\n.X_POINTS_PER_INCH 100\n.WRITER_ID w\n\
+         .HAND left\n.AGE 300\n.START_BOX 5 5 1 1\n",
+    );
+    let errors = statements::validate(&file, &ParseOptions::new());
+    let messages: Vec<_> = errors.iter().map(ToString::to_string).collect();
+    assert_eq!(
+        messages,
+        [
+            "Validation error: Duplicate .VERSION statement (line 2, column 1)",
+            "Validation error: Unknown .HAND value \"left\" (line 8, column 1)",
+            "Validation error: Bounding box is inverted: (5, 5) to (1, 1) (line 10, column 1)",
+            "Validation error: .X_POINTS_PER_MM 10 disagrees with .X_POINTS_PER_INCH 100 (3.937007874015748 points per \
+             millimeter)",
+            "Validation error: .AGE 300 is outside of the plausible range 0..=150",
+            "Validation error: Missing mandatory .DATA_ID statement",
+            "Validation error: Missing mandatory .HIERARCHY statement",
+        ]
+    );
+
+    let file = temp_file(".VERSION 1.0\n.DATA_SOURCE s\n.DATA_ID i\n.COORD X Y T\n.HIERARCHY W\n.PEN_DOWN\n1 1 0\n");
+    assert!(statements::validate(&file, &ParseOptions::new()).is_empty());
+    // Grammar errors stop the check
+    let file = temp_file(".VERSION x\n.VERSION 1.0\n");
+    assert!(matches!(
+        statements::validate(&file, &ParseOptions::new())[..],
+        [UniPenError::PestRule(_)]
+    ));
+}
+
+// Only built when the crate is tested on its own, as the CLI enables the serde feature in the workspace
+#[cfg(not(feature = "serde"))]
+mod without_serde {
+    use unipen::error::UniPenError;
+    use unipen::model::UniPen;
+    use unipen::statements;
+
+    #[test]
+    fn parse_and_build() {
+        let source = ".VERSION 1.0\n.DATA_SOURCE s\n.DATA_ID i\n.COORD X Y T\n.HIERARCHY W\n.PEN_DOWN\n1 2 0\n";
+        let parsed = statements::parse_str("memory", source, None).unwrap();
+        let mut text = Vec::new();
+        statements::write_unipen(&parsed, &mut text).unwrap();
+        assert_eq!(
+            String::from_utf8(text).unwrap(),
+            format!(".COMMENT .INCLUDE memory\n{}", source.replace(".PEN_DOWN\n", ".PEN_DOWN "))
+        );
+        let unipen = UniPen::builder().statements(parsed).unwrap().build().unwrap();
+        assert_eq!(unipen.sets()[0].coordinates.len(), 1);
+        assert!(unipen.to_unipen_string().contains(".PEN_DOWN\n1 2 0\n"));
+        // The error variant for serialization exists without the feature
+        assert!(matches!(UniPenError::Serialize("failed".into()), UniPenError::Serialize(_)));
+    }
+}
+
+#[test]
+fn owned_and_borrowed_number_conversions() {
+    for number in [
+        Number::Integer(7),
+        Number::Integer(-3),
+        Number::Decimal(2.5),
+        Number::Decimal(4.0),
+        Number::Decimal(-1.0),
+    ] {
+        assert_eq!(i32::from(number), i32::from(&number));
+        assert_eq!(f64::from(number), f64::from(&number));
+        assert_eq!(usize::try_from(number).ok(), usize::try_from(&number).ok());
+    }
+    assert_eq!(i32::from(Number::Decimal(2.5)), 2);
+    assert_eq!(f64::from(Number::Integer(-3)), -3.0);
+    assert_eq!(usize::try_from(Number::Decimal(4.0)).unwrap(), 4);
+    assert!(matches!(
+        usize::try_from(Number::Decimal(2.5)),
+        Err(UniPenError::Validation(_))
+    ));
+}
+
+#[test]
+fn argument_iterators() {
+    let parsed = parse_text(".H_LINE 1 2.5 3\n.SEGMENT WORD 0:1 OK \"hi\"\n");
+    let numbers = parsed[1].number_args().collect::<Result<Vec<_>, _>>().unwrap();
+    assert_eq!(numbers, [1.0, 2.5, 3.0]);
+    assert_eq!(parsed[1].string_args().count(), 0);
+    assert_eq!(parsed[2].string_args().collect::<Vec<_>>(), ["WORD", "hi"]);
+    let numbers: Vec<_> = parsed[2].number_args().collect();
+    assert_eq!(numbers.len(), 4);
+    assert!(numbers.iter().all(Result::is_err));
+    assert!(
+        matches!(&numbers[0], Err(UniPenError::Translation(message)) if message.contains("expected a number, got WORD")),
+        "{:?}",
+        numbers[0]
+    );
+}
+
+#[test]
+fn pen_data_spans_lines() {
+    let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/multi_line_stroke.txt");
+    let parsed = statements::parse(&fixture, None).unwrap();
+    let pen_downs: Vec<_> = parsed
+        .iter()
+        .filter(|statement| matches!(statement.keyword, Keyword::PenDown))
+        .collect();
+    assert_eq!(pen_downs.len(), 1);
+    // The 50 points of the 10 lines, as X and Y values
+    let values: Vec<_> = pen_downs[0]
+        .arguments
+        .iter()
+        .map(|argument| match argument {
+            StatementArgument::Number(Number::Integer(value)) => *value,
+            _ => panic!("{argument:?}"),
+        })
+        .collect();
+    let expected: Vec<_> = (0..50).flat_map(|point| [point, point * 2]).collect();
+    assert_eq!(values, expected);
+
+    let crlf = statements::parse_str(
+        "crlf",
+        &std::fs::read_to_string(&fixture).unwrap().replace('\n', "\r\n"),
+        None,
+    )
+    .unwrap();
+    let pen_down = crlf
+        .iter()
+        .find(|statement| matches!(statement.keyword, Keyword::PenDown))
+        .unwrap();
+    assert_eq!(pen_down.arguments.len(), 100);
+
+    let unipen = UniPen::builder().statements(parsed).unwrap().build().unwrap();
+    let set = &unipen.sets()[0];
+    assert_eq!(set.points_for_component(0).map(<[_]>::len), Some(50));
+    assert_eq!(set.points_for_component(1).map(<[_]>::len), Some(1));
+    assert_eq!(set.coordinates[49].time, std::time::Duration::from_millis(490));
+}
+
+#[test]
+fn pairs_of_the_grammar() {
+    let text = format!("{HEAD}.PEN_DOWN\n0 0 0\n1 1 1\n.PEN_UP\n");
+    let rules: Vec<Rule> = statements::parse_pairs(&text).unwrap().map(|pair| pair.as_rule()).collect();
+    assert_eq!(rules.len(), 8);
+    assert_eq!(rules[5], Rule::s_pen_down);
+    assert_eq!(rules[6], Rule::s_pen_up);
+    assert_eq!(rules[7], Rule::EOI);
+    assert!(matches!(
+        statements::parse_pairs(".NOT_A_KEYWORD\n"),
+        Err(UniPenError::PestRule(_))
+    ));
+}
+
+#[test]
+fn rules_of_the_grammar_are_public() {
+    let pair = StatementParser::parse(Rule::s_dt, ".DT 5\n").unwrap().next().unwrap();
+    assert_eq!(pair.as_rule(), Rule::s_dt);
+    let inner: Vec<Rule> = pair.into_inner().map(|pair| pair.as_rule()).collect();
+    assert!(matches!(inner.as_slice(), [Rule::t_number]));
+    assert!(StatementParser::parse(Rule::s_dt, ".PEN_UP\n").is_err());
+}
+
+#[test]
+fn file_size_limit() {
+    let dir = temp_dir();
+    let main = dir.join("main.dat");
+    std::fs::write(&main, format!("{HEAD}.INCLUDE inc.dat\n")).unwrap();
+    // The included file is the larger one
+    std::fs::write(dir.join("inc.dat"), format!(".PEN_DOWN\n{}", "0 0 0\n".repeat(30))).unwrap();
+    let size = std::fs::metadata(&main).unwrap().len();
+    let included = std::fs::metadata(dir.join("inc.dat")).unwrap().len();
+    let options = |limit| ParseOptions::new().include_dir(&dir).max_file_bytes(limit);
+
+    let result = statements::parse_with_options(&main, &options(size - 1));
+    assert!(matches!(result, Err(UniPenError::FileTooLarge { size: found, limit, .. }) if found == size && limit == size - 1));
+    let result = statements::parse_with_options(&main, &options(size));
+    assert!(matches!(result, Err(UniPenError::FileTooLarge { size: found, .. }) if found == included));
+    assert!(statements::parse_with_options(&main, &options(included)).is_ok());
+
+    // The streaming parser limits the total size of the files
+    let last = statements::parse_iter_with_options(&main, &options(included)).last().unwrap();
+    assert!(matches!(last, Err(UniPenError::FileTooLarge { size: found, .. }) if found == size + included));
+    assert!(statements::parse_iter_with_options(&main, &options(size + included)).all(|statement| statement.is_ok()));
+}
+
+#[test]
+fn labels_with_and_without_escapes() {
+    let text = format!("{HEAD}.LEXICON \"plain word\" \"a\\tb\" \"q\\\"uote\" \"back\\\\slash\" \"tab\there\"\n");
+    let labels: Vec<String> = parse_text(&text)
+        .into_iter()
+        .filter(|statement| matches!(statement.keyword, Keyword::Lexicon))
+        .flat_map(|statement| statement.arguments)
+        .filter_map(|argument| match argument {
+            StatementArgument::Label(label) => Some(label.to_string()),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(labels, ["plain word", "a\tb", "q\"uote", "back\\slash", "tab here"]);
+}
+
+#[test]
+fn labels_unescape_and_escape() {
+    assert_eq!(&*unescape_label("\"a\\nb\""), "a\nb");
+    assert_eq!(&*unescape_label("\"a\\tb\""), "a\tb");
+    assert_eq!(&*unescape_label("\"a\\\\b\""), "a\\b");
+    assert_eq!(&*unescape_label("\"a\tb\r\nc  d\""), "a b c  d");
+    assert_eq!(&*unescape_label("\"plain\""), "plain");
+    for text in ["x\ny", "t\tq", "q\"\\", "plain words"] {
+        assert_eq!(&*unescape_label(&escape_label(text)), text);
+    }
+}
+
+#[test]
+fn line_endings() {
+    let fixture = std::fs::read_to_string(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/crlf.txt")).unwrap();
+    assert!(fixture.contains("\r\n"));
+    let displayed = |text: &str| {
+        // Skips the synthetic include, which names the temporary file
+        parse_text(text)
+            .iter()
+            .skip(1)
+            .map(|statement| statement.to_string())
+            .collect::<Vec<_>>()
+    };
+    let crlf = displayed(&fixture);
+    assert!(crlf.iter().all(|statement| !statement.contains('\r')), "{crlf:?}");
+    assert!(crlf.contains(&".SEGMENT WORD 0 \"two lines\"".to_string()), "{crlf:?}");
+    assert_eq!(crlf, displayed(&fixture.replace("\r\n", "\n")));
+    assert_eq!(crlf, displayed(&fixture.replace("\r\n", "\r")));
+}
+
+#[test]
+fn lenient_custom_keywords() {
+    let file = temp_file(format!(
+        "{HEAD}.KEYWORD VENDOR_TILT . some doc\n.PEN_DOWN\n0 0 0\n.VENDOR_TILT 12 34\n.PEN_UP\n1 1 1\n"
+    ));
+    assert!(matches!(statements::parse(&file, None), Err(UniPenError::PestRule(_))));
+    let options = ParseOptions::new().lenient_keywords(true);
+    let parsed = statements::parse_with_options(&file, &options).unwrap();
+    let custom: Vec<_> = parsed
+        .iter()
+        .filter(|statement| matches!(&statement.keyword, Keyword::Custom(name) if &**name == "VENDOR_TILT"))
+        .collect();
+    assert_eq!(custom.len(), 1);
+    assert_eq!(custom[0].to_string(), ".VENDOR_TILT 12 34");
+    assert_eq!(custom[0].span, Some((9, 1)));
+    let streamed = statements::parse_iter_with_options(&file, &options)
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(format!("{streamed:?}"), format!("{parsed:?}"));
+    // The builder skips custom statements
+    let unipen = UniPen::builder().statements(parsed).unwrap().build().unwrap();
+    assert_eq!(unipen.sets()[0].components.len(), 2);
+
+    let file = temp_file(format!("{HEAD}.PEN_DOWN\n0 0 0\n.OTHER 1\n"));
+    let error = statements::parse_with_options(&file, &options).err().unwrap().to_string();
+    assert!(error.contains("Undeclared keyword .OTHER (line 8, column 1)"), "{error}");
+    let error = statements::parse_iter_with_options(&file, &options)
+        .find_map(Result::err)
+        .unwrap()
+        .to_string();
+    assert!(error.contains("Undeclared keyword .OTHER"), "{error}");
+
+    // Known keywords with invalid arguments are still errors
+    let file = temp_file(format!("{HEAD}.DT abc\n"));
+    let error = statements::parse_with_options(&file, &options).err().unwrap().to_string();
+    assert!(error.contains("Invalid .DT statement (line 6, column 1)"), "{error}");
+}
+
+#[test]
+fn custom_keywords_round_trip() {
+    let statement = Statement {
+        keyword: Keyword::Custom("VENDOR_X".into()),
+        arguments: vec![StatementArgument::FreeText(" a b".into())],
+        synthetic: false,
+        span: None,
+    };
+    let text = format!("{HEAD}.KEYWORD VENDOR_X\n{statement}\n");
+    assert!(text.ends_with("\n.VENDOR_X a b\n"), "{text}");
+    let options = ParseOptions::new().lenient_keywords(true).synthetic_includes(false);
+    let parsed = statements::parse_with_options(&temp_file(&text), &options).unwrap();
+    let last = parsed.last().unwrap();
+    assert!(matches!(&last.keyword, Keyword::Custom(name) if &**name == "VENDOR_X"));
+    assert_eq!(last.to_string(), statement.to_string());
+    // Names only parse to the keywords of the grammar
+    assert!(".VENDOR_X".parse::<Keyword>().is_err());
+}
+
+fn included_names(parsed: &[Statement]) -> Vec<String> {
+    parsed
+        .iter()
+        .filter(|statement| statement.synthetic && matches!(statement.keyword, Keyword::Include))
+        .map(file_name)
+        .collect()
+}
+
+fn comments(parsed: &[Statement]) -> Vec<String> {
+    parsed
+        .iter()
+        .filter(|statement| matches!(statement.keyword, Keyword::Comment))
+        .filter_map(|statement| statement.string_args().next().map(|text| text.trim().to_string()))
+        .collect()
+}
+
+#[test]
+fn includes_relative_to_the_including_file() {
+    let dir = temp_dir();
+    let include = dir.join("incdir");
+    std::fs::create_dir_all(dir.join("sub")).unwrap();
+    std::fs::create_dir_all(&include).unwrap();
+    std::fs::write(
+        dir.join("top.dat"),
+        format!("{HEAD}.INCLUDE sub/mid.dat\n.INCLUDE shared.dat\n"),
+    )
+    .unwrap();
+    std::fs::write(dir.join("sub/mid.dat"), ".COMMENT mid\n.INCLUDE leaf.dat\n").unwrap();
+    std::fs::write(dir.join("sub/leaf.dat"), ".COMMENT leaf\n").unwrap();
+    std::fs::write(include.join("shared.dat"), ".COMMENT shared\n").unwrap();
+
+    // The leaf is only next to the file including it, and the shared file only in the include directory
+    let parsed = statements::parse(&dir.join("top.dat"), Some(&include)).unwrap();
+    assert_eq!(comments(&parsed), ["mid", "leaf", "shared"]);
+    let names = included_names(&parsed);
+    assert!(Path::new(&names[2]).ends_with("sub/leaf.dat"), "{names:?}");
+    assert!(Path::new(&names[3]).ends_with("incdir/shared.dat"), "{names:?}");
+    let streamed = statements::parse_iter(&dir.join("top.dat"), Some(&include))
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(comments(&streamed), ["mid", "leaf", "shared"]);
+
+    // Without an include directory, relative includes still resolve, and a missing include is an I/O error
+    let error = statements::parse(&dir.join("top.dat"), None).err().unwrap();
+    assert!(
+        matches!(&error, UniPenError::Io(error) if error.kind() == std::io::ErrorKind::NotFound),
+        "{error}"
+    );
+    std::fs::write(dir.join("top2.dat"), format!("{HEAD}.INCLUDE sub/mid.dat\n")).unwrap();
+    assert_eq!(
+        comments(&statements::parse(&dir.join("top2.dat"), None).unwrap()),
+        ["mid", "leaf"]
+    );
+
+    // The leaf is not in the include directory
+    let options = ParseOptions::new().include_dir(&dir).include_dir_only(true);
+    assert!(matches!(
+        statements::parse_with_options(&dir.join("top2.dat"), &options),
+        Err(UniPenError::Io(_))
+    ));
+    let options = ParseOptions::new().include_dir_only(true);
+    assert!(matches!(
+        statements::parse_with_options(&dir.join("top2.dat"), &options),
+        Err(UniPenError::MissingInclude)
+    ));
+}
+
+#[test]
+fn absolute_includes() {
+    let dir = temp_dir();
+    let leaf = temp_dir().join("leaf.dat");
+    std::fs::write(&leaf, ".COMMENT absolute leaf\n").unwrap();
+    std::fs::write(dir.join("top.dat"), format!("{HEAD}.INCLUDE {}\n", leaf.display())).unwrap();
+    for options in [
+        ParseOptions::new(),
+        ParseOptions::new().include_dir(&dir),
+        ParseOptions::new().include_dir(&dir).include_dir_only(true),
+    ] {
+        let parsed = statements::parse_with_options(&dir.join("top.dat"), &options).unwrap();
+        assert_eq!(included_names(&parsed)[1], leaf.to_string_lossy());
+        assert_eq!(comments(&parsed), ["absolute leaf"]);
+    }
+}
+
+#[test]
+fn glob_includes() {
+    let dir = temp_dir();
+    std::fs::create_dir_all(dir.join("data")).unwrap();
+    std::fs::write(dir.join("data/b.dat"), ".COMMENT b\n").unwrap();
+    std::fs::write(dir.join("data/a.dat"), ".COMMENT a\n").unwrap();
+    std::fs::write(dir.join("data/c.txt"), ".COMMENT c\n").unwrap();
+    let top = dir.join("top.dat");
+    std::fs::write(
+        &top,
+        format!("{HEAD}.INCLUDE data/*.dat\n.COMMENT after\n.INCLUDE d?ta/a.*\n"),
+    )
+    .unwrap();
+
+    // Matched files are included in sorted order
+    let options = ParseOptions::new().include_globs(IncludeGlobs::Expand);
+    let parsed = statements::parse_with_options(&top, &options).unwrap();
+    assert_eq!(comments(&parsed), ["a", "b", "after", "a"]);
+    let streamed = statements::parse_iter_with_options(&top, &options)
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(format!("{streamed:?}"), format!("{parsed:?}"));
+    assert!(UniPen::builder().statements(parsed).unwrap().build().is_ok());
+    // By default, wildcards are part of the file name
+    assert!(matches!(statements::parse(&top, None), Err(UniPenError::Io(_))));
+
+    let none = dir.join("none.dat");
+    std::fs::write(&none, format!("{HEAD}.INCLUDE nothing/*.dat\n.COMMENT x\n")).unwrap();
+    assert_eq!(comments(&statements::parse_with_options(&none, &options).unwrap()), ["x"]);
+    let strict = ParseOptions::new().include_globs(IncludeGlobs::ExpandStrict);
+    assert!(statements::parse_with_options(&none, &strict).is_err());
+    assert!(statements::parse_iter_with_options(&none, &strict).any(|statement| statement.is_err()));
+
+    // Globs fall back to the include directory
+    std::fs::create_dir_all(dir.join("sub")).unwrap();
+    std::fs::write(dir.join("sub/top.dat"), format!("{HEAD}.INCLUDE data/*.dat\n")).unwrap();
+    let parsed = statements::parse_with_options(&dir.join("sub/top.dat"), &options.include_dir(&dir)).unwrap();
+    assert_eq!(comments(&parsed), ["a", "b"]);
+}
+
+/// Counts how often each included file is read.
+#[derive(Default)]
+struct CountingResolver(RefCell<HashMap<String, usize>>);
+
+impl CountingResolver {
+    fn reads(&self, path: &str) -> usize {
+        self.0.borrow().get(path).copied().unwrap_or_default()
+    }
+}
+
+impl IncludeResolver for CountingResolver {
+    fn resolve(&self, path: &str) -> Result<String, UniPenError> {
+        *self.0.borrow_mut().entry(path.to_string()).or_default() += 1;
+        Ok(match path {
+            "header" => ".COMMENT header\n.INCLUDE inner\n".into(),
+            "inner" => ".COMMENT inner\n".into(),
+            "mid" => ".INCLUDE header\n".into(),
+            _ => ".PEN_DOWN\n1 2 3\n".into(),
+        })
+    }
+}
+
+#[test]
+fn repeated_includes_are_read_once() {
+    let text = format!("{HEAD}.INCLUDE header\n.INCLUDE data\n.INCLUDE header\n.INCLUDE mid\n");
+    let resolver = CountingResolver::default();
+    let parsed = statements::parse_str("main", &text, Some(&resolver)).unwrap();
+    assert_eq!(resolver.reads("header"), 3);
+    let resolver = CountingResolver::default();
+    let options = ParseOptions::new().reuse_includes(true);
+    let reused = statements::parse_str_with_options("main", &text, Some(&resolver), &options).unwrap();
+    assert_eq!(resolver.reads("header"), 1);
+    assert_eq!(resolver.reads("inner"), 1);
+    assert_eq!(resolver.reads("mid"), 1);
+    assert_eq!(format!("{reused:?}"), format!("{parsed:?}"));
+
+    // A file first included at a shallower depth is parsed again when its includes would be too deep
+    let text = format!("{HEAD}.INCLUDE header\n.INCLUDE mid\n");
+    let resolver = CountingResolver::default();
+    let options = ParseOptions::new().reuse_includes(true);
+    assert!(statements::parse_str_with_options("main", &text, Some(&resolver), &options.clone().max_depth(2)).is_err());
+    assert!(statements::parse_str_with_options("main", &text, Some(&resolver), &options.max_depth(3)).is_ok());
+
+    // Files included through different relative paths are recognized, and reusing them gives the same statements and
+    // counts, but with the names of the first inclusion
+    let dir = temp_dir();
+    std::fs::create_dir_all(dir.join("sub")).unwrap();
+    std::fs::write(
+        dir.join("top.dat"),
+        format!("{HEAD}.INCLUDE h.dat\n.INCLUDE sub/m.dat\n.INCLUDE ./h.dat\n"),
+    )
+    .unwrap();
+    std::fs::write(dir.join("h.dat"), ".PEN_DOWN\n1 2 3\n4 5 6\n.INCLUDE sub/i.dat\n").unwrap();
+    std::fs::write(dir.join("sub/m.dat"), ".INCLUDE ../h.dat\n").unwrap();
+    std::fs::write(dir.join("sub/i.dat"), ".DT 5\n").unwrap();
+    let (parsed, stats) = statements::parse_with_stats(&dir.join("top.dat"), &ParseOptions::new()).unwrap();
+    let options = ParseOptions::new().reuse_includes(true);
+    let (reused, reused_stats) = statements::parse_with_stats(&dir.join("top.dat"), &options).unwrap();
+    assert_eq!(reused_stats, stats);
+    assert_eq!(reused.len(), parsed.len());
+    let path = |name: &str| dir.join(name).to_string_lossy().into_owned();
+    let first = [path("top.dat"), path("h.dat"), path("sub/i.dat"), path("sub/m.dat")];
+    assert_eq!(
+        included_names(&parsed)[4..],
+        [
+            path("sub/../h.dat"),
+            path("sub/../sub/i.dat"),
+            path("./h.dat"),
+            path("sub/i.dat")
+        ]
+    );
+    assert_eq!(
+        included_names(&reused)[4..],
+        [path("h.dat"), path("sub/i.dat"), path("h.dat"), path("sub/i.dat")]
+    );
+    assert_eq!(included_names(&parsed)[..4], first);
+    assert_eq!(included_names(&reused)[..4], first);
+    let set_names = |parsed: Vec<Statement>| {
+        let unipen = UniPen::builder().statements(parsed).unwrap().build().unwrap();
+        unipen.sets().iter().map(|set| set.name.to_string()).collect::<Vec<_>>()
+    };
+    assert_eq!(set_names(parsed), [path("h.dat"), path("sub/../h.dat"), path("./h.dat")]);
+    assert_eq!(set_names(reused), [path("h.dat"), path("h.dat"), path("h.dat")]);
+}
@@ -0,0 +1,66 @@
+mod common;
+
+use common::{build, build_with, rebuild};
+
+const HEAD_XY: &str = ".VERSION 1.0\n.DATA_SOURCE src\n.DATA_ID id\n.COORD X Y\n.HIERARCHY WORD\n";
+
+#[test]
+fn unipen_text_round_trips() {
+    let unipen = build(
+        ".REC_SOURCE rs\n.REC_ID ri\n.REC_INFO some info\n.TRAINING_SET A B WORD 0-1\n.START_SET s1\n\
+         .PEN_DOWN 1 2 30 1.5 2 41\n.DT 7\n.PEN_UP 3 3 50\n.SEGMENT WORD 0 OK \"a \\\"q\\\" b\"\n.SEGMENT WORD 0:1-1\n\
+         .START_BOX 0 0 5 5 0:0\n.START_SET s2\n.PEN_DOWN 9 9 60\n\
+         .REC_LABELS WORD 0-1 ACCEPT \"x\"\n.REC_SCORES WORD 2 -1.25 \"y\"\n",
+    )
+    .unwrap();
+    let text = unipen.to_unipen_string();
+    let again = rebuild(&unipen);
+    assert!(again == unipen);
+    assert_eq!(again.to_unipen_string(), text);
+    assert_eq!(again.sets()[0].segments[0].label.as_deref(), Some("a \"q\" b"));
+}
+
+#[test]
+fn times_in_points_per_second() {
+    let unipen = build(".POINTS_PER_SECOND 200\n.PEN_DOWN\n1 2 30\n1 2 41\n.DT 5\n").unwrap();
+    let text = unipen.to_unipen_string();
+    assert!(text.contains(".POINTS_PER_SECOND 200\n"), "{text}");
+    assert!(text.contains(".PEN_DOWN\n1 2 30\n1 2 41\n.DT 5\n"), "{text}");
+    assert!(rebuild(&unipen) == unipen);
+}
+
+#[test]
+fn times_without_time_coordinate() {
+    let unipen = build_with(
+        HEAD_XY,
+        ".POINTS_PER_SECOND 100\n.PEN_DOWN\n1 2\n1 2\n1 2\n.DT 5\n.PEN_UP\n3 4\n",
+    )
+    .unwrap();
+    let text = unipen.to_unipen_string();
+    assert!(text.contains(".COORD X Y\n"), "{text}");
+    assert!(text.contains(".PEN_DOWN\n1 2\n1 2\n1 2\n.DT 5\n.PEN_UP\n3 4\n"), "{text}");
+    let again = rebuild(&unipen);
+    assert!(again == unipen);
+    let millis: Vec<_> = again.sets()[0].coordinates.iter().map(|c| c.time.as_millis()).collect();
+    assert_eq!(millis, [0, 10, 20, 80]);
+}
+
+#[test]
+fn recognizer_time_is_not_written() {
+    let unipen = build(".PEN_DOWN 1 2 3\n.REC_TIME 0 250\n").unwrap();
+    assert!(!unipen.to_unipen_string().contains(".REC_TIME"));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn absent_coordinate_types_are_left_out() {
+    let head = ".VERSION 1.0\n.DATA_SOURCE src\n.DATA_ID id\n.COORD X Y T P\n.HIERARCHY WORD\n";
+    let unipen = build_with(head, ".PEN_DOWN\n1 2 0 5\n3 4 10 6\n").unwrap();
+    assert!(unipen.to_unipen_string().contains(".COORD X Y T P\n.HIERARCHY WORD\n"));
+    let mut json = serde_json::to_value(&unipen).unwrap();
+    json["sets"][0]["coordinates"][1]["pressure"] = serde_json::Value::Null;
+    let unipen: unipen::model::UniPen = serde_json::from_value(json).unwrap();
+    let text = unipen.to_unipen_string();
+    assert!(text.contains(".COORD X Y T\n"), "{text}");
+    assert!(text.contains(".PEN_DOWN\n1 2 0\n3 4 10\n"), "{text}");
+}